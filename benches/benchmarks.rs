@@ -1,208 +1,287 @@
+use clap::Parser;
 use criterion::{
     criterion_group, criterion_main, AxisScale, BenchmarkId, Criterion, PlotConfiguration,
 };
-use itertools::Itertools;
-use rayon::prelude::*;
+use rsfluid::log::*;
+use rsfluid::plotting::{render_grid, Interpolation, Renderer};
+use rsfluid::scene::cell::CellTypes;
+use rsfluid::scene::grid::{CellGetter, Grid};
+use rsfluid::scene::setup::{setup_scene, CLIArgs};
+use rsfluid::scene::timestepper::{ExecutionMode, Integrate};
+use rsfluid::scene::visualization::{render_grid_plots, PlotParamsBuilder};
+use rsfluid::types::*;
 use std::time::Duration;
-type Index2 = nalgebra::Vector2<usize>;
 
-#[macro_export]
-macro_rules! idx {
-    ($x:expr, $($y:expr),+ ) => {
-        Index2::new($x, $($y),+)
-    };
-}
+const DENSITY: Scalar = 1000.0;
+const DT: Scalar = 0.016;
+const ITERATIONS: u64 = 40;
 
+/// Array-of-structs cell layout (the layout before the structure-of-arrays
+/// fields in `GridFields`) as a reference for the solver benchmark.
 #[derive(Clone, Debug)]
-pub struct C {
-    pub i: usize,
+pub struct CellAoS {
     pub index: Index2,
+    pub mode: CellTypes,
+    pub velocity: FrontBackBuffer<Vector2>,
+    pub pressure: Scalar,
+    pub smoke: FrontBackBuffer<Scalar>,
+    pub div: Scalar,
+    pub s_tot_inv: Scalar,
+    pub s_nbs: [Vector2; 2],
 }
 
-#[derive(Clone, Debug)]
-pub struct G {
-    pub cells: Vec<C>,
+pub struct GridAoS {
+    pub cells: Vec<CellAoS>,
     pub dim: Index2,
+    pub cell_width: Scalar,
 }
 
-pub struct PosStencilMut<'a, T> {
-    cell: &'a mut T,
-    neighbors: [Option<&'a mut T>; 2],
-}
-
-impl<'a, T> PosStencilMut<'a, T> {
-    #[inline(always)]
-    fn get_neighbors<'cell>(cell: *mut T, dim: Index2, index: Index2) -> [Option<&'cell mut T>; 2] {
-        assert!(dim.x >= 1 && dim.y >= 1);
-
-        let mut nbs = [None, None];
+impl GridAoS {
+    fn from(grid: &Grid) -> GridAoS {
+        let cells = grid
+            .iter_index()
+            .map(|idx| {
+                let c = grid.cell(idx);
+                CellAoS {
+                    index: idx,
+                    mode: c.mode(),
+                    velocity: FrontBackBuffer {
+                        front: c.velocity(),
+                        back: c.velocity(),
+                    },
+                    pressure: c.pressure(),
+                    smoke: FrontBackBuffer {
+                        front: c.smoke(),
+                        back: c.smoke(),
+                    },
+                    div: c.div(),
+                    s_tot_inv: 0.0,
+                    s_nbs: [Vector2::zeros(), Vector2::zeros()],
+                }
+            })
+            .collect();
+
+        return GridAoS {
+            cells,
+            dim: grid.dim,
+            cell_width: grid.cell_width,
+        };
+    }
 
-        for dir in 0..2 {
-            if index[dir] >= dim[dir] - 1 {
-                // No neighbor possible.
-                continue;
+    /// The sequential Gauss-Seidel pressure solve on the array-of-structs layout.
+    fn solve_incompressibility(&mut self, dt: Scalar, iterations: u64, density: Scalar) {
+        self.cells.iter_mut().for_each(|c| c.pressure = 0.0);
+
+        let r = 1.9; // Overrelaxation factor.
+        let cp = density * self.cell_width / dt;
+        let dim_x = self.dim.x;
+
+        for _iter in 0..iterations {
+            for j in 1..self.dim.y - 1 {
+                for i in (1..dim_x - 1).map(|i| i + j * dim_x) {
+                    if self.cells[i].mode == CellTypes::Solid {
+                        continue;
+                    }
+
+                    let s_factor = |i: usize| {
+                        return if self.cells[i].mode == CellTypes::Solid {
+                            0.0
+                        } else {
+                            1.0
+                        };
+                    };
+
+                    let nbs = [[i - 1, i - dim_x], [i + 1, i + dim_x]];
+                    let s_nbs = [
+                        vec2!(s_factor(nbs[0][0]), s_factor(nbs[0][1])),
+                        vec2!(s_factor(nbs[1][0]), s_factor(nbs[1][1])),
+                    ];
+                    let s = s_nbs[0].sum() + s_nbs[1].sum();
+                    if s == 0.0 {
+                        continue;
+                    }
+
+                    let mut div = 0.0;
+                    for dir in 0..2 {
+                        div += self.cells[nbs[1][dir]].velocity.back[dir]
+                            - self.cells[i].velocity.back[dir];
+                    }
+
+                    let c = &mut self.cells[i];
+                    c.div = div;
+
+                    let div_normed = div / s;
+                    c.pressure -= cp * div_normed;
+                    c.velocity.back += s_nbs[0] * r * div_normed;
+
+                    self.cells[nbs[1][0]].velocity.back.x -= r * s_nbs[1].x * div_normed;
+                    self.cells[nbs[1][1]].velocity.back.y -= r * s_nbs[1].y * div_normed;
+                }
             }
-
-            // For x-direction : offset = 1, for y-direction: offset = dim[0],
-            // general: for n-direction: offset = dim[0]*dim[1]*...*dim[n-1]
-            let offset = dim.iter().take(dir).fold(1, std::ops::Mul::mul);
-            nbs[dir] = Some(unsafe { &mut *cell.add(offset) });
         }
-
-        return nbs;
     }
 }
 
-pub trait PosStencil<'a, T: 'a> {
-    fn positive_stencils_mut(
-        &'a mut self,
-        dim: Index2,
-        min: Option<Index2>,
-        max: Option<Index2>,
-    ) -> Box<dyn Iterator<Item = PosStencilMut<'a, T>> + '_>;
+fn solve_grid(log: &Logger, grid: &mut Grid, mode: ExecutionMode) {
+    grid.solve_incompressibility(log, DT, ITERATIONS, DENSITY, mode);
 }
 
-impl<'a, T: 'a> PosStencil<'a, T> for Vec<T> {
-    fn positive_stencils_mut(
-        &'a mut self,
-        dim: Index2,
-        min: Option<Index2>,
-        max: Option<Index2>,
-    ) -> Box<dyn Iterator<Item = PosStencilMut<'a, T>> + '_> {
-        return Box::new(positive_stencils_mut(self.as_mut_slice(), dim, min, max));
-    }
-}
+fn criterion_benchmark(c: &mut Criterion) {
+    let (log, switch) = create_logger();
+    switch.disable();
 
-fn positive_stencils_mut<T>(
-    data: &mut [T],
-    dim: Index2,
-    min: Option<Index2>,
-    max: Option<Index2>,
-) -> impl Iterator<Item = PosStencilMut<'_, T>> {
-    assert!(
-        dim > idx!(0, 0) && dim.iter().fold(1, std::ops::Mul::mul) == data.len(),
-        "Wrong dimensions."
-    );
-
-    let min = min.unwrap_or(Index2::zeros());
-    let max = max.unwrap_or(dim - idx!(1, 1));
-
-    assert!(min >= Index2::zeros() && max < dim);
-
-    return (min[0]..max[0])
-        .step_by(2)
-        .cartesian_product((min[1]..max[1]).step_by(2))
-        .map(move |(i, j)| {
-            let index = idx!(i, j);
-            let cell: *mut T = &mut data[index[0] + index[1] * dim[0]];
-
-            // Here the unsafe part happens.
-            // Get two non-aliasing mutable references for the neighbors.
-            return PosStencilMut {
-                cell: unsafe { &mut *cell },
-                neighbors: PosStencilMut::get_neighbors::<'_>(cell, dim, index),
-            };
-        });
-}
+    let mut group = c.benchmark_group("Solver AoS vs. SoA");
+    group.measurement_time(Duration::from_secs(10));
 
-fn test() {
-    let mut v = vec![1, 2, 3, 4];
-    let s = positive_stencils_mut(v.as_mut_slice(), idx!(2, 2), None, None)
-        .next()
-        .unwrap();
-    // drop(v); // This should invalidate the life-time of `s` but it does not???
-    *s.cell += 3;
-}
+    for dim in ["100,50", "200,100", "400,200"] {
+        let cli = CLIArgs::parse_from(["rsfluid", "--dim", dim]);
+        let mut timestepper = setup_scene::<Scalar>(&log, &cli).expect("Scene setup failed.");
 
-// Safety: `PosStencilMut` can only be created by `pos_stencils_mut`
-// which guarantees non-aliased mutable references.
-// Therefore it can safely be transferred to another thread.
-unsafe impl<'a, T> Send for PosStencilMut<'a, T> {}
-
-// For PosStencilMut to be Sync we have to enforce that you can't write to something stored
-// in a &PosStencilMut while that same something could be read or written to from another &PosStencilMut.
-// Since you need an &mut PosStencilMut to write to the pointer, and the borrow checker enforces that
-// mutable references must be exclusive, there are no soundness issues making PosStencilMut sync either.
-unsafe impl<'a, T> Sync for PosStencilMut<'a, T> {}
-
-#[inline(always)]
-fn stencil_compute(stencil: &mut PosStencilMut<'_, C>) {
-    stencil.cell.i += match stencil.neighbors[0].as_deref() {
-        Some(c) => c.i,
-        None => 0,
-    } + match stencil.neighbors[1].as_deref() {
-        Some(c) => c.i,
-        None => 0,
-    };
-
-    if let Some(n) = stencil.neighbors[0].as_deref_mut() {
-        n.i += stencil.cell.i;
-    }
-}
+        // Run some steps to get a developed flow.
+        for _ in 0..5 {
+            timestepper.compute_step(DT);
+        }
 
-fn run_grid_parallel(n: usize, grid: &mut G) {
-    let mut stencils: Vec<_> =
-        positive_stencils_mut(grid.cells.as_mut_slice(), grid.dim, None, None).collect();
+        let grid = timestepper.objects[0]
+            .as_any_mut()
+            .downcast_mut::<Grid>()
+            .expect("Not a grid");
 
-    for _ in 0..n {
-        stencils
-            .par_iter_mut()
-            .for_each(|stencil| stencil_compute(stencil))
-    }
-}
+        let mut grid_aos = GridAoS::from(grid);
+
+        group.bench_with_input(BenchmarkId::new("AoS-Single", dim), dim, |b, _| {
+            b.iter(|| grid_aos.solve_incompressibility(DT, ITERATIONS, DENSITY))
+        });
+
+        group.bench_with_input(BenchmarkId::new("SoA-Single", dim), dim, |b, _| {
+            b.iter(|| solve_grid(&log, grid, ExecutionMode::Single))
+        });
 
-fn run_grid_single(n: usize, grid: &mut G) {
-    let mut stencils: Vec<_> = grid
-        .cells
-        .positive_stencils_mut(grid.dim, None, None)
-        .collect();
+        group.bench_with_input(BenchmarkId::new("SoA-Parallel", dim), dim, |b, _| {
+            b.iter(|| solve_grid(&log, grid, ExecutionMode::Parallel))
+        });
 
-    for _ in 0..n {
-        stencils
-            .iter_mut()
-            .for_each(|stencil| stencil_compute(stencil))
+        group.bench_with_input(BenchmarkId::new("SoA-ParallelUnsafe", dim), dim, |b, _| {
+            b.iter(|| solve_grid(&log, grid, ExecutionMode::ParallelUnsafe))
+        });
     }
-}
 
-fn criterion_benchmark(c: &mut Criterion) {
-    let mut dim = idx!(100, 100);
+    let plot_config = PlotConfiguration::default().summary_scale(AxisScale::Logarithmic);
+    group.plot_config(plot_config);
+    group.finish();
+}
 
-    let mut dim2 = idx!(100, 100);
+fn tiled_benchmark(c: &mut Criterion) {
+    let (log, switch) = create_logger();
+    switch.disable();
 
-    let i = 4;
-    let a = &dim;
-    let b: &mut Index2 = &mut (*a);
-    b.x = 3;
+    let mut group = c.benchmark_group("Solver tiled");
+    group.sample_size(10);
+    group.measurement_time(Duration::from_secs(20));
 
-    let cell_generator = (0..dim[0]).cartesian_product(0..dim[1]).map(|(i, j)| C {
-        i: i + j,
-        index: idx!(i, j),
-    });
+    for dim in ["400,200", "1000,500", "2000,1000", "4000,2000"] {
+        let cli = CLIArgs::parse_from(["rsfluid", "--dim", dim, "--parallel"]);
+        let mut timestepper = setup_scene::<Scalar>(&log, &cli).expect("Scene setup failed.");
 
-    let mut grid = G {
-        cells: Vec::from_iter(cell_generator),
-        dim,
-    };
+        // Run some steps to get a developed flow.
+        for _ in 0..2 {
+            timestepper.compute_step(DT);
+        }
 
-    test();
+        let grid = timestepper.objects[0]
+            .as_any_mut()
+            .downcast_mut::<Grid>()
+            .expect("Not a grid");
 
-    let mut group = c.benchmark_group("Grid Single vs. Parallel");
-    for i in [10, 40, 100, 500, 1000].iter() {
-        group.bench_with_input(BenchmarkId::new("Single", i), i, |b, i| {
-            b.iter(|| run_grid_parallel(*i, &mut grid))
+        group.bench_with_input(BenchmarkId::new("Parallel", dim), dim, |b, _| {
+            b.iter(|| solve_grid(&log, grid, ExecutionMode::Parallel))
         });
 
-        group.bench_with_input(BenchmarkId::new("Parallel", i), i, |b, i| {
-            b.iter(|| run_grid_single(*i, &mut grid))
-        });
+        for (tile, sweeps) in [(64, 4), (128, 4), (128, 8)] {
+            let mode = ExecutionMode::ParallelTiled {
+                tile_size: idx!(tile, tile),
+                sweeps,
+            };
+            let name = format!("ParallelTiled-{}x{}-{}", tile, tile, sweeps);
+
+            group.bench_with_input(BenchmarkId::new(name, dim), dim, |b, _| {
+                b.iter(|| solve_grid(&log, grid, mode))
+            });
+        }
     }
 
     let plot_config = PlotConfiguration::default().summary_scale(AxisScale::Logarithmic);
     group.plot_config(plot_config);
+    group.finish();
+}
+
+fn plot_benchmark(c: &mut Criterion) {
+    let (log, switch) = create_logger();
+    switch.disable();
 
+    let mut group = c.benchmark_group("Plot renderer");
+    group.sample_size(10);
     group.measurement_time(Duration::from_secs(20));
+
+    let cli = CLIArgs::parse_from(["rsfluid", "--dim", "480,160", "--parallel"]);
+    let mut timestepper = setup_scene::<Scalar>(&log, &cli).expect("Scene setup failed.");
+
+    // Run some steps to get a developed flow.
+    for _ in 0..5 {
+        timestepper.compute_step(DT);
+    }
+
+    let grid = timestepper.objects[0]
+        .as_any()
+        .downcast_ref::<Grid>()
+        .expect("Not a grid");
+
+    // Precomputed cell colors to only measure the rasterization.
+    let cg = colorgrad::turbo();
+    let colors = grid
+        .iter_index()
+        .map(|idx| cg.at(to_float(grid.cell(idx).smoke())))
+        .collect::<Vec<_>>();
+    let get_color = |idx: Index2| colors[idx.x + idx.y * grid.dim.x].clone();
+
+    for size in [idx!(960, 320), idx!(1920, 640)] {
+        let name = format!("{}x{}", size.x, size.y);
+
+        for (renderer_name, renderer) in [
+            ("Plotters", Renderer::Plotters),
+            ("Direct-Nearest", Renderer::Direct(Interpolation::Nearest)),
+            ("Direct-Bilinear", Renderer::Direct(Interpolation::Bilinear)),
+        ] {
+            let params = PlotParamsBuilder::default()
+                .with_velocity(true)
+                .with_pressure(true)
+                .with_stats(true)
+                .size(size)
+                .renderer(renderer)
+                .build()
+                .unwrap();
+
+            group.bench_with_input(
+                BenchmarkId::new(format!("Plots-{}", renderer_name), &name),
+                &name,
+                |b, _| b.iter(|| render_grid_plots(grid, 0, &params).expect("Rendering failed.")),
+            );
+
+            group.bench_with_input(
+                BenchmarkId::new(format!("Raster-{}", renderer_name), &name),
+                &name,
+                |b, _| {
+                    b.iter(|| {
+                        render_grid(renderer, size, grid.dim, get_color, None)
+                            .expect("Rendering failed.")
+                    })
+                },
+            );
+        }
+    }
+
     group.finish();
 }
 
-criterion_group!(benches, criterion_benchmark);
+criterion_group!(benches, criterion_benchmark, tiled_benchmark, plot_benchmark);
 criterion_main!(benches);
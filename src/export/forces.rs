@@ -0,0 +1,186 @@
+use crate::log::*;
+use crate::plotting;
+use crate::plotting::{Chart, Series};
+use crate::scene::forces::{find_obstacles, force_coefficients, pressure_force};
+use crate::scene::grid::Grid;
+use crate::scene::timestepper::TimeStepper;
+use crate::types::*;
+
+use std::fs::File;
+use std::io::{BufWriter, Write};
+
+/// The force on one obstacle in one step.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ForceRecord {
+    pub step: u64,
+    pub t: f64,
+    pub obstacle: usize,
+    pub force: [f64; 2],
+    pub drag: f64,
+    pub lift: f64,
+}
+
+impl ForceRecord {
+    /// The records of all obstacles of the grid with the inflow speed `speed`.
+    pub fn from_grid<T: Float>(
+        grid: &Grid<T>,
+        step: u64,
+        t: T,
+        density: T,
+        speed: T,
+    ) -> Vec<ForceRecord> {
+        return find_obstacles(grid)
+            .iter()
+            .enumerate()
+            .map(|(k, obstacle)| {
+                let force = pressure_force(grid, obstacle);
+                let c = force_coefficients(force, density, speed, obstacle.size(grid.cell_width));
+
+                return ForceRecord {
+                    step,
+                    t: to_float(t),
+                    obstacle: k,
+                    force: [to_float(force.x), to_float(force.y)],
+                    drag: to_float(c.x),
+                    lift: to_float(c.y),
+                };
+            })
+            .collect();
+    }
+}
+
+const CSV_HEADER: &str = "step,t,obstacle,force_x,force_y,drag_coefficient,lift_coefficient";
+
+/// Records the forces on the obstacles of each step and writes them at the end
+/// as `<output>.csv` and the line charts of the coefficients `<output>.png`.
+pub struct ForceRecorder {
+    /// The output path without extension.
+    pub output: String,
+    /// The inflow speed to normalize the coefficients.
+    pub speed: Scalar,
+    pub records: Vec<ForceRecord>,
+}
+
+impl ForceRecorder {
+    pub fn new(output: String, speed: Scalar) -> Self {
+        return ForceRecorder {
+            output,
+            speed,
+            records: vec![],
+        };
+    }
+
+    pub fn record(&mut self, timestepper: &TimeStepper, step: u64) {
+        let grid = timestepper.objects[0]
+            .as_any()
+            .downcast_ref::<Grid>()
+            .expect("Not a grid");
+
+        self.records.extend(ForceRecord::from_grid(
+            grid,
+            step,
+            timestepper.t(),
+            timestepper.density(),
+            self.speed,
+        ));
+    }
+
+    pub fn write_csv(&self, w: &mut impl Write) -> GenericResult<()> {
+        writeln!(w, "{}", CSV_HEADER)?;
+
+        for r in self.records.iter() {
+            writeln!(
+                w,
+                "{},{},{},{},{},{},{}",
+                r.step, r.t, r.obstacle, r.force[0], r.force[1], r.drag, r.lift
+            )?;
+        }
+
+        return Ok(());
+    }
+
+    fn obstacles(&self) -> usize {
+        return self
+            .records
+            .iter()
+            .map(|r| r.obstacle + 1)
+            .max()
+            .unwrap_or(0);
+    }
+
+    /// The charts of the drag and lift coefficients (one line per obstacle).
+    pub fn charts(&self) -> Vec<Chart> {
+        let chart = |title: &str, f: fn(&ForceRecord) -> f64| Chart {
+            title: title.to_string(),
+            series: (0..self.obstacles())
+                .map(|k| Series {
+                    name: format!("obstacle {}", k),
+                    points: self
+                        .records
+                        .iter()
+                        .filter(|r| r.obstacle == k)
+                        .map(|r| (r.t, f(r)))
+                        .collect(),
+                })
+                .collect(),
+        };
+
+        return vec![
+            chart("drag coefficient", |r| r.drag),
+            chart("lift coefficient", |r| r.lift),
+        ];
+    }
+
+    /// The mean drag and lift coefficients of the obstacle over the second half
+    /// of the records (after the start-up of the flow).
+    pub fn mean_coefficients(&self, obstacle: usize) -> Option<(f64, f64)> {
+        let records = self
+            .records
+            .iter()
+            .filter(|r| r.obstacle == obstacle)
+            .collect::<Vec<_>>();
+        let half = &records[records.len() / 2..];
+
+        if half.is_empty() {
+            return None;
+        }
+
+        let n = half.len() as f64;
+        return Some((
+            half.iter().map(|r| r.drag).sum::<f64>() / n,
+            half.iter().map(|r| r.lift).sum::<f64>() / n,
+        ));
+    }
+
+    /// Write the CSV file and the line charts.
+    pub fn finish(&self, log: &Logger) -> GenericResult<()> {
+        for k in 0..self.obstacles() {
+            if let Some((drag, lift)) = self.mean_coefficients(k) {
+                info!(
+                    log,
+                    "Obstacle {}: mean drag coefficient: {:.4}, mean lift coefficient: {:.4}.",
+                    k,
+                    drag,
+                    lift
+                );
+            }
+        }
+
+        let file = format!("{}.csv", self.output);
+        info!(
+            log,
+            "Write forces of {} records to '{}'.",
+            self.records.len(),
+            file
+        );
+
+        let mut w = BufWriter::new(File::create(&file)?);
+        self.write_csv(&mut w)?;
+        w.flush()?;
+
+        let image = plotting::line_charts(idx!(1600, 600), idx!(2, 1), &self.charts(), "t")?;
+        plotting::save_png(&image, &format!("{}.png", self.output))?;
+
+        return Ok(());
+    }
+}
@@ -0,0 +1,14 @@
+mod forces;
+mod npy;
+mod probes;
+mod shedding;
+mod stats;
+mod vtk;
+pub use forces::*;
+pub use npy::*;
+pub use probes::*;
+pub use shedding::*;
+pub use stats::*;
+pub use vtk::*;
+
+mod tests;
@@ -0,0 +1,379 @@
+use crate::log::*;
+use crate::scene::cell::CellTypes;
+use crate::scene::cell_stats::Stats;
+use crate::scene::grid::Grid;
+use crate::scene::grid_fields::GridFields;
+use crate::scene::timestepper::TimeStepper;
+use crate::types::*;
+
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+/// Magic bytes of the `.npy` format (version 1.0).
+const NPY_MAGIC: &[u8; 6] = b"\x93NUMPY";
+
+/// The data of a NumPy array (`<f8` or `|u1`).
+#[derive(Clone, Debug, PartialEq)]
+pub enum NpyData {
+    F64(Vec<f64>),
+    U8(Vec<u8>),
+}
+
+/// A NumPy array in C order (last index is the fastest).
+#[derive(Clone, Debug, PartialEq)]
+pub struct NpyArray {
+    pub shape: Vec<usize>,
+    pub data: NpyData,
+}
+
+/// The metadata sidecar (`.json`) written with the arrays of each frame.
+/// The arrays have the shape `(dim.y, dim.x)` (`array[y, x]`), the velocities
+/// `u`, `v` are the staggered values on the left and bottom face of each cell and
+/// `mode` is `1` for solid and `0` for fluid cells.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct NpyMetadata {
+    pub step: u64,
+    pub t: f64,
+    pub cell_width: f64,
+    pub dim: [usize; 2],
+    pub shape: [usize; 2],
+    pub fields: Vec<String>,
+    /// The min. and max. statistics of the grid (as used for the plots).
+    #[serde(default)]
+    pub stats: Option<[Stats<f64>; 2]>,
+}
+
+/// Write the array in `.npy` format (version 1.0).
+pub fn write_npy<W: Write>(w: &mut W, array: &NpyArray) -> GenericResult<()> {
+    let descr = match array.data {
+        NpyData::F64(_) => "<f8",
+        NpyData::U8(_) => "|u1",
+    };
+
+    let shape = match array.shape.len() {
+        1 => format!("({},)", array.shape[0]),
+        _ => format!(
+            "({})",
+            array.shape.iter().map(|s| s.to_string()).collect::<Vec<_>>().join(", ")
+        ),
+    };
+
+    let mut header = format!(
+        "{{'descr': '{}', 'fortran_order': False, 'shape': {}, }}",
+        descr, shape
+    );
+
+    // Pad the header with spaces such that the data is aligned to 64 bytes.
+    let total = NPY_MAGIC.len() + 2 + 2 + header.len() + 1;
+    header.push_str(&" ".repeat((64 - total % 64) % 64));
+    header.push('\n');
+
+    w.write_all(NPY_MAGIC)?;
+    w.write_all(&[1, 0])?;
+    w.write_all(&(header.len() as u16).to_le_bytes())?;
+    w.write_all(header.as_bytes())?;
+
+    match &array.data {
+        NpyData::F64(values) => {
+            for v in values {
+                w.write_all(&v.to_le_bytes())?;
+            }
+        }
+        NpyData::U8(values) => w.write_all(values)?,
+    }
+
+    return Ok(());
+}
+
+/// Read an array in `.npy` format (only `<f8` and `|u1` in C order).
+pub fn read_npy<R: Read>(r: &mut R) -> GenericResult<NpyArray> {
+    let mut magic = [0u8; 8];
+    r.read_exact(&mut magic)?;
+    if &magic[0..6] != NPY_MAGIC || magic[6] != 1 {
+        bail!("Not a '.npy' file of version 1.0.");
+    }
+
+    let mut len = [0u8; 2];
+    r.read_exact(&mut len)?;
+    let mut header = vec![0u8; u16::from_le_bytes(len) as usize];
+    r.read_exact(&mut header)?;
+    let header = String::from_utf8(header)?;
+
+    // Get the value after `'key': ` in the header dictionary.
+    let value = |key: &str| -> SimpleResult<String> {
+        let pattern = format!("'{}': ", key);
+        let start = match header.find(&pattern) {
+            Some(i) => i + pattern.len(),
+            None => bail!("Key '{}' not in header '{}'.", key, header),
+        };
+        let rest = &header[start..];
+        let end = if rest.starts_with('(') {
+            rest.find(')').map(|i| i + 1)
+        } else {
+            rest.find(',')
+        };
+
+        return Ok(rest[..end.unwrap_or(rest.len())].trim_matches('\'').to_string());
+    };
+
+    if value("fortran_order")? != "False" {
+        bail!("Fortran order is not supported.");
+    }
+
+    let shape = value("shape")?
+        .trim_matches(|c| c == '(' || c == ')')
+        .split(',')
+        .map(|s| s.trim())
+        .filter(|s| !s.is_empty())
+        .map(|s| s.parse::<usize>())
+        .collect::<Result<Vec<_>, _>>()?;
+    let n: usize = shape.iter().product();
+
+    let data = match value("descr")?.as_str() {
+        "<f8" => {
+            let mut bytes = vec![0u8; 8 * n];
+            r.read_exact(&mut bytes)?;
+            NpyData::F64(
+                bytes
+                    .chunks_exact(8)
+                    .map(|b| f64::from_le_bytes(b.try_into().unwrap()))
+                    .collect(),
+            )
+        }
+        "|u1" => {
+            let mut bytes = vec![0u8; n];
+            r.read_exact(&mut bytes)?;
+            NpyData::U8(bytes)
+        }
+        descr => bail!("Data type '{}' is not supported.", descr),
+    };
+
+    return Ok(NpyArray { shape, data });
+}
+
+/// Write the named arrays into an (uncompressed) `.npz` archive.
+pub fn write_npz(file: &str, arrays: &[(String, NpyArray)]) -> GenericResult<()> {
+    let mut zip = zip::ZipWriter::new(BufWriter::new(File::create(file)?));
+    let options =
+        zip::write::FileOptions::default().compression_method(zip::CompressionMethod::Stored);
+
+    for (name, array) in arrays {
+        zip.start_file(format!("{}.npy", name), options)?;
+        write_npy(&mut zip, array)?;
+    }
+
+    zip.finish()?.flush()?;
+
+    return Ok(());
+}
+
+/// Read all named arrays from a `.npz` archive (in archive order).
+pub fn read_npz(file: &str) -> GenericResult<Vec<(String, NpyArray)>> {
+    let mut zip = zip::ZipArchive::new(BufReader::new(File::open(file)?))?;
+
+    let mut arrays = vec![];
+    for i in 0..zip.len() {
+        let mut f = zip.by_index(i)?;
+        let name = f.name().trim_end_matches(".npy").to_string();
+        arrays.push((name, read_npy(&mut f)?));
+    }
+
+    return Ok(arrays);
+}
+
+/// All fields of the grid as named arrays with shape `(dim.y, dim.x)`
+/// (see [`NpyMetadata`]).
+pub fn grid_arrays<T: Float>(grid: &Grid<T>) -> Vec<(String, NpyArray)> {
+    let fields = grid.fields();
+    let shape = vec![grid.dim.y, grid.dim.x];
+
+    let to_array = |values: &[T]| NpyArray {
+        shape: shape.clone(),
+        data: NpyData::F64(values.iter().map(|v| to_float(*v)).collect()),
+    };
+
+    let mode = NpyArray {
+        shape: shape.clone(),
+        data: NpyData::U8(
+            fields
+                .mode
+                .iter()
+                .map(|m| (*m == CellTypes::Solid) as u8)
+                .collect(),
+        ),
+    };
+
+    return vec![
+        ("u".to_string(), to_array(&fields.velocity[0].back)),
+        ("v".to_string(), to_array(&fields.velocity[1].back)),
+        ("pressure".to_string(), to_array(&fields.pressure)),
+        ("smoke".to_string(), to_array(&fields.smoke.back)),
+        ("div".to_string(), to_array(&fields.div)),
+        ("mode".to_string(), mode),
+    ];
+}
+
+/// Writes the fields of each frame as `<output>-<name>.npy` arrays or bundled
+/// into `<output>.npz`, together with the metadata sidecar `<output>.json`
+/// (`{}` in `output` is replaced by the step).
+pub struct NpyWriter {
+    pub output: String,
+    /// Bundle all arrays of a frame into an `.npz` archive.
+    pub bundle: bool,
+}
+
+impl NpyWriter {
+    pub fn new(output: String, bundle: bool) -> NpyWriter {
+        return NpyWriter { output, bundle };
+    }
+
+    pub fn write_grid<T: Float>(
+        &self,
+        log: &Logger,
+        grid: &Grid<T>,
+        t: T,
+        step: u64,
+    ) -> GenericResult<()> {
+        let base = self.output.replace("{}", &format!("{:06}", step));
+        info!(log, "Saving fields to '{}'.", base);
+
+        let arrays = grid_arrays(grid);
+
+        if self.bundle {
+            write_npz(&format!("{}.npz", base), &arrays)?;
+        } else {
+            for (name, array) in arrays.iter() {
+                let mut w = BufWriter::new(File::create(format!("{}-{}.npy", base, name))?);
+                write_npy(&mut w, array)?;
+                w.flush()?;
+            }
+        }
+
+        let meta = NpyMetadata {
+            step,
+            t: to_float(t),
+            cell_width: to_float(grid.cell_width),
+            dim: [grid.dim.x, grid.dim.y],
+            shape: [grid.dim.y, grid.dim.x],
+            fields: arrays.into_iter().map(|(name, _)| name).collect(),
+            stats: Some([grid.stats[0].cast(), grid.stats[1].cast()]),
+        };
+        serde_json::to_writer_pretty(File::create(format!("{}.json", base))?, &meta)?;
+
+        return Ok(());
+    }
+
+    /// Write the fields of the grid of the timestepper.
+    pub fn write(&self, log: &Logger, timestepper: &TimeStepper, step: u64) -> GenericResult<()> {
+        let grid = timestepper.objects[0]
+            .as_any()
+            .downcast_ref::<Grid>()
+            .expect("Not a grid");
+
+        return self.write_grid(log, grid, timestepper.t(), step);
+    }
+}
+
+/// Read the fields of a frame written by [`NpyWriter`] with base path `base`
+/// (without extension) back into a grid.
+/// The `.npz` archive is used if it exists, otherwise the single `.npy` files.
+pub fn read_grid<T: Float>(base: &str) -> GenericResult<(Grid<T>, NpyMetadata)> {
+    let meta: NpyMetadata = serde_json::from_reader(BufReader::new(File::open(format!(
+        "{}.json",
+        base
+    ))?))?;
+
+    let npz_file = format!("{}.npz", base);
+    let arrays = if Path::new(&npz_file).exists() {
+        read_npz(&npz_file)?
+    } else {
+        let mut arrays = vec![];
+        for name in meta.fields.iter() {
+            let mut r = BufReader::new(File::open(format!("{}-{}.npy", base, name))?);
+            arrays.push((name.clone(), read_npy(&mut r)?));
+        }
+        arrays
+    };
+
+    let get = |name: &str| -> SimpleResult<&NpyData> {
+        return match arrays.iter().find(|(n, _)| n == name) {
+            Some((_, a)) if a.shape == meta.shape => Ok(&a.data),
+            Some((_, a)) => bail!(
+                "Array '{}' has shape {:?} but expected {:?}.",
+                name,
+                a.shape,
+                meta.shape
+            ),
+            None => bail!("Array '{}' is missing in frame '{}'.", name, base),
+        };
+    };
+
+    let to_field = |name: &str| -> SimpleResult<Vec<T>> {
+        return match get(name)? {
+            NpyData::F64(values) => Ok(values.iter().map(|v| to_float(*v)).collect()),
+            NpyData::U8(_) => bail!("Array '{}' is not of type 'f64'.", name),
+        };
+    };
+
+    let mut fields = GridFields::new(idx!(meta.dim[0], meta.dim[1]));
+
+    for (dir, name) in ["u", "v"].iter().enumerate() {
+        fields.velocity[dir].back = to_field(name)?;
+        fields.velocity[dir].front = fields.velocity[dir].back.clone();
+    }
+    fields.pressure = to_field("pressure")?;
+    fields.smoke.back = to_field("smoke")?;
+    fields.smoke.front = fields.smoke.back.clone();
+    fields.div = to_field("div")?;
+
+    fields.mode = match get("mode")? {
+        NpyData::U8(values) => values
+            .iter()
+            .map(|m| if *m == 1 { CellTypes::Solid } else { CellTypes::Fluid })
+            .collect(),
+        NpyData::F64(_) => bail!("Array 'mode' is not of type 'u8'."),
+    };
+
+    let mut grid = Grid::from_fields(fields, to_float(meta.cell_width));
+    if let Some(stats) = &meta.stats {
+        grid.stats = [stats[0].cast(), stats[1].cast()];
+    }
+
+    return Ok((grid, meta));
+}
+
+/// Find all frames written by [`NpyWriter`] with output pattern `output`
+/// (by their `.json` sidecar). Returns the steps and base paths sorted by step.
+pub fn find_frames(output: &str) -> GenericResult<Vec<(u64, String)>> {
+    let (prefix, suffix) = match output.split_once("{}") {
+        Some(s) => s,
+        None => bail!("Pattern '{}' contains no '{{}}'.", output),
+    };
+
+    // Split the directory from the file name prefix.
+    let (dir, name_prefix) = match prefix.rfind('/') {
+        Some(i) => (&prefix[..i + 1], &prefix[i + 1..]),
+        None => ("", prefix),
+    };
+    let name_suffix = format!("{}.json", suffix);
+
+    let mut frames = vec![];
+    for entry in std::fs::read_dir(if dir.is_empty() { "." } else { dir })? {
+        let name = entry?.file_name().to_string_lossy().to_string();
+
+        let step = name
+            .strip_prefix(name_prefix)
+            .and_then(|n| n.strip_suffix(&name_suffix))
+            .filter(|s| !s.is_empty() && s.chars().all(|c| c.is_ascii_digit()));
+
+        if let Some(step) = step {
+            frames.push((step.parse::<u64>()?, format!("{}{}{}", prefix, step, suffix)));
+        }
+    }
+
+    frames.sort();
+
+    return Ok(frames);
+}
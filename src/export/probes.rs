@@ -0,0 +1,205 @@
+use crate::log::*;
+use crate::plotting;
+use crate::plotting::{Chart, Series};
+use crate::scene::grid::Grid;
+use crate::scene::probes::{sample_probe, ProbeConfig, ProbeSample};
+use crate::scene::timestepper::TimeStepper;
+use crate::types::*;
+
+use std::fs::File;
+use std::io::{BufWriter, Write};
+
+const CSV_HEADER: &str = "step,t,probe,x,y,velocity_x,velocity_y,pressure,smoke";
+
+/// Write one CSV row of a probe sample.
+pub fn write_probe_sample(
+    w: &mut impl Write,
+    step: u64,
+    t: Scalar,
+    probe: usize,
+    pos: Vector2,
+    sample: &ProbeSample,
+) -> GenericResult<()> {
+    writeln!(
+        w,
+        "{},{},{},{},{},{},{},{},{}",
+        step,
+        t,
+        probe,
+        pos.x,
+        pos.y,
+        sample.velocity.x,
+        sample.velocity.y,
+        sample.pressure,
+        sample.smoke
+    )?;
+
+    return Ok(());
+}
+
+/// A sampled line probe at the time `t`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Profile {
+    pub t: Scalar,
+    /// The samples with their distance along the line.
+    pub samples: Vec<(Scalar, ProbeSample)>,
+}
+
+/// The charts of the velocity, the pressure and the smoke along a line probe
+/// (one line per profile).
+pub fn profile_charts(profiles: &[Profile]) -> Vec<Chart> {
+    let chart = |title: &str, f: fn(&ProbeSample) -> Scalar| Chart {
+        title: title.to_string(),
+        series: profiles
+            .iter()
+            .map(|p| Series {
+                name: format!("t = {:.3}", p.t),
+                points: p
+                    .samples
+                    .iter()
+                    .map(|(s, sample)| (to_float(*s), to_float(f(sample))))
+                    .collect(),
+            })
+            .collect(),
+    };
+
+    return vec![
+        chart("velocity x", |s| s.velocity.x),
+        chart("velocity y", |s| s.velocity.y),
+        chart("pressure", |s| s.pressure),
+        chart("smoke", |s| s.smoke),
+    ];
+}
+
+/// Appends the samples of the point probes to `<output>-points.csv` and of
+/// each line probe to `<output>-line-<k>.csv` every step. The profiles of the
+/// line probes at the profile times are plotted to `<output>-line-<k>.png`.
+pub struct ProbeRecorder {
+    /// The output path without extension.
+    pub output: String,
+    pub config: ProbeConfig,
+    /// The times of the profile plots (ascending).
+    pub profile_times: Vec<Scalar>,
+    /// The profiles of each line probe.
+    pub profiles: Vec<Vec<Profile>>,
+
+    points_writer: Option<BufWriter<File>>,
+    line_writers: Vec<BufWriter<File>>,
+}
+
+impl ProbeRecorder {
+    pub fn new(
+        output: String,
+        config: ProbeConfig,
+        mut profile_times: Vec<Scalar>,
+    ) -> GenericResult<Self> {
+        let create = |file: String| -> GenericResult<BufWriter<File>> {
+            let mut w = BufWriter::new(File::create(file)?);
+            writeln!(w, "{}", CSV_HEADER)?;
+            return Ok(w);
+        };
+
+        let points_writer = if config.points.is_empty() {
+            None
+        } else {
+            Some(create(format!("{}-points.csv", output))?)
+        };
+
+        let line_writers = (0..config.lines.len())
+            .map(|k| create(format!("{}-line-{}.csv", output, k)))
+            .collect::<GenericResult<Vec<_>>>()?;
+
+        profile_times.sort_by(|a, b| a.total_cmp(b));
+
+        return Ok(ProbeRecorder {
+            output,
+            profiles: vec![vec![]; config.lines.len()],
+            config,
+            profile_times,
+            points_writer,
+            line_writers,
+        });
+    }
+
+    pub fn record(&mut self, timestepper: &TimeStepper, step: u64) -> GenericResult<()> {
+        let grid = timestepper.objects[0]
+            .as_any()
+            .downcast_ref::<Grid>()
+            .expect("Not a grid");
+        let t = timestepper.t();
+
+        if let Some(ref mut w) = self.points_writer {
+            for (k, pos) in self.config.points.iter().enumerate() {
+                write_probe_sample(w, step, t, k, *pos, &sample_probe(grid, *pos))?;
+            }
+        }
+
+        // The profile of the first step at or after each profile time.
+        let n_profiles = self.profiles.first().map_or(0, |p| p.len());
+        let with_profile = self
+            .profile_times
+            .get(n_profiles)
+            .is_some_and(|pt| t >= *pt);
+
+        for (k, line) in self.config.lines.iter().enumerate() {
+            let samples = line
+                .points()
+                .into_iter()
+                .map(|(s, pos)| (s, pos, sample_probe(grid, pos)))
+                .collect::<Vec<_>>();
+
+            for (i, (_, pos, sample)) in samples.iter().enumerate() {
+                write_probe_sample(&mut self.line_writers[k], step, t, i, *pos, sample)?;
+            }
+
+            if with_profile {
+                self.profiles[k].push(Profile {
+                    t,
+                    samples: samples
+                        .into_iter()
+                        .map(|(s, _, sample)| (s, sample))
+                        .collect(),
+                });
+            }
+        }
+
+        return Ok(());
+    }
+
+    /// Flush the CSV files and plot the profiles.
+    pub fn finish(mut self, log: &Logger) -> GenericResult<()> {
+        info!(log, "Write probes to '{}-*.csv'.", self.output);
+
+        if let Some(ref mut w) = self.points_writer {
+            w.flush()?;
+        }
+        for w in self.line_writers.iter_mut() {
+            w.flush()?;
+        }
+
+        for (k, profiles) in self.profiles.iter().enumerate() {
+            if profiles.is_empty() {
+                continue;
+            }
+
+            let file = format!("{}-line-{}.png", self.output, k);
+            info!(
+                log,
+                "Plot {} profiles of line probe {} to '{}'.",
+                profiles.len(),
+                k,
+                file
+            );
+
+            let image = plotting::line_charts(
+                idx!(1600, 1000),
+                idx!(2, 2),
+                &profile_charts(profiles),
+                "s",
+            )?;
+            plotting::save_png(&image, &file)?;
+        }
+
+        return Ok(());
+    }
+}
@@ -0,0 +1,201 @@
+use crate::log::*;
+use crate::scene::forces::find_obstacles;
+use crate::scene::grid::Grid;
+use crate::scene::timestepper::TimeStepper;
+use crate::types::*;
+
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+
+/// The dominant frequency of the signal `(t, value)` from the upward zero
+/// crossings (interpolated linearly) of the signal without its mean. A crossing
+/// only counts after the signal fell below `-hysteresis` times its standard
+/// deviation, such that noise around zero is ignored.
+pub fn zero_crossing_frequency(samples: &[(f64, f64)], hysteresis: f64) -> Option<f64> {
+    if samples.len() < 2 {
+        return None;
+    }
+
+    let n = samples.len() as f64;
+    let mean = samples.iter().map(|s| s.1).sum::<f64>() / n;
+    let std = (samples.iter().map(|s| (s.1 - mean).powi(2)).sum::<f64>() / n).sqrt();
+    if std == 0.0 || !std.is_finite() {
+        return None;
+    }
+
+    let threshold = hysteresis * std;
+    let mut armed = false;
+    let mut crossings = vec![];
+
+    for w in samples.windows(2) {
+        let (t0, v0) = (w[0].0, w[0].1 - mean);
+        let (t1, v1) = (w[1].0, w[1].1 - mean);
+
+        if v0 < -threshold {
+            armed = true;
+        }
+
+        if armed && v0 < 0.0 && v1 >= 0.0 {
+            crossings.push(t0 + (t1 - t0) * (-v0) / (v1 - v0));
+            armed = false;
+        }
+    }
+
+    if crossings.len() < 2 {
+        return None;
+    }
+
+    let periods = (crossings.len() - 1) as f64;
+    return Some(periods / (crossings[crossings.len() - 1] - crossings[0]));
+}
+
+/// The result of the shedding analysis.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SheddingSummary {
+    /// The probe position in world coordinates (none without a probe and an obstacle).
+    pub probe: Option<[f64; 2]>,
+    /// The analyzed time window (the second half of the run).
+    pub window: [f64; 2],
+    pub samples: usize,
+    pub frequency: Option<f64>,
+    pub diameter: f64,
+    pub speed: f64,
+    pub strouhal: Option<f64>,
+}
+
+/// Records the transverse velocity at a probe downstream of the obstacle and
+/// computes the shedding frequency and the Strouhal number `St = f D / U` at the
+/// end. Writes the probe signal `<output>.csv` and the summary `<output>.json`.
+pub struct SheddingAnalysis {
+    /// The output path without extension.
+    pub output: String,
+    /// The probe position in world coordinates, by default three diameters
+    /// behind the center of the first obstacle.
+    pub probe: Option<Vector2>,
+    /// The inflow speed `U`.
+    pub speed: Scalar,
+    /// The obstacle diameter `D` (from the first obstacle).
+    pub diameter: Scalar,
+    /// The transverse velocity `(t, v)` at the probe.
+    pub samples: Vec<(f64, f64)>,
+    /// Whether the obstacle and the default probe were looked up.
+    pub initialized: bool,
+}
+
+impl SheddingAnalysis {
+    pub fn new(output: String, probe: Option<Vector2>, speed: Scalar) -> Self {
+        return SheddingAnalysis {
+            output,
+            probe,
+            speed,
+            diameter: 0.0,
+            samples: vec![],
+            initialized: false,
+        };
+    }
+
+    pub fn record(&mut self, log: &Logger, timestepper: &TimeStepper) {
+        let grid = timestepper.objects[0]
+            .as_any()
+            .downcast_ref::<Grid>()
+            .expect("Not a grid");
+
+        if !self.initialized {
+            self.initialized = true;
+
+            if let Some(obstacle) = find_obstacles(grid).first() {
+                self.diameter = obstacle.size(grid.cell_width);
+                let center = obstacle.center(grid.cell_width);
+                let probe = *self
+                    .probe
+                    .get_or_insert(center + vec2!(3.0 * self.diameter, 0.0));
+
+                info!(
+                    log,
+                    "Shedding probe at '{:.3}, {:.3}' (obstacle diameter: {:.3}).",
+                    probe.x,
+                    probe.y,
+                    self.diameter
+                );
+            }
+
+            if self.probe.is_none() {
+                warn!(
+                    log,
+                    "No shedding probe given and no obstacle found, nothing is recorded."
+                );
+            }
+        }
+
+        if let Some(probe) = self.probe {
+            let v = grid.sample_velocity(probe).y;
+            self.samples.push((to_float(timestepper.t()), to_float(v)));
+        }
+    }
+
+    /// Analyze the second half of the samples (after the start-up of the flow).
+    pub fn summary(&self) -> SheddingSummary {
+        let window = &self.samples[self.samples.len() / 2..];
+        let frequency = zero_crossing_frequency(window, 0.25);
+        let (diameter, speed) = (to_float::<f64>(self.diameter), to_float::<f64>(self.speed));
+
+        return SheddingSummary {
+            probe: self.probe.map(|p| [to_float::<f64>(p.x), to_float(p.y)]),
+            window: [
+                window.first().map_or(0.0, |s| s.0),
+                window.last().map_or(0.0, |s| s.0),
+            ],
+            samples: window.len(),
+            frequency,
+            diameter,
+            speed,
+            strouhal: frequency
+                .filter(|_| speed > 0.0)
+                .map(|f| f * diameter / speed),
+        };
+    }
+
+    pub fn write_csv(&self, w: &mut impl Write) -> GenericResult<()> {
+        writeln!(w, "t,velocity_y")?;
+
+        for (t, v) in self.samples.iter() {
+            writeln!(w, "{},{}", t, v)?;
+        }
+
+        return Ok(());
+    }
+
+    /// Log the summary and write the probe signal and the summary.
+    pub fn finish(&self, log: &Logger) -> GenericResult<()> {
+        let summary = self.summary();
+
+        match (summary.frequency, summary.strouhal) {
+            (Some(f), Some(st)) => info!(
+                log,
+                "Shedding frequency: {:.4} Hz, Strouhal number: {:.4} (t: {:.2} - {:.2}).",
+                f,
+                st,
+                summary.window[0],
+                summary.window[1]
+            ),
+            _ => warn!(
+                log,
+                "No shedding frequency found in {} samples (t: {:.2} - {:.2}).",
+                summary.samples,
+                summary.window[0],
+                summary.window[1]
+            ),
+        }
+
+        let file = format!("{}.json", self.output);
+        info!(log, "Write shedding analysis to '{}'.", file);
+        serde_json::to_writer_pretty(File::create(file)?, &summary)?;
+
+        let mut w = BufWriter::new(File::create(format!("{}.csv", self.output))?);
+        self.write_csv(&mut w)?;
+        w.flush()?;
+
+        return Ok(());
+    }
+}
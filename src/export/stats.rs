@@ -0,0 +1,149 @@
+use crate::log::*;
+use crate::plotting;
+use crate::plotting::{Chart, Series};
+use crate::scene::cell_stats::Stats;
+use crate::scene::grid::Grid;
+use crate::scene::timestepper::TimeStepper;
+use crate::types::*;
+
+use std::fs::File;
+use std::io::{BufWriter, Write};
+
+/// The statistics of one step.
+#[derive(Clone, Debug, PartialEq)]
+pub struct StatsRecord {
+    pub step: u64,
+    pub t: f64,
+    pub pressure: [f64; 2],
+    pub div: [f64; 2],
+    pub velocity_norm: [f64; 2],
+    pub smoke: f64,
+    pub kinetic_energy: f64,
+    /// The max. absolute divergence after the pressure solve.
+    pub div_residual: f64,
+}
+
+impl StatsRecord {
+    pub fn from_grid<T: Float>(grid: &Grid<T>, step: u64, t: T, density: T) -> StatsRecord {
+        let min_max = |f: fn(&Stats<T>) -> T| {
+            [
+                to_float::<f64>(f(&grid.stats[0])),
+                to_float(f(&grid.stats[1])),
+            ]
+        };
+
+        return StatsRecord {
+            step,
+            t: to_float(t),
+            pressure: min_max(|s| s.pressure),
+            div: min_max(|s| s.div),
+            velocity_norm: min_max(|s| s.velocity_norm),
+            smoke: to_float(grid.total_smoke()),
+            kinetic_energy: to_float(grid.kinetic_energy(density)),
+            div_residual: to_float(grid.div_residual),
+        };
+    }
+}
+
+const CSV_HEADER: &str = "step,t,pressure_min,pressure_max,div_min,div_max,\
+velocity_norm_min,velocity_norm_max,smoke,kinetic_energy,div_residual";
+
+/// Records the statistics of each step and writes them at the end as
+/// `<output>.csv` and the line charts `<output>.png`.
+pub struct StatsRecorder {
+    /// The output path without extension.
+    pub output: String,
+    pub records: Vec<StatsRecord>,
+}
+
+impl StatsRecorder {
+    pub fn new(output: String) -> Self {
+        return StatsRecorder {
+            output,
+            records: vec![],
+        };
+    }
+
+    pub fn record(&mut self, timestepper: &TimeStepper, step: u64) {
+        let grid = timestepper.objects[0]
+            .as_any()
+            .downcast_ref::<Grid>()
+            .expect("Not a grid");
+
+        self.records.push(StatsRecord::from_grid(
+            grid,
+            step,
+            timestepper.t(),
+            timestepper.density(),
+        ));
+    }
+
+    pub fn write_csv(&self, w: &mut impl Write) -> GenericResult<()> {
+        writeln!(w, "{}", CSV_HEADER)?;
+
+        for r in self.records.iter() {
+            writeln!(
+                w,
+                "{},{},{},{},{},{},{},{},{},{},{}",
+                r.step,
+                r.t,
+                r.pressure[0],
+                r.pressure[1],
+                r.div[0],
+                r.div[1],
+                r.velocity_norm[0],
+                r.velocity_norm[1],
+                r.smoke,
+                r.kinetic_energy,
+                r.div_residual
+            )?;
+        }
+
+        return Ok(());
+    }
+
+    /// The charts of the statistics over the time.
+    pub fn charts(&self) -> Vec<Chart> {
+        let series = |name: &str, f: &dyn Fn(&StatsRecord) -> f64| Series {
+            name: name.to_string(),
+            points: self.records.iter().map(|r| (r.t, f(r))).collect(),
+        };
+        let min_max = |title: &str, f: &dyn Fn(&StatsRecord) -> [f64; 2]| Chart {
+            title: title.to_string(),
+            series: vec![series("min", &|r| f(r)[0]), series("max", &|r| f(r)[1])],
+        };
+        let single = |title: &str, f: &dyn Fn(&StatsRecord) -> f64| Chart {
+            title: title.to_string(),
+            series: vec![series(title, f)],
+        };
+
+        return vec![
+            min_max("pressure", &|r| r.pressure),
+            min_max("divergence", &|r| r.div),
+            min_max("velocity norm", &|r| r.velocity_norm),
+            single("total smoke", &|r| r.smoke),
+            single("kinetic energy", &|r| r.kinetic_energy),
+            single("divergence residual", &|r| r.div_residual),
+        ];
+    }
+
+    /// Write the CSV file and the line charts.
+    pub fn finish(&self, log: &Logger) -> GenericResult<()> {
+        let file = format!("{}.csv", self.output);
+        info!(
+            log,
+            "Write statistics of {} steps to '{}'.",
+            self.records.len(),
+            file
+        );
+
+        let mut w = BufWriter::new(File::create(&file)?);
+        self.write_csv(&mut w)?;
+        w.flush()?;
+
+        let image = plotting::line_charts(idx!(1600, 1200), idx!(2, 3), &self.charts(), "t")?;
+        plotting::save_png(&image, &format!("{}.png", self.output))?;
+
+        return Ok(());
+    }
+}
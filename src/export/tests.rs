@@ -0,0 +1,355 @@
+#[cfg(test)]
+mod tests {
+
+    use crate::export::*;
+    use crate::scene::grid::*;
+    use crate::types::*;
+
+    #[test]
+    fn test_vti() -> GenericResult<()> {
+        let mut grid = Grid::new(dim!(3, 2), 0.5);
+        grid.cell_mut(idx!(1, 1)).set_velocity(vec2!(1.0, 2.0));
+        grid.cell_mut(idx!(2, 1)).set_velocity(vec2!(3.0, 4.0));
+
+        let file = std::env::temp_dir().join(format!("rsfluid-test-{}.vti", std::process::id()));
+        let file = file.to_str().unwrap();
+        write_vti(&grid, file, true)?;
+
+        let data = std::fs::read(file)?;
+        std::fs::remove_file(file)?;
+
+        let text = String::from_utf8_lossy(&data);
+        assert!(text.contains(r#"WholeExtent="0 5 0 4 0 0""#));
+        assert!(text.contains(r#"Spacing="0.5 0.5 0.5""#));
+
+        for name in ["velocity", "pressure", "smoke", "divergence", "solid", "velocity_x_face"] {
+            assert!(text.contains(&format!(r#"Name="{}""#, name)), "Missing '{}'.", name);
+        }
+
+        // The first appended array is the cell centered velocity.
+        let marker = b"   _";
+        let start = data.windows(marker.len()).position(|w| w == marker).unwrap() + marker.len();
+        let n = grid.dim.x * grid.dim.y;
+        let size = u64::from_le_bytes(data[start..start + 8].try_into()?);
+        assert!(size as usize == 3 * 8 * n);
+
+        let value = |k: usize| {
+            let b = start + 8 + 8 * k;
+            return f64::from_le_bytes(data[b..b + 8].try_into().unwrap());
+        };
+
+        // Cell (1, 1): u = (1 + 3) / 2, v = (2 + 0) / 2.
+        let k = 3 * (1 + grid.dim.x);
+        assert!(value(k) == 2.0 && value(k + 1) == 1.0 && value(k + 2) == 0.0);
+
+        return Ok(());
+    }
+
+    #[test]
+    fn test_pvd_file() {
+        let writer = VtkWriter::new("./frames/fields-{}.vti".to_string(), false);
+        assert!(writer.pvd_file() == "./frames/fields.pvd");
+    }
+
+    #[test]
+    fn test_pvd_collection() -> GenericResult<()> {
+        let (log, _) = crate::log::create_logger();
+        let dir = std::env::temp_dir().join(format!("rsfluid-pvd-{}", std::process::id()));
+        std::fs::create_dir_all(&dir)?;
+        let output = dir.join("fields-{}.vti").to_str().unwrap().to_string();
+
+        let mut writer = VtkWriter::new(output.clone(), false);
+        for (t, name) in [(0.25, "a.vti"), (0.5, "b.vti"), (0.75, "c.vti")] {
+            writer.add_to_collection(t, name)?;
+        }
+        let entries = read_pvd(writer.pvd_file())?;
+        assert!(entries.len() == 3 && entries[2] == (0.75, "c.vti".to_string()));
+
+        // A restarted run keeps the frames up to the restart.
+        let mut writer = VtkWriter::new(output.clone(), false);
+        writer.resume(&log, 0.5)?;
+        writer.add_to_collection(0.75, "d.vti")?;
+        let entries = read_pvd(writer.pvd_file())?;
+        let names = entries.iter().map(|e| e.1.as_str()).collect::<Vec<_>>();
+        assert!(names == ["a.vti", "b.vti", "d.vti"]);
+
+        // A new run starts a new collection.
+        let mut writer = VtkWriter::new(output, false);
+        writer.add_to_collection(0.25, "e.vti")?;
+        assert!(read_pvd(writer.pvd_file())?.len() == 1);
+
+        std::fs::remove_dir_all(dir)?;
+
+        return Ok(());
+    }
+
+    #[test]
+    fn test_npy_round_trip() -> GenericResult<()> {
+        let arrays = [
+            NpyArray {
+                shape: vec![2, 3],
+                data: NpyData::F64(vec![1.0, -2.5, 3.0, f64::MAX, 0.0, -0.0]),
+            },
+            NpyArray {
+                shape: vec![4],
+                data: NpyData::U8(vec![0, 1, 255, 7]),
+            },
+        ];
+
+        for array in arrays {
+            let mut buffer = vec![];
+            write_npy(&mut buffer, &array)?;
+
+            // The data starts 64-byte aligned.
+            let header_len = u16::from_le_bytes([buffer[8], buffer[9]]) as usize;
+            assert!((10 + header_len) % 64 == 0);
+
+            let read = read_npy(&mut buffer.as_slice())?;
+            assert!(read == array, "Read {:?} != {:?}", read, array);
+        }
+
+        return Ok(());
+    }
+
+    #[test]
+    fn test_npz_grid() -> GenericResult<()> {
+        let (log, switch) = crate::log::create_logger();
+        switch.disable();
+
+        let mut grid = Grid::new(dim!(4, 3), 0.25);
+        grid.cell_mut(idx!(2, 1)).set_velocity(vec2!(1.0, 2.0));
+        *grid.cell_mut(idx!(0, 0)).mode = crate::scene::cell::CellTypes::Solid;
+
+        let base = std::env::temp_dir().join(format!("rsfluid-test-{}-{{}}", std::process::id()));
+        let writer = NpyWriter::new(base.to_str().unwrap().to_string(), true);
+        writer.write_grid(&log, &grid, 0.5, 3)?;
+
+        let base = writer.output.replace("{}", "000003");
+        let arrays = read_npz(&format!("{}.npz", base))?;
+        let meta: NpyMetadata =
+            serde_json::from_reader(std::fs::File::open(format!("{}.json", base))?)?;
+        std::fs::remove_file(format!("{}.npz", base))?;
+        std::fs::remove_file(format!("{}.json", base))?;
+
+        assert!(arrays == grid_arrays(&grid));
+        assert!(meta.step == 3 && meta.t == 0.5 && meta.cell_width == 0.25);
+        assert!(meta.dim == [6, 5] && meta.shape == [5, 6]);
+        assert!(meta.fields == ["u", "v", "pressure", "smoke", "div", "mode"]);
+
+        // Array `u[y, x]` at cell (2, 1).
+        let (_, u) = &arrays[0];
+        assert!(u.data == NpyData::F64((0..30).map(|i| if i == 2 + 6 { 1.0 } else { 0.0 }).collect()));
+
+        let (_, mode) = &arrays[5];
+        assert!(mode.data == NpyData::U8((0..30).map(|i| (i == 0) as u8).collect()));
+
+        return Ok(());
+    }
+
+    #[test]
+    fn test_read_grid() -> GenericResult<()> {
+        let (log, switch) = crate::log::create_logger();
+        switch.disable();
+
+        let dir = std::env::temp_dir().join(format!("rsfluid-test-read-{}", std::process::id()));
+        std::fs::create_dir_all(&dir)?;
+
+        let mut grid = Grid::new(dim!(4, 3), 0.25);
+        grid.cell_mut(idx!(2, 1)).set_velocity(vec2!(1.0, 2.0));
+        *grid.cell_mut(idx!(3, 2)).smoke = 0.5;
+        *grid.cell_mut(idx!(0, 0)).mode = crate::scene::cell::CellTypes::Solid;
+        grid.stats[1].pressure = 3.0;
+
+        for bundle in [true, false] {
+            let output = format!("{}/fields-{{}}-{}", dir.to_str().unwrap(), bundle);
+            let writer = NpyWriter::new(output.clone(), bundle);
+            writer.write_grid(&log, &grid, 0.5, 12)?;
+            writer.write_grid(&log, &grid, 0.75, 3)?;
+
+            let frames = find_frames(&output)?;
+            assert!(frames.iter().map(|(s, _)| *s).collect::<Vec<_>>() == [3, 12]);
+
+            let (read, meta) = read_grid::<Scalar>(&frames[1].1)?;
+            assert!(meta.step == 12 && meta.t == 0.5);
+            assert!(read.dim == grid.dim && read.cell_width == grid.cell_width);
+            assert!(read.stats == grid.stats);
+            assert!(grid_arrays(&read) == grid_arrays(&grid));
+        }
+
+        std::fs::remove_dir_all(&dir)?;
+
+        return Ok(());
+    }
+
+    #[test]
+    fn test_stats_recorder() -> GenericResult<()> {
+        let mut grid = Grid::new(dim!(4, 2), 0.5);
+        for idx in grid.iter_index().collect::<Vec<_>>() {
+            grid.cell_mut(idx).set_velocity(vec2!(2.0, 0.0));
+            *grid.cell_mut(idx).smoke = 0.5;
+        }
+        *grid.cell_mut(idx!(1, 1)).mode = crate::scene::cell::CellTypes::Solid;
+        let stats = crate::scene::cell_stats::Stats::from(grid.cell(idx!(2, 1)));
+        grid.stats = [stats.clone(), stats];
+
+        // 7 fluid cells of area 0.25.
+        grid.div_residual = 0.25;
+        let record = StatsRecord::from_grid(&grid, 3, 0.5, 2.0);
+        assert!(record.smoke == 7.0 * 0.25 * 0.5);
+        assert!(record.kinetic_energy == 7.0 * 0.25 * 0.5 * 2.0 * 4.0);
+        assert!(record.div_residual == 0.25 && record.t == 0.5);
+        assert!(grid.max_divergence() == 0.0);
+
+        let mut recorder = StatsRecorder::new("stats".to_string());
+        recorder.records.push(record.clone());
+        recorder.records.push(StatsRecord { step: 4, t: 1.0, ..record });
+
+        let mut csv = vec![];
+        recorder.write_csv(&mut csv)?;
+        let csv = String::from_utf8(csv)?;
+        let lines = csv.lines().collect::<Vec<_>>();
+        assert!(lines.len() == 3 && lines[0].starts_with("step,t,pressure_min"));
+        assert!(lines[2].starts_with("4,1,") && lines[2].ends_with(",0.875,7,0.25"));
+
+        let charts = recorder.charts();
+        assert!(charts.len() == 6 && charts[0].series.len() == 2);
+        assert!(charts[5].series[0].points == [(0.5, 0.25), (1.0, 0.25)]);
+
+        // An inflow through the lower face of the fluid cell `(2, 1)`.
+        grid.cell_mut(idx!(2, 1)).set_velocity(vec2!(2.0, 0.25));
+        assert!(grid.max_divergence() == 0.25);
+
+        let image = crate::plotting::line_charts(idx!(800, 900), idx!(2, 3), &charts, "t")?;
+        assert!(image.data.len() == 800 * 900 * 3);
+
+        return Ok(());
+    }
+
+    #[test]
+    fn test_force_recorder() -> GenericResult<()> {
+        let record = |step: u64, drag: f64, lift: f64| ForceRecord {
+            step,
+            t: step as f64 * 0.5,
+            obstacle: 0,
+            force: [drag, lift],
+            drag,
+            lift,
+        };
+
+        let mut recorder = ForceRecorder::new("forces".to_string(), 2.0);
+        recorder.records = vec![
+            record(0, 9.0, 9.0),
+            record(1, 1.0, 0.5),
+            record(2, 2.0, -0.5),
+        ];
+
+        // The start-up (first half) is skipped.
+        assert!(recorder.mean_coefficients(0) == Some((1.5, 0.0)));
+        assert!(recorder.mean_coefficients(1).is_none());
+
+        let mut csv = vec![];
+        recorder.write_csv(&mut csv)?;
+        let csv = String::from_utf8(csv)?;
+        let lines = csv.lines().collect::<Vec<_>>();
+        assert!(lines.len() == 4 && lines[0].starts_with("step,t,obstacle,force_x"));
+        assert!(lines[3] == "2,1,0,2,-0.5,2,-0.5");
+
+        let charts = recorder.charts();
+        assert!(charts.len() == 2 && charts[1].series.len() == 1);
+        assert!(charts[1].series[0].points == [(0.0, 9.0), (0.5, 0.5), (1.0, -0.5)]);
+
+        return Ok(());
+    }
+
+    #[test]
+    fn test_shedding_frequency() {
+        let signal = |f: f64, offset: f64| {
+            return (0..400)
+                .map(|i| {
+                    let t = i as f64 * 0.01;
+                    return (t, offset + (2.0 * std::f64::consts::PI * f * t).sin());
+                })
+                .collect::<Vec<_>>();
+        };
+
+        let f = zero_crossing_frequency(&signal(2.5, 3.0), 0.25).unwrap();
+        assert!((f - 2.5).abs() < 1e-3);
+        assert!(zero_crossing_frequency(&signal(0.0, 1.0), 0.25).is_none());
+
+        // Small oscillations around zero are ignored by the hysteresis.
+        let mut noisy = signal(2.5, 0.0);
+        for (i, s) in noisy.iter_mut().enumerate() {
+            s.1 += if i % 2 == 0 { 0.05 } else { -0.05 };
+        }
+        let f = zero_crossing_frequency(&noisy, 0.25).unwrap();
+        assert!((f - 2.5).abs() < 0.05);
+
+        let mut analysis = SheddingAnalysis::new("shedding".to_string(), None, 2.0);
+        analysis.diameter = 0.4;
+        analysis.samples = signal(5.0, 0.0);
+        analysis.samples.extend(signal(2.5, 0.0).iter().map(|(t, v)| (t + 4.0, *v)));
+
+        // The start-up (first half) is skipped.
+        let summary = analysis.summary();
+        assert!(summary.samples == 400 && summary.window[0] == 4.0);
+        assert!((summary.strouhal.unwrap() - 2.5 * 0.4 / 2.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_shedding_without_probe() {
+        use crate::log::*;
+        use crate::scene::timestepper::{ExecutionMode, TimeStepper};
+
+        let (log, _) = create_logger();
+        let grid = Grid::new(dim!(10, 5), 0.1);
+        let timestepper = TimeStepper::new(
+            &log,
+            1.0,
+            Vector2::zeros(),
+            1,
+            ExecutionMode::Single,
+            vec![Box::new(grid)],
+            vec![],
+        );
+
+        // Without an obstacle the probe doesn't fall back to the corner.
+        let mut analysis = SheddingAnalysis::new("shedding".to_string(), None, 2.0);
+        analysis.record(&log, &timestepper);
+        analysis.record(&log, &timestepper);
+        assert!(analysis.initialized && analysis.samples.is_empty());
+        assert!(analysis.summary().probe.is_none());
+
+        let mut analysis =
+            SheddingAnalysis::new("shedding".to_string(), Some(vec2!(0.5, 0.25)), 2.0);
+        analysis.record(&log, &timestepper);
+        assert!(analysis.samples == [(0.0, 0.0)]);
+        assert!(analysis.summary().probe == Some([0.5, 0.25]));
+    }
+
+    #[test]
+    fn test_probe_profiles() -> GenericResult<()> {
+        use crate::scene::probes::ProbeSample;
+
+        let sample = |v: Scalar| ProbeSample {
+            velocity: vec2!(v, 0.0),
+            pressure: 2.0 * v,
+            smoke: 0.5,
+        };
+
+        let mut csv = vec![];
+        write_probe_sample(&mut csv, 3, 0.5, 1, vec2!(1.0, 0.25), &sample(1.5))?;
+        assert!(String::from_utf8(csv)? == "3,0.5,1,1,0.25,1.5,0,3,0.5\n");
+
+        let profiles = [0.5, 1.0].map(|t| Profile {
+            t,
+            samples: vec![(0.0, sample(t)), (1.0, sample(2.0 * t))],
+        });
+
+        let charts = profile_charts(&profiles);
+        assert!(charts.len() == 4 && charts[2].title == "pressure");
+        assert!(charts[0].series[1].name == "t = 1.000");
+        assert!(charts[2].series[1].points == [(0.0, 2.0), (1.0, 4.0)]);
+
+        return Ok(());
+    }
+}
@@ -0,0 +1,322 @@
+use crate::log::*;
+use crate::scene::cell::CellTypes;
+use crate::scene::grid::Grid;
+use crate::scene::timestepper::TimeStepper;
+use crate::types::*;
+
+use std::fs::{File, OpenOptions};
+use std::io::{BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+/// A data array in a VTK file (all values are written as `Float64` or `UInt8`).
+enum DataArray {
+    Float64 {
+        name: &'static str,
+        components: usize,
+        values: Vec<f64>,
+    },
+    UInt8 {
+        name: &'static str,
+        values: Vec<u8>,
+    },
+}
+
+impl DataArray {
+    fn bytes(&self) -> Vec<u8> {
+        return match self {
+            DataArray::Float64 { values, .. } => {
+                values.iter().flat_map(|v| v.to_le_bytes()).collect()
+            }
+            DataArray::UInt8 { values, .. } => values.clone(),
+        };
+    }
+
+    fn header(&self, offset: usize) -> String {
+        return match self {
+            DataArray::Float64 {
+                name, components, ..
+            } => format!(
+                r#"<DataArray type="Float64" Name="{}" NumberOfComponents="{}" format="appended" offset="{}"/>"#,
+                name, components, offset
+            ),
+            DataArray::UInt8 { name, .. } => format!(
+                r#"<DataArray type="UInt8" Name="{}" NumberOfComponents="1" format="appended" offset="{}"/>"#,
+                name, offset
+            ),
+        };
+    }
+}
+
+/// Collect all cell data arrays of the grid.
+/// The velocity is interpolated to the cell centers, with `raw_faces`
+/// the staggered face values (`u` on the left face, `v` on the bottom face)
+/// are added too.
+fn cell_data<T: Float>(grid: &Grid<T>, raw_faces: bool) -> Vec<DataArray> {
+    let fields = grid.fields();
+    let dim = grid.dim;
+    let to_f64 = |values: &[T]| values.iter().map(|v| to_float::<f64>(*v)).collect();
+
+    let [u, v] = [&fields.velocity[0].back, &fields.velocity[1].back];
+
+    let mut velocity = Vec::with_capacity(3 * fields.len());
+    for j in 0..dim.y {
+        for i in 0..dim.x {
+            let k = i + j * dim.x;
+
+            // Average the faces (the last faces on the border are the only ones).
+            let u_c = if i + 1 < dim.x { (u[k] + u[k + 1]) * to_float(0.5) } else { u[k] };
+            let v_c = if j + 1 < dim.y { (v[k] + v[k + dim.x]) * to_float(0.5) } else { v[k] };
+
+            velocity.extend([to_float::<f64>(u_c), to_float(v_c), 0.0]);
+        }
+    }
+
+    let mut arrays = vec![
+        DataArray::Float64 {
+            name: "velocity",
+            components: 3,
+            values: velocity,
+        },
+        DataArray::Float64 {
+            name: "pressure",
+            components: 1,
+            values: to_f64(&fields.pressure),
+        },
+        DataArray::Float64 {
+            name: "smoke",
+            components: 1,
+            values: to_f64(&fields.smoke.back),
+        },
+        DataArray::Float64 {
+            name: "divergence",
+            components: 1,
+            values: to_f64(&fields.div),
+        },
+        DataArray::UInt8 {
+            name: "solid",
+            values: fields
+                .mode
+                .iter()
+                .map(|m| (*m == CellTypes::Solid) as u8)
+                .collect(),
+        },
+    ];
+
+    if raw_faces {
+        arrays.push(DataArray::Float64 {
+            name: "velocity_x_face",
+            components: 1,
+            values: to_f64(u),
+        });
+        arrays.push(DataArray::Float64 {
+            name: "velocity_y_face",
+            components: 1,
+            values: to_f64(v),
+        });
+    }
+
+    return arrays;
+}
+
+/// Write all fields of the grid as cell data into a VTK XML ImageData file (`.vti`)
+/// with raw appended (little-endian) data.
+pub fn write_vti<T: Float>(grid: &Grid<T>, file: &str, raw_faces: bool) -> GenericResult<()> {
+    let arrays = cell_data(grid, raw_faces);
+    let h = to_float::<f64>(grid.cell_width);
+    let extent = format!("0 {} 0 {} 0 0", grid.dim.x, grid.dim.y);
+
+    let mut w = BufWriter::new(File::create(file)?);
+
+    writeln!(w, r#"<?xml version="1.0"?>"#)?;
+    writeln!(
+        w,
+        r#"<VTKFile type="ImageData" version="1.0" byte_order="LittleEndian" header_type="UInt64">"#
+    )?;
+    writeln!(
+        w,
+        r#"  <ImageData WholeExtent="{}" Origin="0 0 0" Spacing="{} {} {}">"#,
+        extent, h, h, h
+    )?;
+    writeln!(w, r#"    <Piece Extent="{}">"#, extent)?;
+    writeln!(w, r#"      <CellData Scalars="pressure" Vectors="velocity">"#)?;
+
+    // Each array in the appended data has an 8 byte header with its size.
+    let data: Vec<Vec<u8>> = arrays.iter().map(|a| a.bytes()).collect();
+    let mut offset = 0;
+    for (array, bytes) in arrays.iter().zip(data.iter()) {
+        writeln!(w, "        {}", array.header(offset))?;
+        offset += 8 + bytes.len();
+    }
+
+    writeln!(w, r#"      </CellData>"#)?;
+    writeln!(w, r#"    </Piece>"#)?;
+    writeln!(w, r#"  </ImageData>"#)?;
+    write!(w, r#"  <AppendedData encoding="raw">"#)?;
+    write!(w, "\n   _")?;
+
+    for bytes in data.iter() {
+        w.write_all(&(bytes.len() as u64).to_le_bytes())?;
+        w.write_all(bytes)?;
+    }
+
+    writeln!(w, "\n  </AppendedData>")?;
+    writeln!(w, "</VTKFile>")?;
+    w.flush()?;
+
+    return Ok(());
+}
+
+const PVD_FOOTER: &str = "  </Collection>\n</VTKFile>\n";
+
+fn pvd_entry(t: Scalar, file: &str) -> String {
+    return format!(
+        "    <DataSet timestep=\"{}\" part=\"0\" file=\"{}\"/>\n",
+        t, file
+    );
+}
+
+/// Write a `.pvd` collection file which indexes the files in `entries` by simulation time.
+pub fn write_pvd(file: &str, entries: &[(Scalar, String)]) -> GenericResult<()> {
+    let mut w = BufWriter::new(File::create(file)?);
+
+    writeln!(w, r#"<?xml version="1.0"?>"#)?;
+    writeln!(w, r#"<VTKFile type="Collection" version="0.1">"#)?;
+    writeln!(w, r#"  <Collection>"#)?;
+    for (t, f) in entries {
+        write!(w, "{}", pvd_entry(*t, f))?;
+    }
+    write!(w, "{}", PVD_FOOTER)?;
+    w.flush()?;
+
+    return Ok(());
+}
+
+/// Append the file `entry` at time `t` to the `.pvd` collection `file`
+/// (written by [`write_pvd`]) by overwriting only its closing tags.
+pub fn append_pvd(file: &str, t: Scalar, entry: &str) -> GenericResult<()> {
+    let mut f = OpenOptions::new().read(true).write(true).open(file)?;
+
+    let footer = PVD_FOOTER.len() as i64;
+    let mut end = vec![0; PVD_FOOTER.len()];
+    f.seek(SeekFrom::End(-footer))?;
+    f.read_exact(&mut end)?;
+    if end != PVD_FOOTER.as_bytes() {
+        bail!("The collection file '{}' does not end with its closing tags.", file);
+    }
+
+    f.seek(SeekFrom::End(-footer))?;
+    f.write_all(format!("{}{}", pvd_entry(t, entry), PVD_FOOTER).as_bytes())?;
+
+    return Ok(());
+}
+
+/// Read the entries `(t, file)` of a `.pvd` collection file written by [`write_pvd`].
+pub fn read_pvd(file: &str) -> GenericResult<Vec<(Scalar, String)>> {
+    let attribute = |line: &str, name: &str| {
+        let start = line.find(&format!("{}=\"", name))? + name.len() + 2;
+        let len = line[start..].find('"')?;
+        return Some(line[start..start + len].to_string());
+    };
+
+    let mut entries = vec![];
+    for line in std::fs::read_to_string(file)?.lines() {
+        if !line.trim_start().starts_with("<DataSet") {
+            continue;
+        }
+
+        match (attribute(line, "timestep"), attribute(line, "file")) {
+            (Some(t), Some(f)) => entries.push((t.parse::<Scalar>()?, f)),
+            _ => bail!("Invalid data set '{}' in '{}'.", line.trim(), file),
+        }
+    }
+
+    return Ok(entries);
+}
+
+/// Writes the fields of each frame into `.vti` files and appends them
+/// to the `.pvd` collection of all written frames.
+pub struct VtkWriter {
+    /// The output pattern for the `.vti` files (`{}` is replaced by the step).
+    pub output: String,
+    /// Also write the raw staggered velocity faces.
+    pub raw_faces: bool,
+
+    pvd_file: String,
+    /// Whether the collection file of this run exists (is appended to).
+    pvd_started: bool,
+}
+
+impl VtkWriter {
+    /// The collection file is the output pattern without `{}` and extension `.pvd`,
+    /// e.g. `./frames/fields-{}.vti` -> `./frames/fields.pvd`.
+    pub fn new(output: String, raw_faces: bool) -> VtkWriter {
+        let pvd_file = Path::new(&output.replace("-{}", "").replace("{}", ""))
+            .with_extension("pvd")
+            .to_string_lossy()
+            .to_string();
+
+        return VtkWriter {
+            output,
+            raw_faces,
+            pvd_file,
+            pvd_started: false,
+        };
+    }
+
+    pub fn pvd_file(&self) -> &str {
+        return &self.pvd_file;
+    }
+
+    /// Continue the collection of a run restarted at time `t`: the frames up to `t`
+    /// are kept, the later ones are dropped (they are written again).
+    pub fn resume(&mut self, log: &Logger, t: Scalar) -> GenericResult<()> {
+        if !Path::new(&self.pvd_file).exists() {
+            warn!(log, "No collection file '{}' to resume.", self.pvd_file);
+            return Ok(());
+        }
+
+        let mut entries = read_pvd(&self.pvd_file)?;
+        entries.retain(|(t_entry, _)| *t_entry <= t);
+        info!(
+            log,
+            "Resume the collection '{}' with {} frames.",
+            self.pvd_file,
+            entries.len()
+        );
+
+        write_pvd(&self.pvd_file, &entries)?;
+        self.pvd_started = true;
+
+        return Ok(());
+    }
+
+    pub fn write(&mut self, log: &Logger, timestepper: &TimeStepper, step: u64) -> GenericResult<()> {
+        let grid = timestepper.objects[0]
+            .as_any()
+            .downcast_ref::<Grid>()
+            .expect("Not a grid");
+
+        let file = self.output.replace("{}", &format!("{:06}", step));
+        info!(log, "Saving fields to '{}'.", file);
+
+        write_vti(grid, &file, self.raw_faces)?;
+
+        // The files are referenced relative to the collection file.
+        let name = Path::new(&file)
+            .file_name()
+            .map_or(file.clone(), |f| f.to_string_lossy().to_string());
+
+        return self.add_to_collection(timestepper.t(), &name);
+    }
+
+    /// Add the file `name` at time `t` to the collection, which is created
+    /// at the first frame of the run.
+    pub fn add_to_collection(&mut self, t: Scalar, name: &str) -> GenericResult<()> {
+        if !self.pvd_started {
+            self.pvd_started = true;
+            return write_pvd(&self.pvd_file, &[(t, name.to_string())]);
+        }
+
+        return append_pvd(&self.pvd_file, t, name);
+    }
+}
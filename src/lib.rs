@@ -7,6 +7,7 @@
 #[macro_use]
 extern crate derive_builder;
 
+pub mod export;
 pub mod log;
 pub mod math;
 pub mod plotting;
@@ -1,10 +1,30 @@
 use indicatif::{ProgressBar, ProgressState, ProgressStyle};
+use rayon::prelude::*;
 use std::fmt::Write;
 use std::fs::create_dir_all;
 
+use rsfluid::export::{
+    find_frames, read_grid, ForceRecorder, NpyWriter, ProbeRecorder, SheddingAnalysis,
+    StatsRecorder, VtkWriter,
+};
 use rsfluid::log::*;
-use rsfluid::scene::setup::{parse_args, setup_scene, CLIArgs};
-use rsfluid::scene::visualization::{save_plots, PlotParams, PlotParamsBuilder};
+use rsfluid::plotting::{
+    load_colormaps, AnimationFormat, AnimationParamsBuilder, Colormap, CustomColormaps,
+    FrameStream, Interpolation, RangeMode, Renderer, StreamFormat,
+};
+use rsfluid::scene::checkpoint::{load_checkpoint, save_checkpoint};
+use rsfluid::scene::grid::Grid;
+use rsfluid::scene::probes::{load_probes, ProbeConfig, ProbeLine};
+use rsfluid::scene::setup::{
+    inflow_velocity, parse_args, setup_scene, thread_pool_builder, CLIArgs, Command, OutputFormat,
+    PlotArgs, PlotInterpolation, PlotRenderer, RenderArgs, StreamlineSeeds,
+};
+use rsfluid::scene::streamlines::Seeds;
+use rsfluid::scene::visualization::{
+    frame_ranges, include_global_ranges, render_grid_plots_in_ranges, render_plots,
+    save_rendered_plots, FrameRanges, PlotAnimations, PlotParams, PlotParamsBuilder, PlotStream,
+    TerminalPreview,
+};
 use rsfluid::types::*;
 
 fn assert_output_path(output: &str) {
@@ -15,7 +35,13 @@ fn assert_output_path(output: &str) {
 
 fn main() -> GenericResult<()> {
     let cli = parse_args();
-    return run(&cli);
+
+    thread_pool_builder(&cli).build_global()?;
+
+    return match cli.command {
+        Command::Simulate(ref args) => run(args),
+        Command::Render(ref args) => render(args),
+    };
 }
 
 fn create_progressbar(steps: u64) -> ProgressBar {
@@ -33,8 +59,61 @@ fn create_progressbar(steps: u64) -> ProgressBar {
     return pb;
 }
 
-fn create_plot_params(cli: &CLIArgs) -> PlotParams {
-    return PlotParamsBuilder::default()
+/// Apply the `--plot-colormap` and `--plot-range` options to the default field styles.
+fn set_field_styles(cli: &PlotArgs, builder: &mut PlotParamsBuilder) -> GenericResult<()> {
+    let custom = match cli.plot_colormap_config {
+        Some(ref file) => load_colormaps(file)?,
+        None => CustomColormaps::new(),
+    };
+
+    let defaults = PlotParamsBuilder::default().build()?;
+    let mut styles = [
+        ("vel", defaults.velocity_style),
+        ("press", defaults.pressure_style),
+        ("vort", defaults.vorticity_style),
+        ("div", defaults.divergence_style),
+    ];
+
+    for (field, name) in cli.plot_colormap.iter() {
+        let style = &mut styles.iter_mut().find(|(f, _)| f == field).unwrap().1;
+        style.colormap = Colormap::parse(name, &custom)?;
+    }
+
+    for (field, range) in cli.plot_range.iter() {
+        let style = &mut styles.iter_mut().find(|(f, _)| f == field).unwrap().1;
+        style.range = range.parse::<RangeMode>()?;
+    }
+
+    let [vel, press, vort, div] = styles.map(|(_, s)| s);
+    builder
+        .velocity_style(vel)
+        .pressure_style(press)
+        .vorticity_style(vort)
+        .divergence_style(div);
+
+    return Ok(());
+}
+
+fn create_plot_params(cli: &PlotArgs) -> GenericResult<PlotParams> {
+    let interpolation = match cli.plot_interpolation {
+        PlotInterpolation::Nearest => Interpolation::Nearest,
+        PlotInterpolation::Bilinear => Interpolation::Bilinear,
+    };
+
+    let renderer = match cli.plot_renderer {
+        PlotRenderer::Plotters => Renderer::Plotters,
+        PlotRenderer::Direct => Renderer::Direct(interpolation),
+    };
+
+    let seeds = match cli.plot_streamline_seeds {
+        StreamlineSeeds::Lattice => Seeds::Lattice(cli.plot_streamline_spacing),
+        StreamlineSeeds::Inflow => Seeds::Inflow(cli.plot_streamline_spacing),
+    };
+
+    let mut builder = PlotParamsBuilder::default();
+    set_field_styles(cli, &mut builder)?;
+
+    return Ok(builder
         .with_pressure(cli.plot_pressure)
         .with_velocity(cli.plot_velocity)
         .output(cli.output.clone())
@@ -42,36 +121,410 @@ fn create_plot_params(cli: &CLIArgs) -> PlotParams {
         .with_stats(cli.plot_stats)
         .with_velocity_masked(cli.plot_masked_velocity)
         .with_pressure_masked(cli.plot_masked_pressure)
-        .build()
-        .unwrap();
+        .with_vorticity(cli.plot_vorticity)
+        .with_vorticity_masked(cli.plot_masked_vorticity)
+        .with_divergence(cli.plot_divergence)
+        .with_divergence_masked(cli.plot_masked_divergence)
+        .renderer(renderer)
+        .quiver(cli.plot_quiver)
+        .quiver_scale(cli.plot_quiver_scale)
+        .quiver_clamp(cli.plot_quiver_clamp)
+        .with_streamlines(cli.plot_streamlines)
+        .streamline_seeds(seeds)
+        .with_lic(cli.plot_lic)
+        .lic_colored(cli.plot_lic_colored)
+        .lic_length(cli.plot_lic_length)
+        .layout(cli.plot_layout)
+        .panels(cli.plot_panels.clone())
+        .with_colorbar(cli.plot_colorbar)
+        .colorbar_ticks(cli.plot_colorbar_ticks)
+        .build()?);
+}
+
+/// Create the animations for the formats `gif` and `apng` in `formats`.
+fn create_animations(cli: &PlotArgs, formats: &[OutputFormat], frames: u64) -> Vec<PlotAnimations> {
+    let mut animations = vec![];
+
+    for (format, anim_format) in [
+        (OutputFormat::Gif, AnimationFormat::Gif),
+        (OutputFormat::Apng, AnimationFormat::Apng),
+    ] {
+        if formats.contains(&format) {
+            assert_output_path(&cli.animation_output);
+
+            let params = AnimationParamsBuilder::default()
+                .format(anim_format)
+                .frame_rate(cli.frame_rate)
+                .loops(cli.loops)
+                .quantization_speed(cli.gif_quantization_speed)
+                .frames(frames as u32)
+                .build()
+                .unwrap();
+
+            animations.push(PlotAnimations::new(cli.animation_output.clone(), params));
+        }
+    }
+
+    return animations;
+}
+
+/// Create the frame stream for the format `rgba` or `y4m` in `formats`.
+fn create_stream(cli: &PlotArgs, formats: &[OutputFormat]) -> GenericResult<Option<PlotStream>> {
+    let stream_formats = [
+        (OutputFormat::Rgba, StreamFormat::Rgba),
+        (OutputFormat::Y4m, StreamFormat::Y4m),
+    ]
+    .into_iter()
+    .filter(|(f, _)| formats.contains(f))
+    .collect::<Vec<_>>();
+
+    return match stream_formats[..] {
+        [] => Ok(None),
+        [(_, format)] => {
+            if cli.stream_output != "-" {
+                assert_output_path(&cli.stream_output);
+            }
+            let stream = FrameStream::new(&cli.stream_output, format, cli.frame_rate)?;
+            Ok(Some(PlotStream::new(cli.stream_plot.clone(), stream)))
+        }
+        _ => bail!("Only one of the output formats 'rgba' and 'y4m' can be streamed."),
+    };
+}
+
+/// The probes from the probes file and the command line.
+fn create_probe_config(cli: &CLIArgs) -> GenericResult<ProbeConfig> {
+    let mut config = match cli.probes_file {
+        Some(ref file) => load_probes(file)?,
+        None => ProbeConfig::default(),
+    };
+
+    config.points.extend(cli.probe_points.iter().cloned());
+    config
+        .lines
+        .extend(cli.probe_lines.iter().map(|l| ProbeLine {
+            start: vec2!(l[0], l[1]),
+            end: vec2!(l[2], l[3]),
+            samples: cli.probe_line_samples,
+        }));
+
+    return Ok(config);
 }
 
 fn run(cli: &CLIArgs) -> GenericResult<()> {
     let (log, switch) = create_logger();
 
-    assert_output_path(&cli.output);
+    let with_png = cli.output_format.contains(&OutputFormat::Png);
+    let mut vtk_writer = None;
+
+    if with_png {
+        assert_output_path(&cli.plot.output);
+    }
+
+    if cli.output_format.contains(&OutputFormat::Vti) {
+        assert_output_path(&cli.vti_output);
+        vtk_writer = Some(VtkWriter::new(cli.vti_output.clone(), cli.vti_raw_faces));
+    }
+
+    let mut npy_writers = vec![];
+    for (format, bundle) in [(OutputFormat::Npy, false), (OutputFormat::Npz, true)] {
+        if cli.output_format.contains(&format) {
+            assert_output_path(&cli.npy_output);
+            npy_writers.push(NpyWriter::new(cli.npy_output.clone(), bundle));
+        }
+    }
+    if cli.checkpoint_every.is_some() {
+        assert_output_path(&cli.checkpoint_output);
+    }
+
+    let mut stats_recorder = None;
+    if cli.record_stats {
+        assert_output_path(&cli.stats_output);
+        stats_recorder = Some(StatsRecorder::new(cli.stats_output.clone()));
+    }
+
+    let mut force_recorder = None;
+    if cli.record_forces {
+        assert_output_path(&cli.forces_output);
+        force_recorder = Some(ForceRecorder::new(
+            cli.forces_output.clone(),
+            inflow_velocity::<Scalar>().norm(),
+        ));
+    }
+
+    let mut shedding = None;
+    if cli.shedding_analysis {
+        assert_output_path(&cli.shedding_output);
+        shedding = Some(SheddingAnalysis::new(
+            cli.shedding_output.clone(),
+            cli.shedding_probe,
+            inflow_velocity::<Scalar>().norm(),
+        ));
+    }
+
+    let mut probes = None;
+    let probe_config = create_probe_config(cli)?;
+    if !probe_config.is_empty() {
+        assert_output_path(&cli.probes_output);
+        probes = Some(ProbeRecorder::new(
+            cli.probes_output.clone(),
+            probe_config,
+            cli.probe_profile_times.clone(),
+        )?);
+    }
 
     let dt = cli.dt;
     let n_steps = (cli.time_end / dt) as u64;
 
     let mut progress = None;
 
-    if cli.show_progress {
+    let mut preview = None;
+
+    if cli.preview_every.is_some() {
+        switch.disable();
+        preview = Some(TerminalPreview::new(
+            cli.preview_plot.clone(),
+            cli.preview_size,
+            create_plot_params(&cli.plot)?,
+        ));
+    } else if cli.show_progress {
         switch.disable();
         progress = Some(create_progressbar(n_steps));
     }
 
-    let mut timestepper = setup_scene(&log, &cli)?;
-    let plot_params = create_plot_params(&cli);
+    let mut timestepper = setup_scene::<Scalar>(&log, &cli)?;
+    let plot_params = create_plot_params(&cli.plot)?;
 
-    for step in 0..n_steps {
+    let mut start_step = 0;
+    if let Some(ref file) = cli.restart_from {
+        start_step = load_checkpoint(file, &mut timestepper)?;
+        info!(log, "Restart from '{}' at step '{}'.", file, start_step);
+
+        if let Some(ref mut w) = vtk_writer {
+            w.resume(&log, timestepper.t())?;
+        }
+
+        if let Some(ref p) = progress {
+            p.set_position(start_step);
+        }
+    }
+
+    let mut animations = create_animations(
+        &cli.plot,
+        &cli.output_format,
+        n_steps.saturating_sub(start_step),
+    );
+    let mut stream = create_stream(&cli.plot, &cli.output_format)?;
+
+    for step in start_step..n_steps {
         timestepper.compute_step(dt);
 
-        save_plots(&log, &timestepper, step, &plot_params)?;
+        if let Some(ref mut r) = stats_recorder {
+            r.record(&timestepper, step);
+        }
+
+        if let Some(ref mut r) = force_recorder {
+            r.record(&timestepper, step);
+        }
+
+        if let Some(ref mut s) = shedding {
+            s.record(&log, &timestepper);
+        }
+
+        if let Some(ref mut p) = probes {
+            p.record(&timestepper, step)?;
+        }
+
+        if with_png || !animations.is_empty() || stream.is_some() {
+            let images = render_plots(&timestepper, step, &plot_params)?;
+            if with_png {
+                save_rendered_plots(&log, &images, step, &plot_params)?;
+            }
+            for a in animations.iter_mut() {
+                a.add_frames(&log, &images)?;
+            }
+            if let Some(ref mut s) = stream {
+                s.add_frames(&log, &images)?;
+            }
+        }
+
+        if let Some(ref mut w) = vtk_writer {
+            w.write(&log, &timestepper, step)?;
+        }
+
+        for w in npy_writers.iter() {
+            w.write(&log, &timestepper, step)?;
+        }
+
+        if let Some(n) = cli.checkpoint_every {
+            if n > 0 && (step + 1) % n == 0 {
+                let file = cli
+                    .checkpoint_output
+                    .replace("{}", &format!("{:06}", step + 1));
+                info!(log, "Write checkpoint '{}'.", file);
+                save_checkpoint(&file, step + 1, &timestepper)?;
+            }
+        }
+
+        if let (Some(ref mut p), Some(n)) = (&mut preview, cli.preview_every) {
+            if n > 0 && ((step + 1) % n == 0 || step + 1 == n_steps) {
+                p.show(&timestepper, step, n_steps)?;
+            }
+        }
+
+        if let Some(ref p) = progress {
+            p.inc(1);
+        }
+    }
+
+    for a in animations {
+        a.finish()?;
+    }
+
+    if let Some(s) = stream {
+        s.finish()?;
+    }
+
+    if let Some(r) = stats_recorder {
+        r.finish(&log)?;
+    }
+
+    if let Some(r) = force_recorder {
+        r.finish(&log)?;
+    }
+
+    if let Some(s) = shedding {
+        s.finish(&log)?;
+    }
+
+    if let Some(p) = probes {
+        p.finish(&log)?;
+    }
+
+    return Ok(());
+}
+
+/// Plot all frames of fields written by `simulate`.
+fn render(cli: &RenderArgs) -> GenericResult<()> {
+    let (log, switch) = create_logger();
+
+    for format in cli.output_format.iter() {
+        if [OutputFormat::Vti, OutputFormat::Npy, OutputFormat::Npz].contains(format) {
+            bail!("Output format '{:?}' is not supported by 'render'.", format);
+        }
+    }
+
+    let with_png = cli.output_format.contains(&OutputFormat::Png);
+    if with_png {
+        assert_output_path(&cli.plot.output);
+    }
+
+    let frames = find_frames(&cli.input)?;
+    info!(log, "Render {} frames from '{}'.", frames.len(), cli.input);
+
+    let mut progress = None;
+    if cli.show_progress {
+        switch.disable();
+        progress = Some(create_progressbar(frames.len() as u64));
+    }
+
+    let plot_params = create_plot_params(&cli.plot)?;
+
+    if cli.two_pass {
+        info!(log, "Compute the global ranges of {} frames.", frames.len());
+
+        let scan_frame = |(_, base): &(u64, String)| -> Result<_, String> {
+            let (grid, _) = read_grid::<Scalar>(base).map_err(|e| format!("'{}': {}", base, e))?;
+            include_global_ranges(&grid, &plot_params);
+            return Ok(());
+        };
+
+        if cli.parallel {
+            frames.par_iter().try_for_each(scan_frame)?;
+        } else {
+            frames.iter().try_for_each(scan_frame)?;
+        }
+    }
+
+    let mut animations = create_animations(&cli.plot, &cli.output_format, frames.len() as u64);
+    let mut stream = create_stream(&cli.plot, &cli.output_format)?;
+    let with_images = !animations.is_empty() || stream.is_some();
+
+    let read_frame = |(step, base): &(u64, String)| -> Result<_, String> {
+        let (grid, _) = read_grid::<Scalar>(base).map_err(|e| format!("'{}': {}", base, e))?;
+        return Ok((*step, grid));
+    };
+
+    let render_frame = |(step, grid, ranges): &(u64, Grid<Scalar>, FrameRanges)| {
+        let mut images = vec![];
+        if with_png || with_images {
+            images = render_grid_plots_in_ranges(grid, *step, &plot_params, ranges)
+                .map_err(|e| e.to_string())?;
+        }
+
+        if with_png {
+            save_rendered_plots(&log, &images, *step, &plot_params).map_err(|e| e.to_string())?;
+        }
 
         if let Some(ref p) = progress {
             p.inc(1);
         }
+
+        return Ok::<_, String>(images);
+    };
+
+    // The range trackers need to be advanced and the animation and stream frames
+    // need to be appended in frame order, so frames are read and rendered in chunks
+    // of the number of threads, while the ranges are computed sequentially.
+    let chunk_size = if cli.parallel {
+        rayon::current_num_threads()
+    } else {
+        1
+    };
+
+    for chunk in frames.chunks(chunk_size.max(1)) {
+        // The errors are converted to strings as they need to be `Send`.
+        let grids: Vec<_> = if cli.parallel {
+            chunk
+                .par_iter()
+                .map(read_frame)
+                .collect::<Result<_, _>>()?
+        } else {
+            chunk.iter().map(read_frame).collect::<Result<_, _>>()?
+        };
+
+        let grids: Vec<_> = grids
+            .into_iter()
+            .map(|(step, grid)| {
+                let ranges = frame_ranges(&grid, &plot_params);
+                return (step, grid, ranges);
+            })
+            .collect();
+
+        let images: Vec<_> = if cli.parallel {
+            grids
+                .par_iter()
+                .map(render_frame)
+                .collect::<Result<_, _>>()?
+        } else {
+            grids.iter().map(render_frame).collect::<Result<_, _>>()?
+        };
+
+        for i in images.iter() {
+            for a in animations.iter_mut() {
+                a.add_frames(&log, i)?;
+            }
+            if let Some(ref mut s) = stream {
+                s.add_frames(&log, i)?;
+            }
+        }
+    }
+
+    for a in animations {
+        a.finish()?;
+    }
+
+    if let Some(s) = stream {
+        s.finish()?;
     }
 
     return Ok(());
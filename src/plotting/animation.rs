@@ -0,0 +1,159 @@
+use crate::plotting::Image;
+use crate::types::*;
+
+use std::fs::File;
+use std::io::BufWriter;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AnimationFormat {
+    Gif,
+    Apng,
+}
+
+impl AnimationFormat {
+    pub fn extension(&self) -> &'static str {
+        return match self {
+            AnimationFormat::Gif => "gif",
+            AnimationFormat::Apng => "png",
+        };
+    }
+}
+
+#[derive(Builder, Clone, Debug)]
+#[builder(pattern = "mutable")]
+pub struct AnimationParams {
+    #[builder(default = "AnimationFormat::Gif")]
+    pub format: AnimationFormat,
+
+    /// Frames per second.
+    #[builder(default = "25.0")]
+    pub frame_rate: f64,
+
+    /// Number of times the animation is played, `0` loops forever.
+    #[builder(default)]
+    pub loops: u16,
+
+    /// Sampling factor `1..=30` of the GIF palette quantization
+    /// (`1`: best quality, `30`: fastest).
+    #[builder(default = "10")]
+    pub quantization_speed: i32,
+
+    /// Number of frames (needed upfront for the APNG header).
+    #[builder(default)]
+    pub frames: u32,
+}
+
+enum Encoder {
+    Gif(gif::Encoder<BufWriter<File>>),
+    Apng(png::Writer<BufWriter<File>>),
+}
+
+/// Streams frames into an animated GIF or APNG file.
+/// The encoder is created with the size of the first frame.
+pub struct AnimationWriter {
+    file: String,
+    params: AnimationParams,
+    size: Index2,
+    encoder: Option<Encoder>,
+}
+
+impl AnimationWriter {
+    pub fn new(file: String, params: AnimationParams) -> Self {
+        return AnimationWriter {
+            file,
+            params,
+            size: idx!(0, 0),
+            encoder: None,
+        };
+    }
+
+    pub fn file(&self) -> &str {
+        return &self.file;
+    }
+
+    fn create_encoder(&self, size: Index2) -> GenericResult<Encoder> {
+        let p = &self.params;
+
+        if p.frame_rate.is_nan() || p.frame_rate <= 0.0 {
+            bail!("Frame rate '{}' must be positive.", p.frame_rate);
+        }
+
+        let w = BufWriter::new(File::create(&self.file)?);
+
+        return match p.format {
+            AnimationFormat::Gif => {
+                if size.x > u16::MAX as usize || size.y > u16::MAX as usize {
+                    bail!("Frame size {:?} is too large for a GIF.", size);
+                }
+                if !(1..=30).contains(&p.quantization_speed) {
+                    bail!(
+                        "Quantization speed '{}' must be in [1, 30].",
+                        p.quantization_speed
+                    );
+                }
+
+                let mut e = gif::Encoder::new(w, size.x as u16, size.y as u16, &[])?;
+                e.set_repeat(if p.loops == 0 {
+                    gif::Repeat::Infinite
+                } else {
+                    gif::Repeat::Finite(p.loops)
+                })?;
+
+                Ok(Encoder::Gif(e))
+            }
+            AnimationFormat::Apng => {
+                let mut e = png::Encoder::new(w, size.x as u32, size.y as u32);
+                e.set_color(png::ColorType::Rgb);
+                e.set_depth(png::BitDepth::Eight);
+                e.set_animated(p.frames, p.loops as u32)?;
+                e.set_frame_delay((1000.0 / p.frame_rate).round() as u16, 1000)?;
+
+                Ok(Encoder::Apng(e.write_header()?))
+            }
+        };
+    }
+
+    /// Append a frame. All frames need to have the same size.
+    pub fn add_frame(&mut self, image: &Image) -> GenericResult<()> {
+        if self.encoder.is_none() {
+            self.encoder = Some(self.create_encoder(image.size)?);
+            self.size = image.size;
+        } else if image.size != self.size {
+            bail!(
+                "Frame size {:?} differs from the animation size {:?}.",
+                image.size,
+                self.size
+            );
+        }
+
+        match self.encoder.as_mut().unwrap() {
+            Encoder::Gif(e) => {
+                let mut frame = gif::Frame::from_rgb_speed(
+                    image.size.x as u16,
+                    image.size.y as u16,
+                    &image.data,
+                    self.params.quantization_speed,
+                );
+                // In units of 10 ms.
+                frame.delay = (100.0 / self.params.frame_rate).round() as u16;
+                e.write_frame(&frame)?;
+            }
+            Encoder::Apng(e) => e.write_image_data(&image.data)?,
+        }
+
+        return Ok(());
+    }
+
+    /// Write the trailer of the file.
+    pub fn finish(self) -> GenericResult<()> {
+        match self.encoder {
+            Some(Encoder::Gif(e)) => {
+                e.into_inner()?;
+            }
+            Some(Encoder::Apng(e)) => e.finish()?,
+            None => {}
+        }
+
+        return Ok(());
+    }
+}
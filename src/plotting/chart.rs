@@ -0,0 +1,95 @@
+use crate::types::*;
+use plotters::prelude::*;
+use std::error::Error;
+
+use super::colormap::format_tick;
+use super::plot::Image;
+
+/// A line of a chart with the points `(x, y)`.
+pub struct Series {
+    pub name: String,
+    pub points: Vec<(f64, f64)>,
+}
+
+/// A line chart with a title and one or more lines.
+pub struct Chart {
+    pub title: String,
+    pub series: Vec<Series>,
+}
+
+/// The range of the values with a margin of 5% (of the value or `1` for constant values).
+fn value_range(values: impl Iterator<Item = f64>) -> std::ops::Range<f64> {
+    let (min, max) = values
+        .filter(|v| v.is_finite())
+        .fold((f64::MAX, f64::MIN), |(min, max), v| {
+            (min.min(v), max.max(v))
+        });
+
+    if min > max || !(max - min).is_finite() {
+        return 0.0..1.0;
+    }
+
+    let mut margin = (max - min) * 0.05;
+    if margin <= max.abs() * 1e-9 {
+        margin = if max == 0.0 { 1.0 } else { max.abs() * 0.05 };
+    }
+
+    return (min - margin)..(max + margin);
+}
+
+/// Render the `charts` row-major into `layout.x` columns and `layout.y` rows
+/// with the label `x_label` of the shared x-axis.
+pub fn line_charts(
+    size: Index2,
+    layout: Index2,
+    charts: &[Chart],
+    x_label: &str,
+) -> Result<Image, Box<dyn Error>> {
+    let mut data = vec![0; size.x * size.y * 3];
+
+    {
+        let root = BitMapBackend::with_buffer(&mut data, (size.x as u32, size.y as u32))
+            .into_drawing_area();
+        root.fill(&WHITE)?;
+
+        let areas = root.split_evenly((layout.y, layout.x));
+
+        for (chart, area) in charts.iter().zip(areas.iter()) {
+            let points = || chart.series.iter().flat_map(|s| s.points.iter());
+
+            let mut c = ChartBuilder::on(area)
+                .caption(&chart.title, ("sans-serif", 18))
+                .margin(10)
+                .x_label_area_size(35)
+                .y_label_area_size(70)
+                .build_cartesian_2d(
+                    value_range(points().map(|p| p.0)),
+                    value_range(points().map(|p| p.1)),
+                )?;
+
+            c.configure_mesh()
+                .x_desc(x_label)
+                .y_label_formatter(&|v| format_tick(*v as Scalar))
+                .draw()?;
+
+            for (k, series) in chart.series.iter().enumerate() {
+                let color = Palette99::pick(k).to_rgba();
+
+                c.draw_series(LineSeries::new(series.points.iter().cloned(), &color))?
+                    .label(&series.name)
+                    .legend(move |(x, y)| PathElement::new(vec![(x, y), (x + 20, y)], color));
+            }
+
+            if chart.series.len() > 1 {
+                c.configure_series_labels()
+                    .background_style(WHITE.mix(0.8))
+                    .border_style(BLACK)
+                    .draw()?;
+            }
+        }
+
+        root.present()?;
+    }
+
+    return Ok(Image { size, data });
+}
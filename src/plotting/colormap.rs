@@ -0,0 +1,268 @@
+use crate::types::*;
+use colorgrad;
+use plotters::prelude::*;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs::File;
+use std::io::BufReader;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+
+use super::plot::Image;
+
+/// Custom colormaps by name, each a list of CSS colors (e.g. `#ff8000`).
+pub type CustomColormaps = HashMap<String, Vec<String>>;
+
+/// The colormap of a field plot.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Colormap {
+    Turbo,
+    Viridis,
+    Magma,
+    Coolwarm,
+    Grayscale,
+    /// A linear gradient through the colors.
+    Custom(Vec<String>),
+}
+
+impl Colormap {
+    /// Parse a colormap name, custom colormaps are looked up in `custom`.
+    pub fn parse(name: &str, custom: &CustomColormaps) -> Result<Colormap, String> {
+        let colormap = match name {
+            "turbo" => Colormap::Turbo,
+            "viridis" => Colormap::Viridis,
+            "magma" => Colormap::Magma,
+            "coolwarm" => Colormap::Coolwarm,
+            "grayscale" => Colormap::Grayscale,
+            _ => match custom.get(name) {
+                Some(colors) => Colormap::Custom(colors.clone()),
+                None => return Err(format!("Unknown colormap '{}'.", name)),
+            },
+        };
+
+        colormap
+            .gradient()
+            .map_err(|e| format!("Colormap '{}': {}", name, e))?;
+
+        return Ok(colormap);
+    }
+
+    pub fn gradient(&self) -> Result<colorgrad::Gradient, Box<dyn Error>> {
+        let custom = |colors: &[&str]| colorgrad::CustomGradient::new().html_colors(colors).build();
+
+        return Ok(match self {
+            Colormap::Turbo => colorgrad::turbo(),
+            Colormap::Viridis => colorgrad::viridis(),
+            Colormap::Magma => colorgrad::magma(),
+            // The diverging map of K. Moreland (blue to red).
+            Colormap::Coolwarm => custom(&["#3b4cc0", "#8db0fe", "#dddddd", "#f49a7b", "#b40426"])?,
+            Colormap::Grayscale => custom(&["#000000", "#ffffff"])?,
+            Colormap::Custom(colors) => {
+                custom(&colors.iter().map(|c| c.as_str()).collect::<Vec<_>>())?
+            }
+        });
+    }
+}
+
+/// Load custom colormaps from a JSON file `{"name": ["#000000", "#ff0000", ...]}`.
+pub fn load_colormaps(file: &str) -> Result<CustomColormaps, Box<dyn Error>> {
+    return Ok(serde_json::from_reader(BufReader::new(File::open(file)?))?);
+}
+
+/// The value range which is mapped onto the colormap.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RangeMode {
+    /// The min. and max. value of each frame.
+    Auto,
+    /// A fixed range `min:max`.
+    Fixed(Scalar, Scalar),
+    /// The max. absolute value of each frame, symmetric around zero.
+    Symmetric,
+    /// The running min. and max. over all frames so far.
+    Global { symmetric: bool },
+    /// The exponential moving average of the frame ranges with the weight
+    /// `alpha` of the newest frame.
+    Smoothed { alpha: Scalar, symmetric: bool },
+}
+
+fn symmetric((min, max): (Scalar, Scalar)) -> (Scalar, Scalar) {
+    let m = min.abs().max(max.abs());
+    return (-m, m);
+}
+
+impl RangeMode {
+    /// The range `(min, max)` of a frame with the values in `[min, max]`
+    /// (without the history of `Global` and `Smoothed`, see [`RangeTracker`]).
+    pub fn range(&self, min: Scalar, max: Scalar) -> (Scalar, Scalar) {
+        return match *self {
+            RangeMode::Auto => (min, max),
+            RangeMode::Fixed(min, max) => (min, max),
+            RangeMode::Symmetric => symmetric((min, max)),
+            RangeMode::Global { symmetric: s } | RangeMode::Smoothed { symmetric: s, .. } => {
+                if s {
+                    symmetric((min, max))
+                } else {
+                    (min, max)
+                }
+            }
+        };
+    }
+}
+
+impl FromStr for RangeMode {
+    type Err = String;
+
+    /// Parse `auto`, `symmetric`, `global`, `global-symmetric`, `smoothed:<alpha>`,
+    /// `smoothed-symmetric:<alpha>` or `<min>:<max>`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parse = |v: &str| v.trim().parse::<Scalar>().map_err(|e| e.to_string());
+
+        return match s.trim().split_once(':') {
+            None => match s.trim() {
+                "auto" => Ok(RangeMode::Auto),
+                "symmetric" => Ok(RangeMode::Symmetric),
+                "global" => Ok(RangeMode::Global { symmetric: false }),
+                "global-symmetric" => Ok(RangeMode::Global { symmetric: true }),
+                _ => Err(format!("Unknown range '{}'.", s)),
+            },
+            Some((mode @ ("smoothed" | "smoothed-symmetric"), alpha)) => {
+                let alpha = parse(alpha)?;
+                if !(alpha > 0.0 && alpha <= 1.0) {
+                    return Err(format!("Smoothing factor '{}' is not in '(0, 1]'.", alpha));
+                }
+                Ok(RangeMode::Smoothed {
+                    alpha,
+                    symmetric: mode == "smoothed-symmetric",
+                })
+            }
+            Some((min, max)) => match (parse(min)?, parse(max)?) {
+                (min, max) if min < max => Ok(RangeMode::Fixed(min, max)),
+                _ => Err(format!("Range '{}' needs 'min < max'.", s)),
+            },
+        };
+    }
+}
+
+/// The range of a field plot across frames (for the modes `Global` and `Smoothed`).
+/// Clones share the state, such that frames rendered in parallel share the range.
+#[derive(Clone, Debug, Default)]
+pub struct RangeTracker {
+    range: Arc<Mutex<Option<(Scalar, Scalar)>>>,
+}
+
+impl RangeTracker {
+    /// Extend the global range by the values in `[min, max]`
+    /// (e.g. with all frames before rendering).
+    pub fn include(&self, min: Scalar, max: Scalar) {
+        let mut range = self.range.lock().unwrap();
+        *range = Some(match *range {
+            Some((r_min, r_max)) => (r_min.min(min), r_max.max(max)),
+            None => (min, max),
+        });
+    }
+
+    /// Add the frame with the values in `[min, max]` and return the range of the frame.
+    pub fn update(&self, mode: RangeMode, min: Scalar, max: Scalar) -> (Scalar, Scalar) {
+        let frame = mode.range(min, max);
+
+        return match mode {
+            RangeMode::Global { .. } => {
+                self.include(frame.0, frame.1);
+                self.range.lock().unwrap().unwrap()
+            }
+            RangeMode::Smoothed { alpha, .. } => {
+                let mut range = self.range.lock().unwrap();
+                let smoothed = match *range {
+                    Some((r_min, r_max)) => (
+                        r_min + alpha * (frame.0 - r_min),
+                        r_max + alpha * (frame.1 - r_max),
+                    ),
+                    None => frame,
+                };
+                *range = Some(smoothed);
+                smoothed
+            }
+            _ => frame,
+        };
+    }
+}
+
+/// The colormap of a field plot over the value range `[min, max]`.
+#[derive(Debug)]
+pub struct Colorbar {
+    pub gradient: colorgrad::Gradient,
+    pub min: Scalar,
+    pub max: Scalar,
+}
+
+impl Colorbar {
+    pub fn at(&self, value: Scalar) -> colorgrad::Color {
+        let t = (value - self.min) / (self.max - self.min).max(Scalar::EPSILON);
+        return self.gradient.at(to_float(t));
+    }
+}
+
+/// The width in pixels of the colorbar next to the plot.
+const COLORBAR_WIDTH: usize = 100;
+
+/// Append the colorbar with tick labels on the right of a plot `image`.
+pub fn draw_colorbar(
+    image: &Image,
+    colorbar: &Colorbar,
+    ticks: usize,
+    text: Option<&str>,
+) -> Result<Image, Box<dyn Error>> {
+    let size = idx!(image.size.x + COLORBAR_WIDTH, image.size.y);
+    let mut data = vec![0; size.x * size.y * 3];
+
+    for (row, src) in data
+        .chunks_exact_mut(size.x * 3)
+        .zip(image.data.chunks_exact(image.size.x * 3))
+    {
+        row[..src.len()].copy_from_slice(src);
+    }
+
+    // The bar spans the plot area (below the title).
+    let top = super::plot::title_height(text) as i32 + 10;
+    let bottom = size.y as i32 - 10;
+    let (left, right) = (image.size.x as i32 + 10, image.size.x as i32 + 30);
+
+    {
+        let root = BitMapBackend::with_buffer(&mut data, (size.x as u32, size.y as u32))
+            .into_drawing_area();
+
+        let height = (bottom - top).max(1);
+        for y in top..bottom {
+            let t = (bottom - 1 - y) as f64 / (height - 1).max(1) as f64;
+            let c = colorbar.gradient.at(t).to_rgba8();
+            root.draw(&Rectangle::new(
+                [(left, y), (right, y + 1)],
+                RGBColor(c[0], c[1], c[2]).filled(),
+            ))?;
+        }
+
+        let style = ("sans-serif", 14).with_color(WHITE).into_text_style(&root);
+
+        for k in 0..ticks.max(2) {
+            let t = k as Scalar / (ticks.max(2) - 1) as Scalar;
+            let y = bottom - 1 - (t * (height - 1) as Scalar).round() as i32;
+            let value = colorbar.min + t * (colorbar.max - colorbar.min);
+
+            root.draw(&PathElement::new(vec![(right, y), (right + 4, y)], WHITE))?;
+            root.draw(&Text::new(format_tick(value), (right + 7, y - 7), &style))?;
+        }
+
+        root.present()?;
+    }
+
+    return Ok(Image { size, data });
+}
+
+/// A short tick label (scientific notation for large and small values).
+pub(super) fn format_tick(value: Scalar) -> String {
+    let a = value.abs();
+    if a != 0.0 && !(1e-2..1e4).contains(&a) {
+        return format!("{:.2e}", value);
+    }
+    return format!("{:.3}", value);
+}
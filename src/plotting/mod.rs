@@ -1,4 +1,14 @@
+mod animation;
+mod chart;
+mod colormap;
 mod plot;
+mod stream;
+mod terminal;
+pub use animation::*;
+pub use chart::*;
+pub use colormap::*;
 pub use plot::*;
+pub use stream::*;
+pub use terminal::*;
 
 mod tests;
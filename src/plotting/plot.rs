@@ -1,37 +1,81 @@
 use crate::types::*;
 use colorgrad;
 use itertools::Itertools;
+use plotters::coord::Shift;
 use plotters::prelude::*;
 
+use rayon::prelude::*;
 use std::error::Error;
+use std::fs::File;
+use std::io::BufWriter;
 
-pub trait ColorFunction = Fn(Index2) -> colorgrad::Color;
+pub trait ColorFunction = Fn(Index2) -> colorgrad::Color + Sync;
 
-pub fn grid<F: ColorFunction>(
-    size: Index2,
-    dim: Index2,
-    get_color: F,
-    file: String,
-    text: Option<&str>,
-) -> Result<(), Box<dyn Error>> {
-    let ratio = dim.y as Scalar / dim.x as Scalar;
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Interpolation {
+    Nearest,
+    Bilinear,
+}
 
-    let border_top = if text.is_some() { 25.0 } else { 0.0 };
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Renderer {
+    /// Draws each cell as a `plotters` rectangle.
+    Plotters,
+    /// Writes the cell colors directly into the pixel buffer.
+    Direct(Interpolation),
+}
 
-    let size_px = dim!(size.x, (size.x as Scalar * ratio + border_top) as usize);
+/// An RGB image (8 bits per channel, row-major from the top-left pixel).
+#[derive(Clone, Debug, PartialEq)]
+pub struct Image {
+    pub size: Index2,
+    pub data: Vec<u8>,
+}
 
-    let root = BitMapBackend::new(&file, (size_px.x as u32, size_px.y as u32)).into_drawing_area();
-    root.fill(&BLACK)?;
+/// The height in pixels of the title above the plot.
+pub(super) fn title_height(text: Option<&str>) -> usize {
+    return if text.is_some() { 25 } else { 0 };
+}
 
-    let text_style = ("sans-serif", 20)
-        .with_color(WHITE)
-        .into_text_style(&root);
+/// The size in pixels of a grid plot with width `size.x`.
+fn grid_size(size: Index2, dim: Index2, text: Option<&str>) -> Index2 {
+    let ratio = dim.y as Scalar / dim.x as Scalar;
+    let border_top = title_height(text) as Scalar;
+
+    return dim!(size.x, (size.x as Scalar * ratio + border_top) as usize);
+}
+
+fn draw_title<DB: DrawingBackend>(
+    root: &DrawingArea<DB, Shift>,
+    text: Option<&str>,
+) -> Result<(), Box<dyn Error>>
+where
+    DB::ErrorType: 'static,
+{
+    let text_style = ("sans-serif", 20).with_color(WHITE).into_text_style(root);
 
     if let Some(text) = text {
         root.titled(&text, &text_style)?;
     }
 
-    let mut chart = ChartBuilder::on(&root)
+    return Ok(());
+}
+
+fn draw_grid<DB: DrawingBackend, F: ColorFunction>(
+    root: &DrawingArea<DB, Shift>,
+    dim: Index2,
+    get_color: F,
+    text: Option<&str>,
+) -> Result<(), Box<dyn Error>>
+where
+    DB::ErrorType: 'static,
+{
+    let border_top = title_height(text) as f64;
+
+    root.fill(&BLACK)?;
+    draw_title(root, text)?;
+
+    let mut chart = ChartBuilder::on(root)
         .margin_top(border_top)
         .x_label_area_size(0)
         .y_label_area_size(0)
@@ -67,11 +111,355 @@ pub fn grid<F: ColorFunction>(
         ))?;
     }
 
-    // To avoid the IO failure being ignored silently, we manually call the present function
-    root.present().expect(
-        "Unable to write result to file, please \
-         make sure 'plotters-doc-data' dir exists under current dir",
+    return Ok(());
+}
+
+/// Save the image as `.png` file.
+pub fn save_png(image: &Image, file: &str) -> Result<(), Box<dyn Error>> {
+    let w = BufWriter::new(File::create(file)?);
+
+    let mut encoder = png::Encoder::new(w, image.size.x as u32, image.size.y as u32);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(&image.data)?;
+    writer.finish()?;
+
+    return Ok(());
+}
+
+pub fn grid<F: ColorFunction>(
+    size: Index2,
+    dim: Index2,
+    get_color: F,
+    file: String,
+    text: Option<&str>,
+) -> Result<(), Box<dyn Error>> {
+    let image = grid_image(size, dim, get_color, text)?;
+    return save_png(&image, &file);
+}
+
+/// Render the grid plot into an in-memory image with `renderer`.
+pub fn render_grid<F: ColorFunction>(
+    renderer: Renderer,
+    size: Index2,
+    dim: Index2,
+    get_color: F,
+    text: Option<&str>,
+) -> Result<Image, Box<dyn Error>> {
+    return match renderer {
+        Renderer::Plotters => grid_image(size, dim, get_color, text),
+        Renderer::Direct(interpolation) => {
+            grid_image_direct(size, dim, get_color, text, interpolation)
+        }
+    };
+}
+
+/// Render the grid plot into an in-memory image (with `plotters`).
+pub fn grid_image<F: ColorFunction>(
+    size: Index2,
+    dim: Index2,
+    get_color: F,
+    text: Option<&str>,
+) -> Result<Image, Box<dyn Error>> {
+    let size_px = grid_size(size, dim, text);
+    let mut data = vec![0; size_px.x * size_px.y * 3];
+
+    {
+        let root = BitMapBackend::with_buffer(&mut data, (size_px.x as u32, size_px.y as u32))
+            .into_drawing_area();
+        draw_grid(&root, dim, get_color, text)?;
+        root.present()?;
+    }
+
+    return Ok(Image {
+        size: size_px,
+        data,
+    });
+}
+
+/// Render the grid plot by writing the cell colors (blended onto black)
+/// directly into the pixel buffer, parallel over the rows.
+/// The cells are upscaled to the plot size with `interpolation`.
+pub fn grid_image_direct<F: ColorFunction>(
+    size: Index2,
+    dim: Index2,
+    get_color: F,
+    text: Option<&str>,
+    interpolation: Interpolation,
+) -> Result<Image, Box<dyn Error>> {
+    let size_px = grid_size(size, dim, text);
+    let border_top = title_height(text);
+    let mut data = vec![0; size_px.x * size_px.y * 3];
+
+    if dim.x == 0 || dim.y == 0 {
+        return Ok(Image {
+            size: size_px,
+            data,
+        });
+    }
+
+    // All cell colors, row-major with `y` pointing up.
+    let colors = (0..dim.x * dim.y)
+        .into_par_iter()
+        .map(|k| {
+            let c = get_color(idx!(k % dim.x, k / dim.x));
+            return [c.r, c.g, c.b].map(|v| ((v * 256.0).min(255.0) * c.a) as f32);
+        })
+        .collect::<Vec<_>>();
+
+    let width = size_px.x;
+    let height = size_px.y - border_top;
+
+    // The continuous cell coordinate of a pixel center.
+    let to_cell = |p: usize, n_px: usize, n: usize| (p as f64 + 0.5) / n_px as f64 * n as f64;
+
+    // The two cells and the weight of the second one (the same for `nearest`).
+    let weights = |c: f64, n: usize| -> (usize, usize, f32) {
+        return match interpolation {
+            Interpolation::Nearest => {
+                let i = (c.max(0.0) as usize).min(n - 1);
+                (i, i, 0.0)
+            }
+            Interpolation::Bilinear => {
+                // Interpolate between the cell centers.
+                let f = (c - 0.5).clamp(0.0, (n - 1) as f64);
+                let i = f as usize;
+                (i, (i + 1).min(n - 1), (f - i as f64) as f32)
+            }
+        };
+    };
+
+    let columns = (0..width)
+        .map(|px| weights(to_cell(px, width, dim.x), dim.x))
+        .collect::<Vec<_>>();
+
+    // Resample each cell row to the plot width once, in fixed point (`value * 256`).
+    let lines = (0..dim.y)
+        .into_par_iter()
+        .map(|j| {
+            let cells = &colors[j * dim.x..][..dim.x];
+            return columns
+                .iter()
+                .flat_map(|(i0, i1, tx)| {
+                    let (c0, c1) = (&cells[*i0], &cells[*i1]);
+                    return [0, 1, 2].map(|k| ((c0[k] + (c1[k] - c0[k]) * tx) * 256.0) as u16);
+                })
+                .collect::<Vec<_>>();
+        })
+        .collect::<Vec<_>>();
+
+    data[border_top * width * 3..]
+        .par_chunks_mut(width * 3)
+        .enumerate()
+        .for_each(|(py, row)| {
+            let (j0, j1, ty) = weights(dim.y as f64 - to_cell(py, height, dim.y), dim.y);
+            let ty = (ty * 256.0).round() as u32;
+
+            for ((p, a), b) in row.iter_mut().zip(lines[j0].iter()).zip(lines[j1].iter()) {
+                *p = ((*a as u32 * (256 - ty) + *b as u32 * ty) >> 16) as u8;
+            }
+        });
+
+    if text.is_some() {
+        let root = BitMapBackend::with_buffer(&mut data, (size_px.x as u32, size_px.y as u32))
+            .into_drawing_area();
+        draw_title(&root, text)?;
+        root.present()?;
+    }
+
+    return Ok(Image {
+        size: size_px,
+        data,
+    });
+}
+
+/// Compose the `panels` row-major into a grid of `layout.x` columns and `layout.y`
+/// rows below the shared title `text`. Each panel is placed at the top-left of
+/// its slot, which has the size of the largest panel.
+pub fn composite(
+    panels: &[&Image],
+    layout: Index2,
+    text: Option<&str>,
+) -> Result<Image, Box<dyn Error>> {
+    if panels.len() > layout.x * layout.y {
+        return Err(format!(
+            "{} panels do not fit into the layout '{}x{}'.",
+            panels.len(),
+            layout.x,
+            layout.y
+        )
+        .into());
+    }
+
+    let border_top = title_height(text);
+    let slot = panels.iter().fold(idx!(0, 0), |s, p| s.sup(&p.size));
+    let size_px = idx!(slot.x * layout.x, slot.y * layout.y + border_top);
+    let mut data = vec![0; size_px.x * size_px.y * 3];
+
+    for (k, panel) in panels.iter().enumerate() {
+        let (left, top) = (
+            (k % layout.x) * slot.x,
+            (k / layout.x) * slot.y + border_top,
+        );
+
+        for (y, src) in panel.data.chunks_exact(panel.size.x * 3).enumerate() {
+            let start = ((top + y) * size_px.x + left) * 3;
+            data[start..start + src.len()].copy_from_slice(src);
+        }
+    }
+
+    if text.is_some() {
+        let root = BitMapBackend::with_buffer(&mut data, (size_px.x as u32, size_px.y as u32))
+            .into_drawing_area();
+        draw_title(&root, text)?;
+        root.present()?;
+    }
+
+    return Ok(Image {
+        size: size_px,
+        data,
+    });
+}
+
+/// The transformation from cell coordinates (`y` pointing up) to the pixels
+/// of a grid plot `image` of the grid with dimension `dim`.
+fn pixel_transform(
+    image: &Image,
+    dim: Index2,
+    text: Option<&str>,
+) -> impl Fn(&Vector2) -> (i32, i32) {
+    let border_top = title_height(text) as Scalar;
+    let scale = vec2!(
+        image.size.x as Scalar / dim.x as Scalar,
+        (image.size.y as Scalar - border_top) / dim.y as Scalar
     );
 
+    return move |p: &Vector2| {
+        return (
+            (p.x * scale.x).round() as i32,
+            (border_top + (dim.y as Scalar - p.y) * scale.y).round() as i32,
+        );
+    };
+}
+
+fn to_rgba(color: &colorgrad::Color) -> RGBAColor {
+    return RGBAColor(
+        (color.r * 256.0).min(255.0) as u8,
+        (color.g * 256.0).min(255.0) as u8,
+        (color.b * 256.0).min(255.0) as u8,
+        color.a,
+    );
+}
+
+/// Draw polylines given in cell coordinates (`y` pointing up)
+/// on top of a grid plot `image` of the grid with dimension `dim`.
+pub fn draw_paths(
+    image: &mut Image,
+    dim: Index2,
+    paths: &[Vec<Vector2>],
+    color: colorgrad::Color,
+    text: Option<&str>,
+) -> Result<(), Box<dyn Error>> {
+    let to_px = pixel_transform(image, dim, text);
+    let style = ShapeStyle::from(to_rgba(&color)).stroke_width(1);
+
+    let size = (image.size.x as u32, image.size.y as u32);
+    let root = BitMapBackend::with_buffer(&mut image.data, size).into_drawing_area();
+
+    for path in paths.iter() {
+        root.draw(&PathElement::new(
+            path.iter().map(&to_px).collect::<Vec<_>>(),
+            style,
+        ))?;
+    }
+
+    root.present()?;
+
+    return Ok(());
+}
+
+/// Draw dots with radius `radius` (in pixels) at the points given in cell
+/// coordinates (see [`draw_paths`]).
+pub fn draw_points(
+    image: &mut Image,
+    dim: Index2,
+    points: &[Vector2],
+    radius: u32,
+    color: colorgrad::Color,
+    text: Option<&str>,
+) -> Result<(), Box<dyn Error>> {
+    let to_px = pixel_transform(image, dim, text);
+    let style = ShapeStyle::from(to_rgba(&color)).filled();
+
+    let size = (image.size.x as u32, image.size.y as u32);
+    let root = BitMapBackend::with_buffer(&mut image.data, size).into_drawing_area();
+
+    for p in points.iter() {
+        root.draw(&Circle::new(to_px(p), radius, style))?;
+    }
+
+    root.present()?;
+
+    return Ok(());
+}
+
+/// Draw the trails (oldest point first) given in cell coordinates, fading out
+/// towards the oldest point (see [`draw_paths`]).
+pub fn draw_trails(
+    image: &mut Image,
+    dim: Index2,
+    trails: &[Vec<Vector2>],
+    color: colorgrad::Color,
+    text: Option<&str>,
+) -> Result<(), Box<dyn Error>> {
+    let to_px = pixel_transform(image, dim, text);
+
+    let size = (image.size.x as u32, image.size.y as u32);
+    let root = BitMapBackend::with_buffer(&mut image.data, size).into_drawing_area();
+
+    for trail in trails.iter() {
+        let n = trail.len();
+        for (k, (a, b)) in trail.iter().tuple_windows().enumerate() {
+            let mut c = color.clone();
+            c.a *= (k + 1) as f64 / n as f64;
+            root.draw(&PathElement::new(
+                vec![to_px(a), to_px(b)],
+                ShapeStyle::from(to_rgba(&c)).stroke_width(1),
+            ))?;
+        }
+    }
+
+    root.present()?;
+
     return Ok(());
 }
+
+/// Draw arrows `(start, end)` given in cell coordinates (see [`draw_paths`]).
+pub fn draw_arrows(
+    image: &mut Image,
+    dim: Index2,
+    arrows: &[(Vector2, Vector2)],
+    color: colorgrad::Color,
+    text: Option<&str>,
+) -> Result<(), Box<dyn Error>> {
+    // The head has a third of the length and opens with 25 degrees.
+    let (sin, cos) = (25.0 as Scalar).to_radians().sin_cos();
+    let rotate =
+        |v: Vector2, s: Scalar| vec2!(v.x * cos - s * v.y * sin, s * v.x * sin + v.y * cos);
+
+    let paths = arrows
+        .iter()
+        .filter(|(start, end)| start != end)
+        .flat_map(|(start, end)| {
+            let head = (end - start) * (-1.0 / 3.0);
+            return [
+                vec![*start, *end],
+                vec![end + rotate(head, 1.0), *end, end + rotate(head, -1.0)],
+            ];
+        })
+        .collect::<Vec<_>>();
+
+    return draw_paths(image, dim, &paths, color, text);
+}
@@ -0,0 +1,121 @@
+use crate::plotting::Image;
+use crate::types::*;
+
+use std::fs::File;
+use std::io::{BufWriter, Write};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum StreamFormat {
+    /// Raw RGBA frames without any header.
+    Rgba,
+    /// YUV4MPEG2 container with 4:4:4 frames.
+    Y4m,
+}
+
+/// Writes frames continuously to a file, a named pipe or `stdout` (`-`)
+/// to be consumed by an encoder, e.g. `rsfluid ... | ffmpeg -i - video.mp4`.
+pub struct FrameStream {
+    format: StreamFormat,
+    frame_rate: f64,
+    size: Option<Index2>,
+    out: Box<dyn Write + Send>,
+    buffer: Vec<u8>,
+}
+
+impl FrameStream {
+    /// Open the stream on `output` (`-` is `stdout`).
+    pub fn new(output: &str, format: StreamFormat, frame_rate: f64) -> GenericResult<Self> {
+        if frame_rate.is_nan() || frame_rate <= 0.0 {
+            bail!("Frame rate '{}' must be positive.", frame_rate);
+        }
+
+        let out: Box<dyn Write + Send> = if output == "-" {
+            Box::new(std::io::stdout())
+        } else {
+            Box::new(File::create(output)?)
+        };
+
+        return Ok(Self::from_writer(out, format, frame_rate));
+    }
+
+    pub fn from_writer(out: Box<dyn Write + Send>, format: StreamFormat, frame_rate: f64) -> Self {
+        return FrameStream {
+            format,
+            frame_rate,
+            size: None,
+            out: Box::new(BufWriter::new(out)),
+            buffer: vec![],
+        };
+    }
+
+    /// The frame size (after the first frame).
+    pub fn size(&self) -> Option<Index2> {
+        return self.size;
+    }
+
+    fn write_header(&mut self, size: Index2) -> GenericResult<()> {
+        if self.format == StreamFormat::Y4m {
+            writeln!(
+                self.out,
+                "YUV4MPEG2 W{} H{} F{}:1000 Ip A1:1 C444",
+                size.x,
+                size.y,
+                (self.frame_rate * 1000.0).round() as u64
+            )?;
+        }
+
+        return Ok(());
+    }
+
+    /// Write a frame. All frames need to have the same size.
+    pub fn write_frame(&mut self, image: &Image) -> GenericResult<()> {
+        match self.size {
+            None => {
+                self.write_header(image.size)?;
+                self.size = Some(image.size);
+            }
+            Some(size) if size != image.size => bail!(
+                "Frame size {:?} differs from the stream size {:?}.",
+                image.size,
+                size
+            ),
+            _ => {}
+        }
+
+        let pixels = image.data.chunks_exact(3);
+        let n = pixels.len();
+        self.buffer.clear();
+
+        match self.format {
+            StreamFormat::Rgba => {
+                for p in pixels {
+                    self.buffer.extend_from_slice(&[p[0], p[1], p[2], 255]);
+                }
+            }
+            StreamFormat::Y4m => {
+                self.out.write_all(b"FRAME\n")?;
+
+                // Planar Y, Cb, Cr (BT.601, limited range).
+                self.buffer.resize(3 * n, 0);
+                let (y, cbcr) = self.buffer.split_at_mut(n);
+                let (cb, cr) = cbcr.split_at_mut(n);
+
+                for (i, p) in pixels.enumerate() {
+                    let (r, g, b) = (p[0] as f32, p[1] as f32, p[2] as f32);
+                    y[i] = (16.0 + 0.257 * r + 0.504 * g + 0.098 * b).round() as u8;
+                    cb[i] = (128.0 - 0.148 * r - 0.291 * g + 0.439 * b).round() as u8;
+                    cr[i] = (128.0 + 0.439 * r - 0.368 * g - 0.071 * b).round() as u8;
+                }
+            }
+        }
+
+        self.out.write_all(&self.buffer)?;
+
+        return Ok(());
+    }
+
+    pub fn flush(&mut self) -> GenericResult<()> {
+        self.out.flush()?;
+        return Ok(());
+    }
+}
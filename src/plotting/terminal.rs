@@ -0,0 +1,61 @@
+use crate::types::*;
+use std::fmt::Write;
+
+use super::plot::ColorFunction;
+
+/// The terminal size (columns, rows) from the environment variables
+/// `COLUMNS` and `LINES` (default `80x24`).
+pub fn terminal_size() -> Index2 {
+    let var = |name: &str, default: usize| {
+        return std::env::var(name)
+            .ok()
+            .and_then(|v| v.trim().parse::<usize>().ok())
+            .filter(|v| *v > 0)
+            .unwrap_or(default);
+    };
+
+    return idx!(var("COLUMNS", 80), var("LINES", 24));
+}
+
+/// Render the grid plot into at most `size` characters (columns, rows) with
+/// Unicode half blocks (two pixels per character) and 24-bit ANSI colors.
+/// The cells are downsampled (nearest) with the aspect ratio of the grid and
+/// their colors blended onto black. Each row ends with a newline.
+pub fn grid_terminal<F: ColorFunction>(size: Index2, dim: Index2, get_color: F) -> String {
+    let mut out = String::new();
+
+    if dim.x == 0 || dim.y == 0 || size.x == 0 || size.y == 0 {
+        return out;
+    }
+
+    // The half blocks are roughly square.
+    let scale = (size.x as Scalar / dim.x as Scalar).min(2.0 * size.y as Scalar / dim.y as Scalar);
+    let width = ((dim.x as Scalar * scale) as usize).max(1);
+    let height = ((dim.y as Scalar * scale) as usize).max(1);
+
+    let rgb = |px: usize, py: usize| {
+        let i = ((px as Scalar + 0.5) / scale) as usize;
+        let j = ((py as Scalar + 0.5) / scale) as usize;
+        let c = get_color(idx!(i.min(dim.x - 1), dim.y - 1 - j.min(dim.y - 1)));
+        return [c.r, c.g, c.b].map(|v| ((v * 256.0).min(255.0) * c.a) as u8);
+    };
+
+    for row in 0..(height + 1) / 2 {
+        for px in 0..width {
+            let top = rgb(px, 2 * row);
+            let _ = write!(out, "\x1b[38;2;{};{};{}m", top[0], top[1], top[2]);
+
+            if 2 * row + 1 < height {
+                let bottom = rgb(px, 2 * row + 1);
+                let _ = write!(out, "\x1b[48;2;{};{};{}m", bottom[0], bottom[1], bottom[2]);
+            } else {
+                out.push_str("\x1b[49m");
+            }
+
+            out.push('▀');
+        }
+        out.push_str("\x1b[0m\n");
+    }
+
+    return out;
+}
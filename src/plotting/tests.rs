@@ -1,9 +1,14 @@
 #[cfg(test)]
 mod tests {
 
-    use crate::plotting::plot::grid;
+    use crate::plotting::animation::*;
+    use crate::plotting::colormap::*;
+    use crate::plotting::plot::*;
+    use crate::plotting::stream::*;
+    use crate::plotting::terminal::*;
     use crate::types::*;
     use colorgrad;
+    use itertools::Itertools;
 
     #[test]
     fn test_grid() -> Result<(), Box<dyn std::error::Error>> {
@@ -14,10 +19,12 @@ mod tests {
                 return cg.at(0.0);
             }
 
-            return cg.at(((index.x as Scalar) / 15.0).sin() * ((index.y as Scalar) / 10.0).cos());
+            let val = ((index.x as Scalar) / 15.0).sin() * ((index.y as Scalar) / 10.0).cos();
+            return cg.at(to_float(val));
         };
 
-        let file = "test.png";
+        let file = std::env::temp_dir().join(format!("rsfluid-test-{}.png", std::process::id()));
+        let file = file.to_str().unwrap();
         grid(
             dim!(500, 500),
             dim!(300, 300),
@@ -26,6 +33,284 @@ mod tests {
             None,
         )?;
 
+        assert!(std::fs::metadata(file)?.len() > 0);
+        std::fs::remove_file(file)?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_animation() -> Result<(), Box<dyn std::error::Error>> {
+        let cg: colorgrad::Gradient = colorgrad::turbo();
+        let dir = std::env::temp_dir().join(format!("rsfluid-test-anim-{}", std::process::id()));
+        std::fs::create_dir_all(&dir)?;
+
+        let images = (0..3)
+            .map(|k| {
+                return grid_image(
+                    dim!(40, 40),
+                    dim!(10, 5),
+                    |index: Index2| cg.at((index.x + k) as f64 / 12.0),
+                    None,
+                );
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        assert!(images[0].size == dim!(40, 20) && images[0].data.len() == 40 * 20 * 3);
+
+        for format in [AnimationFormat::Gif, AnimationFormat::Apng] {
+            let file = dir.join(format!("anim.{}", format.extension()));
+            let params = AnimationParamsBuilder::default()
+                .format(format)
+                .frame_rate(10.0)
+                .frames(images.len() as u32)
+                .build()?;
+
+            let mut writer = AnimationWriter::new(file.to_str().unwrap().to_string(), params);
+            for image in images.iter() {
+                writer.add_frame(image)?;
+            }
+            assert!(writer
+                .add_frame(&grid_image(dim!(20, 20), dim!(1, 1), |_| cg.at(0.0), None)?)
+                .is_err());
+            writer.finish()?;
+
+            let frames = match format {
+                AnimationFormat::Gif => {
+                    let mut options = gif::DecodeOptions::new();
+                    options.set_color_output(gif::ColorOutput::RGBA);
+                    let mut decoder = options.read_info(std::fs::File::open(&file)?)?;
+                    assert!(decoder.width() == 40 && decoder.height() == 20);
+
+                    let mut n = 0;
+                    while let Some(frame) = decoder.read_next_frame()? {
+                        assert!(frame.delay == 10);
+                        n += 1;
+                    }
+                    n
+                }
+                AnimationFormat::Apng => {
+                    let mut reader = png::Decoder::new(std::fs::File::open(&file)?).read_info()?;
+                    let control = *reader.info().animation_control().unwrap();
+                    assert!(control.num_plays == 0);
+
+                    let mut buffer = vec![0; reader.output_buffer_size()];
+                    let mut n = 0;
+                    while n < control.num_frames {
+                        reader.next_frame(&mut buffer)?;
+                        assert!(buffer == images[n as usize].data);
+                        n += 1;
+                    }
+                    n
+                }
+            };
+            assert!(frames == images.len() as u32);
+        }
+
+        std::fs::remove_dir_all(&dir)?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_stream() -> Result<(), Box<dyn std::error::Error>> {
+        let dir = std::env::temp_dir().join(format!("rsfluid-test-stream-{}", std::process::id()));
+        std::fs::create_dir_all(&dir)?;
+
+        let image = Image {
+            size: dim!(2, 1),
+            data: vec![255, 255, 255, 0, 0, 0],
+        };
+
+        for format in [StreamFormat::Rgba, StreamFormat::Y4m] {
+            let file = dir.join(format!("{:?}", format));
+
+            let mut stream = FrameStream::new(file.to_str().unwrap(), format, 30.0)?;
+            stream.write_frame(&image)?;
+            stream.write_frame(&image)?;
+            assert!(stream
+                .write_frame(&Image {
+                    size: dim!(1, 1),
+                    data: vec![0, 0, 0]
+                })
+                .is_err());
+            stream.flush()?;
+
+            let data = std::fs::read(&file)?;
+            let frame: &[u8] = match format {
+                StreamFormat::Rgba => &[255, 255, 255, 255, 0, 0, 0, 255],
+                StreamFormat::Y4m => b"FRAME\n\xeb\x10\x80\x80\x80\x80",
+            };
+            let header: &[u8] = match format {
+                StreamFormat::Rgba => b"",
+                StreamFormat::Y4m => b"YUV4MPEG2 W2 H1 F30000:1000 Ip A1:1 C444\n",
+            };
+
+            assert!(data == [header, frame, frame].concat());
+        }
+
+        std::fs::remove_dir_all(&dir)?;
+
         Ok(())
     }
+
+    #[test]
+    fn test_grid_image_direct() -> Result<(), Box<dyn std::error::Error>> {
+        let cg: colorgrad::Gradient = colorgrad::turbo();
+
+        let get_color = |index: Index2| {
+            let mut c = cg.at((index.x * 5 + index.y) as f64 / 50.0);
+            c.a = if index.x == 3 { 0.5 } else { 1.0 };
+            return c;
+        };
+
+        for text in [None, Some("frame: 1")] {
+            let reference = grid_image(dim!(40, 40), dim!(10, 5), get_color, text)?;
+            let image = grid_image_direct(
+                dim!(40, 40),
+                dim!(10, 5),
+                get_color,
+                text,
+                Interpolation::Nearest,
+            )?;
+
+            assert!(image.size == reference.size);
+
+            // `plotters` shifts the cell borders by a pixel, so only compare
+            // the inner pixels of each `4x4` pixel cell.
+            let top = image.size.y - 20;
+            let max_diff = (0..40)
+                .cartesian_product(0..20)
+                .filter(|(x, y)| [1, 2].contains(&(x % 4)) && [1, 2].contains(&(y % 4)))
+                .flat_map(|(x, y)| {
+                    let k = (x + (y + top) * 40) * 3;
+                    return (k..k + 3)
+                        .map(|k| (image.data[k] as i32 - reference.data[k] as i32).abs());
+                })
+                .max()
+                .unwrap();
+            assert!(max_diff <= 1, "Max. difference {}", max_diff);
+        }
+
+        // A constant field stays constant with bilinear interpolation.
+        let image = grid_image_direct(
+            dim!(37, 37),
+            dim!(3, 2),
+            |_| colorgrad::Color::new(0.5, 0.25, 1.0, 1.0),
+            None,
+            Interpolation::Bilinear,
+        )?;
+        assert!(image.data.chunks_exact(3).all(|p| p == [128, 64, 255]));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_colorbar() -> Result<(), Box<dyn std::error::Error>> {
+        assert!("auto".parse::<RangeMode>()? == RangeMode::Auto);
+        assert!(" -1.5:2 ".parse::<RangeMode>()? == RangeMode::Fixed(-1.5, 2.0));
+        assert!("2:1".parse::<RangeMode>().is_err() && "1".parse::<RangeMode>().is_err());
+        assert!(RangeMode::Symmetric.range(-1.0, 3.0) == (-3.0, 3.0));
+
+        let mut custom = CustomColormaps::new();
+        custom.insert("red".into(), vec!["#000000".into(), "#ff0000".into()]);
+        custom.insert("broken".into(), vec!["#nocolor".into()]);
+
+        assert!(Colormap::parse("viridis", &custom)? == Colormap::Viridis);
+        assert!(Colormap::parse("unknown", &custom).is_err());
+        assert!(Colormap::parse("broken", &custom).is_err());
+
+        let colorbar = Colorbar {
+            gradient: Colormap::parse("red", &custom)?.gradient()?,
+            min: -1.0,
+            max: 1.0,
+        };
+        assert!(colorbar.at(1.0).to_rgba8() == [255, 0, 0, 255]);
+        assert!(colorbar.at(-2.0).to_rgba8() == [0, 0, 0, 255]);
+
+        let get_color = |_: Index2| colorgrad::Color::new(0.0, 0.0, 1.0, 1.0);
+        let image = grid_image_direct(
+            dim!(200, 100),
+            dim!(10, 5),
+            get_color,
+            None,
+            Interpolation::Nearest,
+        )?;
+        let with_bar = draw_colorbar(&image, &colorbar, 5, None)?;
+
+        assert!(with_bar.size.x > image.size.x && with_bar.size.y == image.size.y);
+
+        // The plot is unchanged and the top of the bar is the max. color.
+        let pixel = |x: usize, y: usize| &with_bar.data[(x + y * with_bar.size.x) * 3..][..3];
+        assert!(pixel(image.size.x - 1, 50) == &image.data[(199 + 50 * 200) * 3..][..3]);
+        assert!(pixel(image.size.x + 20, 10) == [255, 0, 0]);
+
+        return Ok(());
+    }
+
+    #[test]
+    fn test_range_tracker() -> Result<(), Box<dyn std::error::Error>> {
+        let global = "global".parse::<RangeMode>()?;
+        assert!(global == RangeMode::Global { symmetric: false });
+        assert!(
+            "smoothed-symmetric:0.5".parse::<RangeMode>()?
+                == RangeMode::Smoothed {
+                    alpha: 0.5,
+                    symmetric: true
+                }
+        );
+        assert!("smoothed:0".parse::<RangeMode>().is_err());
+
+        let tracker = RangeTracker::default();
+        assert!(tracker.update(global, 0.0, 1.0) == (0.0, 1.0));
+        assert!(tracker.update(global, -1.0, 0.5) == (-1.0, 1.0));
+        assert!(tracker.clone().update(global, 0.0, 2.0) == (-1.0, 2.0));
+        assert!(tracker.update(global, 0.0, 0.0) == (-1.0, 2.0));
+
+        // Pre-computed global ranges are used from the first frame on.
+        let tracker = RangeTracker::default();
+        tracker.include(-4.0, 4.0);
+        assert!(tracker.update(global, 0.0, 1.0) == (-4.0, 4.0));
+
+        let smoothed = RangeMode::Smoothed {
+            alpha: 0.5,
+            symmetric: false,
+        };
+        let tracker = RangeTracker::default();
+        assert!(tracker.update(smoothed, 0.0, 2.0) == (0.0, 2.0));
+        assert!(tracker.update(smoothed, 1.0, 4.0) == (0.5, 3.0));
+
+        // Frame ranges without history are not tracked.
+        assert!(tracker.update(RangeMode::Auto, 1.0, 4.0) == (1.0, 4.0));
+        assert!(tracker.update(RangeMode::Fixed(0.0, 1.0), 1.0, 4.0) == (0.0, 1.0));
+
+        return Ok(());
+    }
+
+    #[test]
+    fn test_grid_terminal() {
+        // The top row red, the bottom row half-transparent blue.
+        let get_color = |index: Index2| {
+            if index.y == 1 {
+                return colorgrad::Color::new(1.0, 0.0, 0.0, 1.0);
+            }
+            return colorgrad::Color::new(0.0, 0.0, 1.0, 0.5);
+        };
+
+        let frame = grid_terminal(idx!(8, 2), idx!(4, 2), get_color);
+        let lines = frame.lines().collect_vec();
+
+        assert!(lines.len() == 2);
+        assert!(frame.matches('▀').count() == 2 * 8);
+        assert!(lines[0].starts_with("\x1b[38;2;255;0;0m\x1b[48;2;255;0;0m▀"));
+        assert!(lines[1].starts_with("\x1b[38;2;0;0;127m\x1b[48;2;0;0;127m▀"));
+        assert!(lines.iter().all(|l| l.ends_with("\x1b[0m")));
+
+        // The aspect ratio is kept, the last character row has only a top pixel.
+        let frame = grid_terminal(idx!(80, 1), idx!(4, 2), get_color);
+        assert!(frame.matches('▀').count() == 4);
+
+        let frame = grid_terminal(idx!(1, 5), idx!(1, 3), get_color);
+        assert!(frame.matches('▀').count() == 2);
+        assert!(frame.lines().last().unwrap().contains("\x1b[49m"));
+    }
 }
@@ -1,92 +1,112 @@
+use crate::scene::grid_fields::GridFields;
 use crate::types::*;
-use crate::log::Logger;
-use crate::scene::timestepper::Integrate;
-use std::any::Any;
+use serde::{Deserialize, Serialize};
 
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Copy, Debug, PartialEq, Hash, Serialize, Deserialize)]
 pub enum CellTypes {
     Solid,
     Fluid,
 }
 
-#[derive(Clone, Debug)]
-pub struct Cell {
+/// Read-only view on a single cell of the grid fields.
+/// All values are read from the back buffers.
+#[derive(Clone, Copy)]
+pub struct CellRef<'a, T: Float = Scalar> {
+    fields: &'a GridFields<T>,
+
+    /// The linear index into the fields.
+    i: usize,
+}
+
+impl<'a, T: Float> CellRef<'a, T> {
+    pub fn new(fields: &'a GridFields<T>, i: usize) -> Self {
+        return CellRef { fields, i };
+    }
+
     /// The index of the cell.
-    index: Index2,
+    pub fn index(&self) -> Index2 {
+        return idx!(self.i % self.fields.dim.x, self.i / self.fields.dim.x);
+    }
 
     /// The mode of the Cell, fluid or solid.
-    pub mode: CellTypes,
+    #[inline(always)]
+    pub fn mode(&self) -> CellTypes {
+        return self.fields.mode[self.i];
+    }
 
     /// Velocity x,y:
     /// - v_x is at the location (h/2, 0),
     /// - v_y is at the location (0, h/2),
-    pub velocity: FrontBackBuffer<Vector2>,
+    #[inline(always)]
+    pub fn velocity(&self) -> Vector2T<T> {
+        return vec2!(
+            self.fields.velocity[0].back[self.i],
+            self.fields.velocity[1].back[self.i]
+        );
+    }
 
     /// The pressure value.
-    pub pressure: Scalar,
+    #[inline(always)]
+    pub fn pressure(&self) -> T {
+        return self.fields.pressure[self.i];
+    }
 
     /// The advected smoke value in `[0,1]`.
-    pub smoke: FrontBackBuffer<Scalar>,
+    #[inline(always)]
+    pub fn smoke(&self) -> T {
+        return self.fields.smoke.back[self.i];
+    }
+
+    /// The divergence in the cell.
+    #[inline(always)]
+    pub fn div(&self) -> T {
+        return self.fields.div[self.i];
+    }
+}
+
+/// Mutable view on a single cell of the grid fields.
+/// All references point into the back buffers.
+/// This is also the item the stencil iterators produce on the grid fields.
+pub struct CellMut<'a, T: Float = Scalar> {
+    /// The index of the cell.
+    pub(crate) index: Index2,
+
+    /// The mode of the Cell, fluid or solid.
+    pub mode: &'a mut CellTypes,
+
+    /// Velocity x,y (see [`CellRef::velocity`]).
+    pub velocity: [&'a mut T; 2],
+
+    /// The pressure value.
+    pub pressure: &'a mut T,
+
+    /// The advected smoke value in `[0,1]`.
+    pub smoke: &'a mut T,
 
     /// The divergence in the cell.
     /// Corresponds to the net-outflow.
-    pub div: Scalar,
+    pub div: &'a mut T,
 
     // Fields for parallel computation (only).
     //  ================================================================
     /// Divergence ratio for velocity correction (only for parallel computation).
     /// For fluid cells: `1.0 / (Sum(fluid neighbors))` =
     ///                  `1.0 / s_nbs.sum()`
-    pub s_tot_inv: Scalar,
+    pub s_tot_inv: &'a mut T,
 
     /// Flag denoting if neighbor is a fluid cell:
     /// `[neg-direction, pos-direction]`  (only for parallel computation).
-    pub s_nbs: [Vector2; 2],
+    pub s_nbs: &'a mut [Vector2T<T>; 2],
     // ==================================================================
 }
 
-impl Cell {
-    pub fn new(index: Index2) -> Self {
-        let default_vel = Vector2::from_element(0.0);
-        let default_pressure = 0.0;
-        let default_smoke = 0.0;
-
-        return Cell {
-            index,
-            mode: CellTypes::Fluid,
-            velocity: FrontBackBuffer {
-                front: default_vel,
-                back: default_vel,
-            },
-            pressure: default_pressure,
-            smoke: FrontBackBuffer {
-                front: default_smoke,
-                back: default_smoke,
-            },
-            div: 0.0,
-            s_tot_inv: 0.0,
-            s_nbs: [Vector2::zeros(), Vector2::zeros()],
-        };
-    }
-
+impl<'a, T: Float> CellMut<'a, T> {
     pub fn index(&self) -> Index2 {
         return self.index;
     }
-}
-
-impl Integrate for Cell {
-    fn integrate(&mut self, _log: &Logger, dt: Scalar, gravity: Vector2) {
-        self.velocity.back = match self.mode {
-            CellTypes::Solid => self.velocity.back,
-            CellTypes::Fluid => self.velocity.back + dt * gravity,
-        };
-    }
-
-    fn as_any(&self) -> &dyn Any {
-        self
-    }
 
-    fn as_any_mut(&mut self) -> &mut dyn Any {
-        self
+    pub fn set_velocity(&mut self, velocity: Vector2T<T>) {
+        *self.velocity[0] = velocity.x;
+        *self.velocity[1] = velocity.y;
     }
 }
@@ -1,19 +1,25 @@
 use crate::scene::cell::*;
 use crate::types::*;
+use serde::{Deserialize, Serialize};
 
-#[derive(Clone, Debug)]
-pub struct Stats {
-    pub velocity: Vector2,
-    pub velocity_norm: Scalar,
-    pub pressure: Scalar,
-    pub smoke: Scalar,
-    pub div: Scalar,
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(bound = "")] // Implied by `Float`.
+pub struct Stats<T: Float = Scalar> {
+    pub velocity: Vector2T<T>,
+    pub velocity_norm: T,
+    pub pressure: T,
+    pub smoke: T,
+    pub div: T,
 }
 
-impl Stats {
-    pub fn identity<const I: usize>() -> Stats {
-        let init = if I == 0 { std::f64::MAX } else { std::f64::MIN };
-        let init_vec2 = Vector2::from_element(init);
+impl<T: Float> Stats<T> {
+    pub fn identity<const I: usize>() -> Stats<T> {
+        let init = if I == 0 {
+            <T as num_traits::Float>::max_value()
+        } else {
+            <T as num_traits::Float>::min_value()
+        };
+        let init_vec2 = Vector2T::from_element(init);
 
         return Stats {
             velocity: init_vec2,
@@ -24,40 +30,53 @@ impl Stats {
         };
     }
 
-    pub fn from(cell: &Cell) -> Stats {
+    pub fn from(cell: CellRef<T>) -> Stats<T> {
+        let velocity = cell.velocity();
+
         return Stats {
-            velocity: cell.velocity.back,
-            velocity_norm: cell.velocity.back.norm(),
-            pressure: cell.pressure,
-            smoke: cell.smoke.back,
-            div: cell.div,
+            velocity,
+            velocity_norm: velocity.norm(),
+            pressure: cell.pressure(),
+            smoke: cell.smoke(),
+            div: cell.div(),
         };
     }
 
-    pub fn accumulate<const I: usize>(&self, stats: &Stats) -> Stats {
-        const MIN_MAX: [fn(f64, f64) -> f64; 2] = [Scalar::min, Scalar::max];
-        const MIN_MAX_V2: [fn(&Vector2, &Vector2) -> Vector2; 2] = [Vector2::inf, Vector2::sup];
+    pub fn accumulate<const I: usize>(&self, stats: &Stats<T>) -> Stats<T> {
+        let min_max: [fn(T, T) -> T; 2] = [num_traits::Float::min, num_traits::Float::max];
+        let min_max_v2 = [Vector2T::<T>::inf, Vector2T::<T>::sup];
 
         return Stats {
-            velocity: MIN_MAX_V2[I](&self.velocity, &stats.velocity),
-            velocity_norm: MIN_MAX[I](self.velocity_norm, stats.velocity_norm),
-            pressure: MIN_MAX[I](self.pressure, stats.pressure),
-            smoke: MIN_MAX[I](self.smoke, stats.smoke),
-            div: MIN_MAX[I](self.div, stats.div),
+            velocity: min_max_v2[I](&self.velocity, &stats.velocity),
+            velocity_norm: min_max[I](self.velocity_norm, stats.velocity_norm),
+            pressure: min_max[I](self.pressure, stats.pressure),
+            smoke: min_max[I](self.smoke, stats.smoke),
+            div: min_max[I](self.div, stats.div),
         };
     }
 
-    pub fn min_identity() -> Stats {
+    pub fn min_identity() -> Stats<T> {
         return Self::identity::<0>();
     }
-    pub fn max_identity() -> Stats {
+    pub fn max_identity() -> Stats<T> {
         return Self::identity::<1>();
     }
 
-    pub fn min(&self, stats: &Stats) -> Stats {
+    /// Convert the statistics to float type `U`.
+    pub fn cast<U: Float>(&self) -> Stats<U> {
+        return Stats {
+            velocity: self.velocity.map(|v| to_float(v)),
+            velocity_norm: to_float(self.velocity_norm),
+            pressure: to_float(self.pressure),
+            smoke: to_float(self.smoke),
+            div: to_float(self.div),
+        };
+    }
+
+    pub fn min(&self, stats: &Stats<T>) -> Stats<T> {
         return self.accumulate::<0>(&stats);
     }
-    pub fn max(&self, stats: &Stats) -> Stats {
+    pub fn max(&self, stats: &Stats<T>) -> Stats<T> {
         return self.accumulate::<1>(&stats);
     }
 }
@@ -0,0 +1,79 @@
+use crate::scene::timestepper::{TimeStepper, TimeStepperState};
+use crate::types::*;
+
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
+
+/// Magic bytes at the start of each checkpoint file.
+const MAGIC: [u8; 8] = *b"RSFLUID\0";
+
+/// The version of the checkpoint format.
+/// Increment this if the serialized state changes.
+pub const CHECKPOINT_VERSION: u32 = 1;
+
+/// Write a checkpoint of the full simulation state after `step` steps.
+/// The file is written first to `<path>.tmp` and then renamed, such that an
+/// interrupted write never leaves a corrupt checkpoint behind.
+///
+/// Format: magic bytes, version (`u32`), float size in bytes (`u32`),
+/// the step (`u64`) and the [`TimeStepperState`] (all `bincode` encoded).
+pub fn save_checkpoint<T: Float>(
+    path: &str,
+    step: u64,
+    timestepper: &TimeStepper<T>,
+) -> GenericResult<()> {
+    let tmp_path = format!("{}.tmp", path);
+
+    {
+        let mut writer = BufWriter::new(File::create(&tmp_path)?);
+
+        writer.write_all(&MAGIC)?;
+        bincode::serialize_into(&mut writer, &CHECKPOINT_VERSION)?;
+        bincode::serialize_into(&mut writer, &(std::mem::size_of::<T>() as u32))?;
+        bincode::serialize_into(&mut writer, &step)?;
+        bincode::serialize_into(&mut writer, &timestepper.save_state()?)?;
+
+        writer.flush()?;
+    }
+
+    std::fs::rename(&tmp_path, path)?;
+
+    return Ok(());
+}
+
+/// Restore the full simulation state from the checkpoint file `path`
+/// into `timestepper` (set up with the same scene).
+/// Returns the number of steps done.
+pub fn load_checkpoint<T: Float>(path: &str, timestepper: &mut TimeStepper<T>) -> GenericResult<u64> {
+    let mut reader = BufReader::new(File::open(path)?);
+
+    let mut magic = [0u8; 8];
+    reader.read_exact(&mut magic)?;
+    if magic != MAGIC {
+        bail!("File '{}' is not a checkpoint.", path);
+    }
+
+    let version: u32 = bincode::deserialize_from(&mut reader)?;
+    if version != CHECKPOINT_VERSION {
+        bail!(
+            "Checkpoint version '{}' is not supported (expected '{}').",
+            version,
+            CHECKPOINT_VERSION
+        );
+    }
+
+    let float_size: u32 = bincode::deserialize_from(&mut reader)?;
+    if float_size as usize != std::mem::size_of::<T>() {
+        bail!(
+            "Checkpoint has float size '{}' but the simulation uses '{}'.",
+            float_size,
+            std::mem::size_of::<T>()
+        );
+    }
+
+    let step: u64 = bincode::deserialize_from(&mut reader)?;
+    let state: TimeStepperState<T> = bincode::deserialize_from(&mut reader)?;
+    timestepper.load_state(state)?;
+
+    return Ok(step);
+}
@@ -0,0 +1,117 @@
+use crate::scene::cell::CellTypes;
+use crate::scene::grid::{CellGetter, Grid};
+use crate::types::*;
+
+/// A connected group of solid cells inside the border (e.g. the cylinder).
+#[derive(Clone, Debug, PartialEq)]
+pub struct Obstacle {
+    pub cells: Vec<Index2>,
+}
+
+impl Obstacle {
+    /// The extent perpendicular to the inflow in x-direction
+    /// (the diameter of a cylinder) in world coordinates.
+    pub fn size<T: Float>(&self, cell_width: T) -> T {
+        let (min, max) = self.cells.iter().fold((usize::MAX, 0), |(min, max), idx| {
+            (min.min(idx.y), max.max(idx.y))
+        });
+
+        if min > max {
+            return T::zero();
+        }
+
+        return to_float::<T>(max - min + 1) * cell_width;
+    }
+
+    /// The mean of the cell centers in world coordinates.
+    pub fn center<T: Float>(&self, cell_width: T) -> Vector2T<T> {
+        let half = Vector2T::from_element(to_float::<T>(0.5));
+        let sum = self.cells.iter().fold(Vector2T::<T>::zeros(), |sum, idx| {
+            sum + to_vec2::<T>(*idx) + half
+        });
+
+        return sum * (cell_width / to_float::<T>(self.cells.len().max(1)));
+    }
+}
+
+/// Find the obstacles as the (4-connected) groups of solid cells inside the
+/// border, such that the walls are excluded. The obstacles are ordered by their
+/// first cell in row-major order.
+pub fn find_obstacles<T: Float>(grid: &Grid<T>) -> Vec<Obstacle> {
+    let is_solid = |idx: Index2| {
+        return grid.is_inside_border(idx) && grid.cell(idx).mode() == CellTypes::Solid;
+    };
+
+    let mut visited = vec![false; grid.dim.x * grid.dim.y];
+    let mut obstacles = vec![];
+
+    for j in 0..grid.dim.y {
+        for i in 0..grid.dim.x {
+            let start = idx!(i, j);
+            if visited[i + j * grid.dim.x] || !is_solid(start) {
+                continue;
+            }
+
+            let mut cells = vec![];
+            let mut stack = vec![start];
+            visited[i + j * grid.dim.x] = true;
+
+            while let Some(idx) = stack.pop() {
+                cells.push(idx);
+
+                for nb in Grid::<T>::get_neighbors_indices(idx).iter().flatten() {
+                    let k = nb.x + nb.y * grid.dim.x;
+                    if is_solid(*nb) && !visited[k] {
+                        visited[k] = true;
+                        stack.push(*nb);
+                    }
+                }
+            }
+
+            cells.sort_by_key(|idx| (idx.y, idx.x));
+            obstacles.push(Obstacle { cells });
+        }
+    }
+
+    return obstacles;
+}
+
+/// The pressure force (per unit depth) of the fluid on the obstacle, integrated
+/// over the faces between its cells and the fluid cells: `F = -Σ p n h` with the
+/// pressure `p` of the fluid cell and the outward normal `n` of the face.
+/// Viscous forces are not included (the solver is inviscid).
+pub fn pressure_force<T: Float>(grid: &Grid<T>, obstacle: &Obstacle) -> Vector2T<T> {
+    let mut force = Vector2T::<T>::zeros();
+
+    for idx in obstacle.cells.iter() {
+        let nbs = Grid::<T>::get_neighbors_indices(*idx);
+
+        for (side, normal) in [(0, -T::one()), (1, T::one())] {
+            for dir in 0..2 {
+                let cell = grid.cell(nbs[side][dir]);
+                if cell.mode() == CellTypes::Fluid {
+                    force[dir] -= cell.pressure() * normal * grid.cell_width;
+                }
+            }
+        }
+    }
+
+    return force;
+}
+
+/// The drag and lift coefficients `F / (0.5 ρ U² D)` of the `force` with the
+/// inflow speed `U` and the obstacle size `D`.
+pub fn force_coefficients<T: Float>(
+    force: Vector2T<T>,
+    density: T,
+    speed: T,
+    size: T,
+) -> Vector2T<T> {
+    let q = to_float::<T>(0.5) * density * speed * speed * size;
+
+    if q == T::zero() {
+        return Vector2T::zeros();
+    }
+
+    return force / q;
+}
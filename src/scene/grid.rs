@@ -2,29 +2,47 @@ use crate::log::{debug, info, warn, Logger};
 use crate::math::*;
 use crate::scene::cell::*;
 use crate::scene::cell_stats::*;
+use crate::scene::grid_fields::GridFields;
 use crate::scene::grid_stencil;
-use crate::scene::grid_stencil::PosStencilMut;
+use crate::scene::grid_stencil::{PosStencil, SliceMut};
+use crate::scene::grid_stencil_tiled;
 use crate::scene::grid_stencil_unsafe;
 use crate::scene::timestepper::{ExecutionMode, Integrate};
 use crate::types::*;
 
 use itertools::Itertools;
 use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
 use std::any::Any;
 use std::num::Wrapping;
 
-pub struct Grid {
-    pub cell_width: Scalar,
+#[derive(Serialize, Deserialize)]
+#[serde(bound = "")] // Implied by `Float`.
+pub struct Grid<T: Float = Scalar> {
+    pub cell_width: T,
     pub dim: Index2,
 
-    pub stats: [Stats; 2], //Min and max. accumulator statistics.
+    pub stats: [Stats<T>; 2], //Min and max. accumulator statistics.
 
-    cells: Vec<Cell>,
+    /// The max. absolute divergence of the fluid cells after the last pressure solve
+    /// (only computed with `with_div_residual`).
+    #[serde(skip, default = "T::zero")]
+    pub div_residual: T,
 
-    extent: Vector2,
+    /// Compute the `div_residual` after each pressure solve (e.g. for the statistics).
+    #[serde(skip)]
+    pub with_div_residual: bool,
+
+    /// Reduce in a fixed order (independent of the number of threads).
+    #[serde(skip)]
+    pub deterministic: bool,
+
+    fields: GridFields<T>,
+
+    extent: Vector2T<T>,
 
     // Grid offsets for each axis of the velocity in the cells..
-    offsets: [Vector2; 2],
+    offsets: [Vector2T<T>; 2],
 }
 
 #[derive(Clone)]
@@ -59,7 +77,7 @@ impl Iterator for GridIndexIterator {
             next.x = self.min.x;
         }
 
-        if Grid::is_inside_range(self.min, self.max, curr) {
+        if Grid::<Scalar>::is_inside_range(self.min, self.max, curr) {
             return Some(curr);
         }
 
@@ -67,28 +85,29 @@ impl Iterator for GridIndexIterator {
     }
 }
 
-impl Grid {
-    pub fn new(mut dim: Index2, cell_width: Scalar) -> Self {
+impl<T: Float> Grid<T> {
+    pub fn new(mut dim: Index2, cell_width: T) -> Self {
         dim.x += 2;
         dim.y += 2;
 
-        let h_2 = cell_width as Scalar * 0.5;
-        let extent = dim.cast::<Scalar>() * cell_width;
+        let h_2 = cell_width * to_float(0.5);
+        let extent = to_vec2::<T>(dim) * cell_width;
 
         return Grid {
             dim,
             cell_width,
 
-            cells: GridIndexIterator::new(dim)
-                .map(|it| Cell::new(it))
-                .collect(),
+            fields: GridFields::new(dim),
 
             stats: [Stats::min_identity(), Stats::max_identity()],
+            div_residual: T::zero(),
+            with_div_residual: false,
+            deterministic: false,
 
             extent,
             // `x`-values lie at offset `(0, h/2)` and
             // `y`-values at `(h/2, 0)`.
-            offsets: [vec2!(0.0, h_2), vec2!(h_2, 0.0)],
+            offsets: [vec2!(T::zero(), h_2), vec2!(h_2, T::zero())],
         };
     }
 
@@ -109,7 +128,7 @@ impl Grid {
     }
 
     pub fn is_inside_border(&self, index: Index2) -> bool {
-        return Grid::is_inside_range(Index2::zeros() + idx!(1, 1), self.dim - idx!(1, 1), index);
+        return Self::is_inside_range(Index2::zeros() + idx!(1, 1), self.dim - idx!(1, 1), index);
     }
 
     pub fn get_neighbors_indices(index: Index2) -> [[Index2; 2]; 2] {
@@ -129,35 +148,55 @@ impl Grid {
         ];
     }
 
-    pub fn set_obstacle(&mut self, pos: Vector2, radius: f64, velocity: Option<Vector2>) {
-        let vel = velocity.unwrap_or(Vector2::zeros());
+    pub fn set_obstacle(&mut self, pos: Vector2T<T>, radius: T, velocity: Option<Vector2T<T>>) {
+        let vel = velocity.unwrap_or(Vector2T::zeros());
 
         for idx in self.iter_index_inside() {
-            let c = (idx.cast::<Scalar>() + vec2!(0.5, 0.5)) * self.cell_width;
+            let c = (to_vec2::<T>(idx) + Vector2T::from_element(to_float(0.5))) * self.cell_width;
 
             if (c - pos).norm_squared() <= radius * radius {
-                let c = self.cell_mut(idx);
-                c.mode = CellTypes::Solid;
-                c.velocity.back = vel;
+                let mut c = self.cell_mut(idx);
+                *c.mode = CellTypes::Solid;
+                c.set_velocity(vel);
             } else {
-                self.cell_mut(idx).mode = CellTypes::Fluid;
+                *self.cell_mut(idx).mode = CellTypes::Fluid;
             }
         }
     }
 
+    /// The structure-of-arrays storage of all cell fields.
+    pub fn fields(&self) -> &GridFields<T> {
+        return &self.fields;
+    }
+
+    /// Create a grid from stored fields (e.g. read back from exported frames).
+    /// The dimension `fields.dim` includes the border cells.
+    pub fn from_fields(fields: GridFields<T>, cell_width: T) -> Self {
+        let mut grid = Grid::new(fields.dim - idx!(2, 2), cell_width);
+
+        grid.stats = Self::compute_stats_ordered(&fields);
+        grid.fields = fields;
+
+        return grid;
+    }
+
     fn compute_stats(&mut self, log: &Logger) {
-        // Parallelized accumulation of statistics.
-        self.stats[0] = self
-            .cells
-            .par_iter()
-            .map(|c| Stats::from(c))
-            .reduce(|| Stats::identity::<0>(), |a, b| Stats::min(&a, &b));
-
-        self.stats[1] = self
-            .cells
-            .par_iter()
-            .map(|c| Stats::from(c))
-            .reduce(|| Stats::identity::<1>(), |a, b| Stats::max(&a, &b));
+        let fields = &self.fields;
+
+        if self.deterministic {
+            self.stats = Self::compute_stats_ordered(fields);
+        } else {
+            // Parallelized accumulation of statistics.
+            self.stats[0] = (0..fields.len())
+                .into_par_iter()
+                .map(|i| Stats::from(fields.cell(i)))
+                .reduce(|| Stats::identity::<0>(), |a, b| Stats::min(&a, &b));
+
+            self.stats[1] = (0..fields.len())
+                .into_par_iter()
+                .map(|i| Stats::from(fields.cell(i)))
+                .reduce(|| Stats::identity::<1>(), |a, b| Stats::max(&a, &b));
+        }
 
         info!(
             log,
@@ -174,6 +213,33 @@ impl Grid {
             self.stats[1].velocity_norm
         );
     }
+
+    /// Accumulate the statistics over fixed-size chunks in parallel and
+    /// the chunk results sequentially in order. The rayon `reduce` combines
+    /// the results in an order depending on the work splitting (e.g. `min(0.0, -0.0)`).
+    fn compute_stats_ordered(fields: &GridFields<T>) -> [Stats<T>; 2] {
+        const CHUNK_SIZE: usize = 4096;
+        let n_chunks = (fields.len() + CHUNK_SIZE - 1) / CHUNK_SIZE;
+
+        let accumulate = |acc: [Stats<T>; 2], i: usize| {
+            let s = Stats::from(fields.cell(i));
+            return [acc[0].min(&s), acc[1].max(&s)];
+        };
+
+        let identity = || [Stats::min_identity(), Stats::max_identity()];
+
+        let chunks: Vec<[Stats<T>; 2]> = (0..n_chunks)
+            .into_par_iter()
+            .map(|c| {
+                let end = fields.len().min((c + 1) * CHUNK_SIZE);
+                return (c * CHUNK_SIZE..end).fold(identity(), accumulate);
+            })
+            .collect();
+
+        return chunks.iter().fold(identity(), |acc, s| {
+            return [acc[0].min(&s[0]), acc[1].max(&s[1])];
+        });
+    }
 }
 
 pub trait CellGetter<'a, I> {
@@ -192,38 +258,36 @@ pub trait CellGetter<'a, I> {
     fn cell_mut_opt(&'a mut self, index: Index2) -> Self::OutputMutOpt;
 }
 
-impl<'t> CellGetter<'t, Index2> for Grid {
-    type Item = Cell;
+// The cells are views into the fields (structure-of-arrays).
+impl<'t, T: Float> CellGetter<'t, Index2> for Grid<T> {
+    type Item = CellRef<'t, T>;
 
-    fn cell(&self, index: Index2) -> &Cell {
-        return &self.cells[index.x + index.y * self.dim.x];
-    }
+    type Output = CellRef<'t, T>;
+    type OutputMut = CellMut<'t, T>;
+
+    type OutputOpt = Option<CellRef<'t, T>>;
+    type OutputMutOpt = Option<CellMut<'t, T>>;
 
-    fn cell_mut(&mut self, index: Index2) -> &mut Cell {
-        return &mut self.cells[index.x + index.y * self.dim.x];
+    fn cell(&'t self, index: Index2) -> CellRef<'t, T> {
+        return self.fields.cell(self.fields.linear_index(index));
     }
 
-    fn cell_opt(&self, index: Index2) -> Option<&Cell> {
-        return Grid::is_inside_range(Index2::zeros(), self.dim, index).then(|| self.cell(index));
+    fn cell_mut(&'t mut self, index: Index2) -> CellMut<'t, T> {
+        let i = self.fields.linear_index(index);
+        return self.fields.cell_mut(i);
     }
 
-    fn cell_mut_opt(&mut self, index: Index2) -> Option<&mut Cell> {
-        return Grid::is_inside_range(Index2::zeros(), self.dim, index)
-            .then(|| self.cell_mut(index));
+    fn cell_opt(&'t self, index: Index2) -> Option<CellRef<'t, T>> {
+        return Grid::<T>::is_inside_range(Index2::zeros(), self.dim, index).then(|| self.cell(index));
     }
-}
 
-impl Grid {
-    pub fn modify_cells<F, const N: usize>(&mut self, indices: [usize; N], mut f: F) -> ()
-    where
-        F: FnMut([&mut Cell; N]),
-    {
-        let refs = self.cells.get_many_mut(indices).expect("Wrong indices.");
-        f(refs);
+    fn cell_mut_opt(&'t mut self, index: Index2) -> Option<CellMut<'t, T>> {
+        return Grid::<T>::is_inside_range(Index2::zeros(), self.dim, index)
+            .then(|| self.cell_mut(index));
     }
 }
 
-impl Integrate for Grid {
+impl<T: Float> Integrate<T> for Grid<T> {
     fn as_any(&self) -> &dyn Any {
         self
     }
@@ -232,95 +296,93 @@ impl Integrate for Grid {
         self
     }
 
+    fn save_state(&self) -> GenericResult<Vec<u8>> {
+        return Ok(bincode::serialize(self)?);
+    }
+
+    fn load_state(&mut self, state: &[u8]) -> GenericResult<()> {
+        let (deterministic, with_div_residual) = (self.deterministic, self.with_div_residual);
+
+        *self = bincode::deserialize(state)?;
+        self.deterministic = deterministic;
+        self.with_div_residual = with_div_residual;
+
+        return Ok(());
+    }
+
     fn reset(&mut self, log: &Logger) {
         info!(log, "Reset stats.");
         self.stats = [Stats::min_identity(), Stats::max_identity()];
     }
 
-    fn integrate(&mut self, log: &Logger, dt: Scalar, gravity: Vector2) {
+    fn integrate(
+        &mut self,
+        log: &Logger,
+        dt: T,
+        gravity: Vector2T<T>,
+        execution_mode: ExecutionMode,
+    ) {
         debug!(log, "Integrate grid.");
 
-        for cell in self.cells.iter_mut() {
-            cell.integrate(log, dt, gravity); // integrate
-        }
+        let fields = &mut self.fields;
+        for dir in 0..2 {
+            let dv = gravity[dir] * dt;
 
-        // Extrapolate to fluid cells on border.
-        let ranges = [
-            [idx!(0, 1), idx!(0, self.dim.y)],
-            [idx!(self.dim.x - 1, self.dim.x), idx!(0, self.dim.y)],
-            [idx!(0, self.dim.x), idx!(0, 1)],
-            [idx!(0, self.dim.x), idx!(self.dim.y - 1, self.dim.y)],
-        ];
+            fields.velocity[dir]
+                .back
+                .iter_mut()
+                .zip(fields.mode.iter())
+                .filter(|(_, mode)| **mode == CellTypes::Fluid)
+                .for_each(|(v, _)| *v += dv); // integrate
+        }
 
         debug!(log, "Extrapolate border.");
-
-        for range in ranges {
-            let xr = range[0];
-            let yr = range[1];
-
-            for (i, j) in (xr[0]..xr[1]).cartesian_product(yr[0]..yr[1]) {
-                let idx = idx!(i, j);
-
-                if self.cell(idx).mode == CellTypes::Solid {
-                    continue;
-                }
-
-                for dir in 0..2 {
-                    let pos = idx.cast::<Scalar>() * self.cell_width + self.offsets[dir];
-
-                    // Just sample on the inside grid by clamping.
-                    self.cell_mut(idx).velocity.back[dir] = self.sample_field(
-                        idx!(1, 1),
-                        self.dim - idx!(1, 1),
-                        pos,
-                        Some(dir),
-                        |cell: &Cell| cell.velocity.back[dir],
-                    );
-                }
-            }
-        }
+        self.extrapolate_border(execution_mode);
     }
 
     fn solve_incompressibility(
         &mut self,
         log: &Logger,
-        dt: Scalar,
+        dt: T,
         iterations: u64,
-        density: Scalar,
+        density: T,
         execution_mode: ExecutionMode,
     ) {
         match execution_mode {
-            ExecutionMode::Parallel => {
-                self.solve_incompressibility_parallel(log, dt, iterations, density, false);
-            }
-            ExecutionMode::ParallelUnsafe => {
-                self.solve_incompressibility_parallel(log, dt, iterations, density, true);
+            ExecutionMode::Parallel
+            | ExecutionMode::ParallelUnsafe
+            | ExecutionMode::ParallelTiled { .. } => {
+                self.solve_incompressibility_parallel(log, dt, iterations, density, execution_mode);
             }
             ExecutionMode::Single => {
                 self.solve_incompressibility_sequential(log, dt, iterations, density);
             }
         }
         self.compute_stats(&log);
+
+        if self.with_div_residual {
+            self.div_residual = self.max_divergence();
+        }
     }
 
-    fn advect(&mut self, log: &slog::Logger, dt: Scalar) {
-        self.advect_velocity(log, dt);
-        self.advect_smoke(log, dt);
+    fn advect(&mut self, log: &slog::Logger, dt: T, execution_mode: ExecutionMode) {
+        self.advect_velocity(log, dt, execution_mode);
+        self.advect_smoke(log, dt, execution_mode);
     }
 }
 
-impl Grid {
+impl<T: Float> Grid<T> {
     #[inline(always)]
-    fn apply_pos_stencils<T>(&mut self, use_unsafe: bool, min: Index2, max: Index2, func: T)
+    fn apply_pos_stencils<F>(&mut self, use_unsafe: bool, min: Index2, max: Index2, func: F)
     where
-        T: Fn(PosStencilMut<Cell>) + Send + Sync,
+        F: Fn(PosStencil<CellMut<T>>) + Send + Sync,
     {
         const OFFSETS: [Index2; 4] = [idx!(0, 0), idx!(1, 0), idx!(0, 1), idx!(1, 1)];
 
         if use_unsafe {
             for offset in OFFSETS.iter() {
                 grid_stencil_unsafe::positive_stencils_mut(
-                    self.cells.as_mut_slice(),
+                    self.fields.view_mut(),
                     self.dim,
                     Some(min),
                     Some(max),
@@ -331,7 +393,7 @@ impl Grid {
         } else {
             for offset in OFFSETS.iter() {
                 grid_stencil::positive_stencils_mut(
-                    self.cells.as_mut_slice(),
+                    self.fields.view_mut(),
                     self.dim,
                     Some(min),
                     Some(max),
@@ -342,29 +404,54 @@ impl Grid {
         }
     }
 
+    #[inline(always)]
+    fn apply_tiled_stencils<F>(
+        &mut self,
+        min: Index2,
+        max: Index2,
+        tile_size: Index2,
+        sweeps: u64,
+        func: F,
+    ) where
+        F: Fn(PosStencil<CellMut<T>>) + Send + Sync,
+    {
+        const OFFSETS: [Index2; 4] = [idx!(0, 0), idx!(1, 0), idx!(0, 1), idx!(1, 1)];
+
+        for offset in OFFSETS.iter() {
+            grid_stencil_tiled::tiles_mut(
+                self.fields.view_mut(),
+                self.dim,
+                min,
+                max,
+                tile_size,
+                *offset,
+            )
+            .into_par_iter()
+            .for_each(|mut tile| {
+                // Safety: `func` does not keep the cells.
+                unsafe { tile.sweep(sweeps, &func) };
+            });
+        }
+    }
+
     fn solve_incompressibility_parallel(
         &mut self,
         log: &Logger,
-        dt: Scalar,
+        dt: T,
         iterations: u64,
-        density: Scalar,
-        use_unsafe: bool,
+        density: T,
+        execution_mode: ExecutionMode,
     ) {
-        assert!(
-            self.dim.x % 2 == 0 && self.dim.y % 2 == 0,
-            "Internal grid dimensions (dim = {} - 1) must be divisible
-             by 2 in each direction.",
-            self.dim
-        );
+        let use_unsafe = matches!(execution_mode, ExecutionMode::ParallelUnsafe);
 
-        let r = 1.9; // Overrelaxation factor.
+        let r: T = to_float(1.9); // Overrelaxation factor.
         let cp = density * self.cell_width / dt;
 
-        let s_factor = |cell: &mut Cell| {
-            return if cell.mode == CellTypes::Solid {
-                0.0
+        let s_factor = |cell: &CellMut<T>| {
+            return if *cell.mode == CellTypes::Solid {
+                T::zero()
             } else {
-                1.0
+                T::one()
             };
         };
 
@@ -373,16 +460,16 @@ impl Grid {
             use_unsafe,
             idx!(0, 0),
             self.dim,
-            |s: PosStencilMut<Cell>| {
+            |s: PosStencil<CellMut<T>>| {
                 // This parallel run runs over all edges affected in the simulation domain.
                 // We also run over some boundary cells
                 // which we will anyway not use later.
-                let cell_s = s_factor(s.cell);
+                let cell_s = s_factor(&s.cell);
 
                 // This cell (1: pos, 0: x)  <-- s from pos x-neighbor.
-                s.cell.s_nbs[1][0] = s_factor(s.neighbors[0]);
+                s.cell.s_nbs[1][0] = s_factor(&s.neighbors[0]);
                 // This cell (1: pos, 1: y) <-- s from pos y-neighbor.
-                s.cell.s_nbs[1][1] = s_factor(s.neighbors[1]);
+                s.cell.s_nbs[1][1] = s_factor(&s.neighbors[1]);
 
                 // Pos. x-neighbor (0: neg, 0: x) <-- s from this cell.
                 s.neighbors[0].s_nbs[0][0] = cell_s;
@@ -392,180 +479,284 @@ impl Grid {
         );
 
         debug!(log, "Sum all 's' factors in all cells.");
-        self.cells.par_iter_mut().for_each(|c: &mut Cell| {
-            if c.mode == CellTypes::Solid {
-                return;
-            }
+        grid_stencil::par_chunks_exact_mut(self.fields.view_mut(), 1)
+            .map(SliceMut::into_first)
+            .for_each(|c: CellMut<T>| {
+                if *c.mode == CellTypes::Solid {
+                    return;
+                }
+
+                // Reset pressure field.
+                *c.pressure = T::zero();
 
-            // Reset pressure field.
-            c.pressure = 0.0;
+                let mut sum = T::zero();
+                c.s_nbs.iter().for_each(|s| {
+                    sum += s.sum();
+                });
 
-            let mut sum = 0.0;
-            c.s_nbs.iter().for_each(|s| {
-                sum += s.sum();
+                // Store the inverse.
+                *c.s_tot_inv = if sum != T::zero() {
+                    T::one() / sum
+                } else {
+                    debug_assert!(
+                        false,
+                        "Cell with index: '{}' [solid: {:?} contains only solid neighbors.",
+                        c.index(),
+                        *c.mode,
+                    );
+                    T::zero()
+                };
             });
 
-            // Store the inverse.
-            c.s_tot_inv = if sum != 0.0 {
-                1.0 / sum
-            } else {
-                debug_assert!(
-                    false,
-                    "Cell with index: '{}' [solid: {:?} contains only solid neighbors.",
-                    c.index(),
-                    c.mode,
-                );
-                0.0
-            };
-        });
+        let update = |s: PosStencil<CellMut<T>>| {
+            // This parallel run runs stencils over the simulation domain:
+            // The `s.cell` will covers all cells in the simulation domain.
 
-        for _iter in 0..iterations {
-            self.apply_pos_stencils(
-                use_unsafe,
-                idx!(1, 1),
-                self.dim,
-                |s: PosStencilMut<Cell>| {
-                    // This parallel run runs stencils over the simulation domain:
-                    // The `s.cell` will covers all cells in the simulation domain.
+            if *s.cell.mode == CellTypes::Solid {
+                return;
+            }
 
-                    if s.cell.mode == CellTypes::Solid {
-                        return;
-                    }
+            debug_assert!(
+                *s.cell.s_tot_inv != T::zero(),
+                "Cell with index: '{}' contains only fluid neighbors.",
+                s.cell.index()
+            );
 
-                    debug_assert!(
-                        s.cell.s_tot_inv != 0.0,
-                        "Cell with index: '{}' contains only fluid neighbors.",
-                        s.cell.index()
-                    );
+            *s.cell.div = T::zero();
+            for dir in 0..2 {
+                *s.cell.div += *s.neighbors[dir].velocity[dir] - *s.cell.velocity[dir]
+            }
 
-                    s.cell.div = 0.0;
-                    for dir in 0..2 {
-                        s.cell.div +=
-                            s.neighbors[dir].velocity.back[dir] - s.cell.velocity.back[dir]
-                    }
+            let div_normed = *s.cell.div * *s.cell.s_tot_inv;
 
-                    let div_normed = s.cell.div * s.cell.s_tot_inv;
+            *s.cell.pressure -= cp * div_normed;
 
-                    s.cell.pressure -= cp * div_normed;
+            // Velocity update own cell.
+            for dir in 0..2 {
+                *s.cell.velocity[dir] += s.cell.s_nbs[0][dir] * r * div_normed;
+            }
 
-                    // Velocity update own cell.
-                    s.cell.velocity.back += r * s.cell.s_nbs[0] * div_normed;
+            // Velocity update neighbors in x-direction.
+            // Solid cells have s_nbs[_] == 0.
+            *s.neighbors[0].velocity[0] -= r * s.cell.s_nbs[1].x * div_normed;
+            // Velocity update neighbors in y-direction.
+            *s.neighbors[1].velocity[1] -= r * s.cell.s_nbs[1].y * div_normed;
+        };
 
-                    // Velocity update neighbors in x-direction.
-                    // Solid cells have s_nbs[_] == 0.
-                    s.neighbors[0].velocity.back[0] -= r * s.cell.s_nbs[1].x * div_normed;
-                    // Velocity update neighbors in y-direction.
-                    s.neighbors[1].velocity.back[1] -= r * s.cell.s_nbs[1].y * div_normed;
-                },
-            );
+        if let ExecutionMode::ParallelTiled { tile_size, sweeps } = execution_mode {
+            // Each tile runs `sweeps` iterations before moving on.
+            let mut remaining = iterations;
+            while remaining > 0 {
+                let n = sweeps.clamp(1, remaining);
+                self.apply_tiled_stencils(idx!(1, 1), self.dim, tile_size, n, update);
+                remaining -= n;
+            }
+        } else {
+            for _iter in 0..iterations {
+                self.apply_pos_stencils(use_unsafe, idx!(1, 1), self.dim, update);
+            }
         }
     }
 
     fn solve_incompressibility_sequential(
         &mut self,
         log: &Logger,
-        dt: Scalar,
+        dt: T,
         iterations: u64,
-        density: Scalar,
+        density: T,
     ) {
         // Set pressure field to zero.
-        self.cells.par_iter_mut().for_each(|c| c.pressure = 0.0);
+        self.fields.pressure.par_iter_mut().for_each(|p| *p = T::zero());
 
-        let r = 1.9; // Overrelaxation factor.
+        let r: T = to_float(1.9); // Overrelaxation factor.
         let cp = density * self.cell_width / dt;
 
+        let dim = self.dim;
+
+        // Only stream the fields needed.
+        let GridFields {
+            mode,
+            velocity: [u, v],
+            pressure,
+            div: divergence,
+            ..
+        } = &mut self.fields;
+        let vel = [&mut u.back, &mut v.back];
+
         for _iter in 0..iterations {
-            for idx in self.iter_index_inside() {
-                if self.cell(idx).mode == CellTypes::Solid {
+            // Run over all cells inside (same order as `iter_index_inside`).
+            for (j, x) in (1..dim.y - 1).flat_map(|j| (1..dim.x - 1).map(move |x| (j, x))) {
+                let i = x + j * dim.x;
+
+                if mode[i] == CellTypes::Solid {
                     continue;
                 }
 
-                let s_factor = |index: Index2| {
-                    return if self.cell(index).mode == CellTypes::Solid {
-                        0.0
+                let s_factor = |i: usize| {
+                    return if mode[i] == CellTypes::Solid {
+                        T::zero()
                     } else {
-                        1.0
+                        T::one()
                     };
                 };
 
-                let nbs = Grid::get_neighbors_indices(idx);
+                // Linear indices of the negative and positive neighbors.
+                let nbs = [[i - 1, i - dim.x], [i + 1, i + dim.x]];
 
                 // Normalization values `s`
                 // for negative/positive neighbors.
                 // - 0: solid, 1: fluid.
-                let mut s_nbs = [Vector2::zeros(), Vector2::zeros()];
-                let mut s = 0.0;
+                let mut s_nbs = [Vector2T::<T>::zeros(), Vector2T::zeros()];
+                let mut s = T::zero();
 
                 for neg_pos in 0..2 {
                     s_nbs[neg_pos] = vec2!(s_factor(nbs[neg_pos][0]), s_factor(nbs[neg_pos][1]));
                     s += s_nbs[neg_pos].sum();
                 }
 
-                if s == 0.0 {
-                    warn!(log, "Fluid in-face count is 0.0 for {:?}", idx);
+                if s == T::zero() {
+                    warn!(log, "Fluid in-face count is 0.0 for {:?}", idx!(x, j));
                     continue;
                 }
 
-                let get_vel = |index: Index2, dir: usize| {
-                    return self.cell(index).velocity.back[dir];
-                };
-
-                let mut div: Scalar = 0.0; // Net outflow on this cell.
+                let mut div = T::zero(); // Net outflow on this cell.
                 let pos_idx = 1;
                 let pos_nbs = &nbs[pos_idx];
                 for dir in 0..2 {
-                    div += get_vel(pos_nbs[dir], dir) - get_vel(idx, dir)
+                    div += vel[dir][pos_nbs[dir]] - vel[dir][i]
                 }
 
-                self.cell_mut(idx).div = div;
+                divergence[i] = div;
 
                 // Normalize outflow to the cells we can control.
                 let div_normed = div / s;
-                self.cell_mut(idx).pressure -= cp * div_normed;
+                pressure[i] -= cp * div_normed;
 
                 // Add outflow-part to inflows to reach net 0-outflow.
                 // Solid cells have s_nbs[0] == 0.
-                self.cell_mut(idx).velocity.back += r * s_nbs[0] * div_normed;
+                for dir in 0..2 {
+                    vel[dir][i] += s_nbs[0][dir] * r * div_normed;
+                }
 
                 // Subtract outflow-part to outflows to iteratively reach net 0-outflow (div(v) == 0).
                 // Solid cells have s_nbs[_] == 0.
-                self.cell_mut(nbs[pos_idx][0]).velocity.back.x -= r * s_nbs[pos_idx].x * div_normed;
-                self.cell_mut(nbs[pos_idx][1]).velocity.back.y -= r * s_nbs[pos_idx].y * div_normed;
+                vel[0][pos_nbs[0]] -= r * s_nbs[pos_idx].x * div_normed;
+                vel[1][pos_nbs[1]] -= r * s_nbs[pos_idx].y * div_normed;
             }
         }
     }
 
-    fn advect_velocity(&mut self, log: &slog::Logger, dt: Scalar) {
-        debug!(log, "Advect velocity.");
+    /// Extrapolate the velocity to the fluid cells on the border.
+    /// The border values are sampled only from the inside cells, hence all
+    /// values are computed first and then written.
+    fn extrapolate_border(&mut self, execution_mode: ExecutionMode) {
+        let ranges = [
+            [idx!(0, 1), idx!(0, self.dim.y)],
+            [idx!(self.dim.x - 1, self.dim.x), idx!(0, self.dim.y)],
+            [idx!(0, self.dim.x), idx!(0, 1)],
+            [idx!(0, self.dim.x), idx!(self.dim.y - 1, self.dim.y)],
+        ];
+
+        let border: Vec<Index2> = ranges
+            .iter()
+            .flat_map(|[xr, yr]| (xr[0]..xr[1]).cartesian_product(yr[0]..yr[1]))
+            .map(|(i, j)| idx!(i, j))
+            .filter(|idx| self.cell(*idx).mode() != CellTypes::Solid)
+            .collect();
+
+        for dir in 0..2 {
+            let extrapolate = |idx: &Index2| {
+                let pos = to_vec2::<T>(*idx) * self.cell_width + self.offsets[dir];
+
+                // Just sample on the inside grid by clamping.
+                let v = self.sample_field(
+                    idx!(1, 1),
+                    self.dim - idx!(1, 1),
+                    pos,
+                    Some(dir),
+                    |cell: CellRef<T>| cell.velocity()[dir],
+                );
 
-        self.cells
-            .par_iter_mut()
-            .for_each(|c| c.velocity.front = c.velocity.back);
+                return (self.fields.linear_index(*idx), v);
+            };
 
-        for idx in self.iter_index_inside() {
-            if self.cell(idx).mode == CellTypes::Solid {
-                continue;
+            let values: Vec<(usize, T)> = match execution_mode {
+                ExecutionMode::Single => border.iter().map(extrapolate).collect(),
+                _ => border.par_iter().map(extrapolate).collect(),
+            };
+
+            for (i, v) in values {
+                self.fields.velocity[dir].back[i] = v;
             }
+        }
+    }
 
-            let nbs = Grid::get_neighbors_indices(idx);
+    /// Compute the values `func(index)` for all inside cells and store them
+    /// into `out` (if `Some`). The rows are processed in parallel for the
+    /// parallel execution modes.
+    fn compute_inside<F>(&self, execution_mode: ExecutionMode, out: &mut [T], func: F)
+    where
+        F: Fn(Index2) -> Option<T> + Sync + Send,
+    {
+        let dim = self.dim;
+
+        let compute_row = |(j, row): (usize, &mut [T])| {
+            if j == 0 || j >= dim.y - 1 {
+                return;
+            }
+
+            for (i, val) in row.iter_mut().enumerate().take(dim.x - 1).skip(1) {
+                if let Some(v) = func(idx!(i, j)) {
+                    *val = v;
+                }
+            }
+        };
+
+        match execution_mode {
+            ExecutionMode::Single => out
+                .chunks_exact_mut(dim.x)
+                .enumerate()
+                .for_each(compute_row),
+            _ => out
+                .par_chunks_exact_mut(dim.x)
+                .enumerate()
+                .for_each(compute_row),
+        }
+    }
+
+    fn advect_velocity(&mut self, log: &slog::Logger, dt: T, execution_mode: ExecutionMode) {
+        debug!(log, "Advect velocity.");
+
+        for dir in 0..2 {
+            self.fields.velocity[dir].copy_back_to_front();
+        }
+
+        // Advect the two staggered grids (x and then y-direction).
+        for dir in 0..2 {
+            // Only the back buffers are read, so take the front buffer out for writing.
+            let mut front = std::mem::take(&mut self.fields.velocity[dir].front);
+
+            let advect = |idx: Index2| {
+                if self.cell(idx).mode() == CellTypes::Solid {
+                    return None;
+                }
 
-            // Advect the two staggered grids (x and then y-direction).
-            for dir in 0..2 {
                 // Is the negative neighbor a solid cell, then do not advect this velocity.
-                if self.cell(nbs[0][dir]).mode == CellTypes::Solid {
-                    continue;
+                let nbs = Grid::<T>::get_neighbors_indices(idx);
+                if self.cell(nbs[0][dir]).mode() == CellTypes::Solid {
+                    return None;
                 }
 
-                let mut pos = idx.cast::<Scalar>() * self.cell_width + self.offsets[dir];
-                let mut vel: Vector2 = self.cell(idx).velocity.back;
+                let mut pos = to_vec2::<T>(idx) * self.cell_width + self.offsets[dir];
+                let mut vel: Vector2T<T> = self.cell(idx).velocity();
 
-                let sample = |pos: Vector2, dir: usize| {
+                let sample = |pos: Vector2T<T>, dir: usize| {
                     return self.sample_field(
                         idx!(1, 1),
                         self.dim - idx!(1, 1),
                         pos,
                         Some(dir),
-                        |cell: &Cell| cell.velocity.back[dir],
+                        |cell: CellRef<T>| cell.velocity()[dir],
                     );
                 };
 
@@ -573,79 +764,92 @@ impl Grid {
                 vel[other_dir] = sample(pos, other_dir);
 
                 // Get position of particle which reached this position.
-                pos = pos - dt * vel;
-                //debug!(log, "Idx: {}", idx);
+                pos -= vel * dt;
 
                 // Set the past velocity at this cell.
-                self.cell_mut(idx).velocity.front[dir] = sample(pos, dir);
-            }
+                return Some(sample(pos, dir));
+            };
+
+            self.compute_inside(execution_mode, &mut front, advect);
+            self.fields.velocity[dir].front = front;
         }
 
-        self.cells.par_iter_mut().for_each(|c| c.velocity.swap());
+        for dir in 0..2 {
+            self.fields.velocity[dir].swap();
+        }
     }
 
-    fn advect_smoke(&mut self, log: &slog::Logger, dt: Scalar) {
+    fn advect_smoke(&mut self, log: &slog::Logger, dt: T, execution_mode: ExecutionMode) {
         debug!(log, "Advect smoke.");
 
-        self.cells
-            .par_iter_mut()
-            .for_each(|c| c.smoke.front = c.smoke.back);
+        self.fields.smoke.copy_back_to_front();
 
-        for idx in self.iter_index_inside() {
-            if self.cell(idx).mode == CellTypes::Solid {
-                continue;
+        // Only the back buffer is read, so take the front buffer out for writing.
+        let mut front = std::mem::take(&mut self.fields.smoke.front);
+
+        let advect = |idx: Index2| {
+            if self.cell(idx).mode() == CellTypes::Solid {
+                return None;
             }
 
-            let nbs = Grid::get_neighbors_indices(idx);
-            let mut pos = (idx.cast::<Scalar>() + vec2!(0.5, 0.5)) * self.cell_width;
+            let nbs = Grid::<T>::get_neighbors_indices(idx);
+            let half = to_float::<T>(0.5);
+            let mut pos = (to_vec2::<T>(idx) + Vector2T::from_element(half)) * self.cell_width;
 
-            let mut vel = Vector2::zeros();
+            let mut vel = Vector2T::<T>::zeros();
             for dir in 0..2 {
                 vel += vec2!(
-                    self.cell(nbs[dir][0]).velocity.back.x,
-                    self.cell(nbs[dir][1]).velocity.back.y
-                ) * 0.5;
+                    self.cell(nbs[dir][0]).velocity().x,
+                    self.cell(nbs[dir][1]).velocity().y
+                ) * half;
             }
 
-            pos = pos - dt * vel;
+            pos -= vel * dt;
 
-            self.cell_mut(idx).smoke.front = self.sample_field(
+            return Some(self.sample_field(
                 idx!(0, 0),
                 self.dim - idx!(0, 0),
                 pos,
                 None,
-                |cell: &Cell| cell.smoke.back,
-            );
-        }
+                |cell: CellRef<T>| cell.smoke(),
+            ));
+        };
 
-        self.cells.par_iter_mut().for_each(|c| c.smoke.swap());
+        self.compute_inside(execution_mode, &mut front, advect);
+        self.fields.smoke.front = front;
+
+        self.fields.smoke.swap();
     }
 
-    pub fn sample_field<F: Fn(&Cell) -> Scalar>(
+    pub fn sample_field<F: Fn(CellRef<T>) -> T>(
         &self,
         min: Index2,
         max: Index2,
-        mut pos: Vector2,
+        mut pos: Vector2T<T>,
         dir: Option<usize>,
         get_val: F,
-    ) -> Scalar {
+    ) -> T {
         let h = self.cell_width;
-        let h_inv = 1.0 / self.cell_width;
+        let h_inv = T::one() / self.cell_width;
 
         // If `dir` is set, we need some offset.
         // For velocities as they are on a staggered grid.
-        let offset = dir.map_or(Vector2::zeros(), |d| self.offsets[d]);
+        let offset = dir.map_or(Vector2T::zeros(), |d| self.offsets[d]);
         pos = pos - offset; // Compute position on staggered grid.
-        pos = clamp_to_range(Vector2::zeros(), self.extent, pos);
+        pos = clamp_to_range(Vector2T::zeros(), self.extent, pos);
 
         // Compute index.
-        let mut index = Index2::from_iterator((pos * h_inv).iter().map(|v| *v as usize));
+        let mut index = (pos * h_inv).map(|v| v.to_usize().unwrap_or(0));
 
         let clamp_index = |i| clamp_to_range(min, max - idx!(1, 1), i);
 
         index = clamp_index(index);
-        let pos_cell = pos - index.cast::<Scalar>() * h;
-        let alpha = clamp_to_range(vec2!(0.0, 0.0), vec2!(1.0, 1.0), pos_cell * h_inv);
+        let pos_cell = pos - to_vec2::<T>(index) * h;
+        let alpha = clamp_to_range(
+            Vector2T::zeros(),
+            Vector2T::from_element(T::one()),
+            pos_cell * h_inv,
+        );
 
         // debug!(log, "Sample at: {}", index);
 
@@ -660,16 +864,121 @@ impl Grid {
         ];
 
         // Get all values on the grid.
-        let m = Matrix2::from_iterator(
+        let m = Matrix2T::<T>::from_iterator(
             nbs.map(|i| {
                 return get_val(self.cell(i));
             })
             .into_iter(), // Column major order.
         );
 
-        let t1 = vec2!(1.0 - alpha.x, alpha.x);
-        let t2 = vec2!(alpha.y, 1.0 - alpha.y);
+        let t1 = vec2!(T::one() - alpha.x, alpha.x);
+        let t2 = vec2!(alpha.y, T::one() - alpha.y);
 
         return t2.dot(&(m * t1));
     }
+
+    /// Sample the staggered velocity at position `pos` (interpolated on the inside grid).
+    pub fn sample_velocity(&self, pos: Vector2T<T>) -> Vector2T<T> {
+        let sample = |dir: usize| {
+            return self.sample_field(
+                idx!(1, 1),
+                self.dim - idx!(1, 1),
+                pos,
+                Some(dir),
+                |cell: CellRef<T>| cell.velocity()[dir],
+            );
+        };
+
+        return vec2!(sample(0), sample(1));
+    }
+
+    /// Sample a cell centered field (e.g. the pressure) at position `pos`
+    /// (interpolated on the inside grid).
+    pub fn sample_center<F: Fn(CellRef<T>) -> T>(&self, pos: Vector2T<T>, get_val: F) -> T {
+        let half = Vector2T::from_element(to_float::<T>(0.5) * self.cell_width);
+        return self.sample_field(idx!(1, 1), self.dim - idx!(1, 1), pos - half, None, get_val);
+    }
+
+    /// Compute the vorticity (curl) at the cell centers from the staggered velocities
+    /// (the mean of the four corner values). It is zero in the border cells (row-major).
+    pub fn compute_vorticity(&self) -> Vec<T> {
+        let h_inv = T::one() / self.cell_width;
+        let quarter = to_float::<T>(0.25);
+
+        // The curl at the lower-left corner of cell `(i, j)`.
+        let corner = |i: usize, j: usize| {
+            let (u0, u1) = (
+                self.cell(idx!(i, j - 1)).velocity().x,
+                self.cell(idx!(i, j)).velocity().x,
+            );
+            let (v0, v1) = (
+                self.cell(idx!(i - 1, j)).velocity().y,
+                self.cell(idx!(i, j)).velocity().y,
+            );
+            return ((v1 - v0) - (u1 - u0)) * h_inv;
+        };
+
+        return (0..self.dim.x * self.dim.y)
+            .into_par_iter()
+            .map(|k| {
+                let (i, j) = (k % self.dim.x, k / self.dim.x);
+                if !self.is_inside_border(idx!(i, j)) {
+                    return T::zero();
+                }
+
+                return (corner(i, j) + corner(i + 1, j) + corner(i, j + 1) + corner(i + 1, j + 1))
+                    * quarter;
+            })
+            .collect();
+    }
+
+    /// The max. absolute divergence of the fluid cells
+    /// (the sum of the staggered velocity differences, as in the pressure solve).
+    /// The rows are reduced in parallel (the max. is independent of the order).
+    pub fn max_divergence(&self) -> T {
+        let max = |a: T, b: T| num_traits::Float::max(a, b);
+
+        return (1..self.dim.y - 1)
+            .into_par_iter()
+            .map(|j| {
+                return (1..self.dim.x - 1)
+                    .map(|i| idx!(i, j))
+                    .filter(|idx| self.cell(*idx).mode() != CellTypes::Solid)
+                    .fold(T::zero(), |m, idx| {
+                        let v = self.cell(idx).velocity();
+                        let div = self.cell(idx!(idx.x + 1, idx.y)).velocity().x - v.x
+                            + self.cell(idx!(idx.x, idx.y + 1)).velocity().y
+                            - v.y;
+                        return max(m, num_traits::Float::abs(div));
+                    });
+            })
+            .reduce(T::zero, max);
+    }
+
+    /// The total amount of smoke in the fluid cells (integrated over the cell area).
+    pub fn total_smoke(&self) -> T {
+        let area = self.cell_width * self.cell_width;
+
+        return self
+            .iter_index_inside()
+            .filter(|idx| self.cell(*idx).mode() != CellTypes::Solid)
+            .fold(T::zero(), |sum, idx| sum + self.cell(idx).smoke() * area);
+    }
+
+    /// The kinetic energy of the fluid cells with the velocity interpolated
+    /// to the cell centers.
+    pub fn kinetic_energy(&self, density: T) -> T {
+        let area = self.cell_width * self.cell_width;
+        let half = to_float::<T>(0.5);
+
+        return self
+            .iter_index_inside()
+            .filter(|idx| self.cell(*idx).mode() != CellTypes::Solid)
+            .fold(T::zero(), |sum, idx| {
+                let v = self.cell(idx).velocity();
+                let u = (v.x + self.cell(idx!(idx.x + 1, idx.y)).velocity().x) * half;
+                let w = (v.y + self.cell(idx!(idx.x, idx.y + 1)).velocity().y) * half;
+                return sum + half * density * (u * u + w * w) * area;
+            });
+    }
 }
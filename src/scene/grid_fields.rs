@@ -0,0 +1,206 @@
+use crate::scene::cell::*;
+use crate::scene::grid_stencil::SliceMut;
+use crate::types::*;
+
+use serde::{Deserialize, Serialize};
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+
+/// Structure-of-arrays storage of all cell fields of the grid.
+/// Each field is stored in its own contiguous array
+/// (first dimension is stored first (column-major)), such that the
+/// kernels only stream the fields they need through the cache.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(bound = "")] // Implied by `Float`.
+pub struct GridFields<T: Float = Scalar> {
+    pub dim: Index2,
+
+    /// The mode of the cells, fluid or solid (solid mask).
+    pub mode: Vec<CellTypes>,
+
+    /// The velocity components `[u, v]` (see [`CellRef::velocity`]).
+    pub velocity: [FrontBackBuffer<Vec<T>>; 2],
+
+    /// The pressure values.
+    pub pressure: Vec<T>,
+
+    /// The advected smoke values in `[0,1]`.
+    pub smoke: FrontBackBuffer<Vec<T>>,
+
+    /// The divergence in the cells.
+    pub div: Vec<T>,
+
+    // Fields for parallel computation (only).
+    //  ================================================================
+    /// Divergence ratios for velocity correction (see [`CellMut::s_tot_inv`]).
+    pub s_tot_inv: Vec<T>,
+
+    /// Flags denoting if neighbors are fluid cells (see [`CellMut::s_nbs`]).
+    pub s_nbs: Vec<[Vector2T<T>; 2]>,
+    // ==================================================================
+}
+
+impl<T: Float> GridFields<T> {
+    pub fn new(dim: Index2) -> Self {
+        let n = dim.x * dim.y;
+        let zeros = vec![T::zero(); n];
+        let buffer = FrontBackBuffer {
+            front: zeros.clone(),
+            back: zeros.clone(),
+        };
+
+        return GridFields {
+            dim,
+            mode: vec![CellTypes::Fluid; n],
+            velocity: [buffer.clone(), buffer.clone()],
+            pressure: zeros.clone(),
+            smoke: buffer,
+            div: zeros.clone(),
+            s_tot_inv: zeros,
+            s_nbs: vec![[Vector2T::zeros(), Vector2T::zeros()]; n],
+        };
+    }
+
+    pub fn len(&self) -> usize {
+        return self.mode.len();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        return self.mode.is_empty();
+    }
+
+    /// The linear index of the cell with index `index`.
+    #[inline(always)]
+    pub fn linear_index(&self, index: Index2) -> usize {
+        return index.x + index.y * self.dim.x;
+    }
+
+    pub fn cell(&self, i: usize) -> CellRef<'_, T> {
+        assert!(i < self.len(), "Index out of range.");
+        return CellRef::new(self, i);
+    }
+
+    pub fn cell_mut(&mut self, i: usize) -> CellMut<'_, T> {
+        assert!(i < self.len(), "Index out of range.");
+        // Safety: `i` is in range and the view is dropped.
+        return unsafe { self.view_mut().get_unchecked_mut(i) };
+    }
+
+    /// A checksum over the bit patterns of all simulation fields
+    /// (front buffers and solver fields excluded) for regression tests.
+    pub fn checksum(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        let mut hash = |values: &[T]| {
+            values
+                .iter()
+                .for_each(|v| to_float::<f64>(*v).to_bits().hash(&mut hasher))
+        };
+
+        hash(&self.velocity[0].back);
+        hash(&self.velocity[1].back);
+        hash(&self.pressure);
+        hash(&self.smoke.back);
+        hash(&self.div);
+
+        self.mode.hash(&mut hasher);
+
+        return hasher.finish();
+    }
+
+    /// Mutable view on all fields for the stencil iterators.
+    pub fn view_mut(&mut self) -> FieldsMut<'_, T> {
+        let [u, v] = &mut self.velocity;
+
+        return FieldsMut {
+            start: 0,
+            dim_x: self.dim.x,
+            mode: &mut self.mode,
+            velocity: [&mut u.back, &mut v.back],
+            pressure: &mut self.pressure,
+            smoke: &mut self.smoke.back,
+            div: &mut self.div,
+            s_tot_inv: &mut self.s_tot_inv,
+            s_nbs: &mut self.s_nbs,
+        };
+    }
+}
+
+/// Mutable view on a range of all fields in [`GridFields`] (back buffers).
+/// The items are [`CellMut`] views.
+pub struct FieldsMut<'a, T: Float = Scalar> {
+    /// The linear index of the first element of this view.
+    start: usize,
+    dim_x: usize,
+
+    mode: &'a mut [CellTypes],
+    velocity: [&'a mut [T]; 2],
+    pressure: &'a mut [T],
+    smoke: &'a mut [T],
+    div: &'a mut [T],
+    s_tot_inv: &'a mut [T],
+    s_nbs: &'a mut [[Vector2T<T>; 2]],
+}
+
+impl<'a, T: Float> SliceMut for FieldsMut<'a, T> {
+    type Item = CellMut<'a, T>;
+
+    fn len(&self) -> usize {
+        return self.mode.len();
+    }
+
+    fn split_at_mut(self, mid: usize) -> (Self, Self) {
+        let [u, v] = self.velocity;
+
+        let (mode, mode_r) = self.mode.split_at_mut(mid);
+        let (u, u_r) = u.split_at_mut(mid);
+        let (v, v_r) = v.split_at_mut(mid);
+        let (pressure, pressure_r) = self.pressure.split_at_mut(mid);
+        let (smoke, smoke_r) = self.smoke.split_at_mut(mid);
+        let (div, div_r) = self.div.split_at_mut(mid);
+        let (s_tot_inv, s_tot_inv_r) = self.s_tot_inv.split_at_mut(mid);
+        let (s_nbs, s_nbs_r) = self.s_nbs.split_at_mut(mid);
+
+        return (
+            FieldsMut {
+                start: self.start,
+                dim_x: self.dim_x,
+                mode,
+                velocity: [u, v],
+                pressure,
+                smoke,
+                div,
+                s_tot_inv,
+                s_nbs,
+            },
+            FieldsMut {
+                start: self.start + mid,
+                dim_x: self.dim_x,
+                mode: mode_r,
+                velocity: [u_r, v_r],
+                pressure: pressure_r,
+                smoke: smoke_r,
+                div: div_r,
+                s_tot_inv: s_tot_inv_r,
+                s_nbs: s_nbs_r,
+            },
+        );
+    }
+
+    unsafe fn get_unchecked_mut(&mut self, i: usize) -> CellMut<'a, T> {
+        let k = self.start + i;
+
+        return CellMut {
+            index: idx!(k % self.dim_x, k / self.dim_x),
+            mode: SliceMut::get_unchecked_mut(&mut self.mode, i),
+            velocity: [
+                SliceMut::get_unchecked_mut(&mut self.velocity[0], i),
+                SliceMut::get_unchecked_mut(&mut self.velocity[1], i),
+            ],
+            pressure: SliceMut::get_unchecked_mut(&mut self.pressure, i),
+            smoke: SliceMut::get_unchecked_mut(&mut self.smoke, i),
+            div: SliceMut::get_unchecked_mut(&mut self.div, i),
+            s_tot_inv: SliceMut::get_unchecked_mut(&mut self.s_tot_inv, i),
+            s_nbs: SliceMut::get_unchecked_mut(&mut self.s_nbs, i),
+        };
+    }
+}
@@ -1,30 +1,164 @@
 use crate::math::*;
 use crate::types::*;
 
-use rayon::iter::{IndexedParallelIterator, ParallelIterator};
-use rayon::slice::ParallelSliceMut;
+use rayon::iter::ParallelIterator;
 
-pub struct PosStencilMut<'a, T>
+/// A mutable view on column-major grid data which can be split like a slice.
+/// Implemented for slices (array-of-structs) and for the
+/// [`GridFields`](crate::scene::grid_fields::GridFields)
+/// (structure-of-arrays), such that the stencil iterators work on both.
+pub trait SliceMut: Sized + Send {
+    /// The mutable reference (view) to a single element.
+    type Item: Send;
+
+    fn len(&self) -> usize;
+
+    fn is_empty(&self) -> bool {
+        return self.len() == 0;
+    }
+
+    fn split_at_mut(self, mid: usize) -> (Self, Self);
+
+    /// Get the mutable reference (view) to the element `i`.
+    ///
+    /// # Safety
+    /// The index `i` must be in range and the caller must guarantee
+    /// that no two items with the same index are alive at the same time.
+    unsafe fn get_unchecked_mut(&mut self, i: usize) -> Self::Item;
+
+    /// Convert into the mutable reference (view) of the first element.
+    fn into_first(mut self) -> Self::Item {
+        assert!(!self.is_empty(), "View is empty.");
+        // Safety: `self` is consumed, only one item gets created.
+        return unsafe { self.get_unchecked_mut(0) };
+    }
+}
+
+impl<'a, T> SliceMut for &'a mut [T]
+where
+    T: Send,
+{
+    type Item = &'a mut T;
+
+    fn len(&self) -> usize {
+        return <[T]>::len(self);
+    }
+
+    fn split_at_mut(self, mid: usize) -> (Self, Self) {
+        return <[T]>::split_at_mut(self, mid);
+    }
+
+    unsafe fn get_unchecked_mut(&mut self, i: usize) -> &'a mut T {
+        return &mut *self.as_mut_ptr().add(i);
+    }
+}
+
+/// Two zipped views of the same length.
+impl<A, B> SliceMut for (A, B)
 where
-    T: Send + Sync,
+    A: SliceMut,
+    B: SliceMut,
+{
+    type Item = (A::Item, B::Item);
+
+    fn len(&self) -> usize {
+        return self.0.len().min(self.1.len());
+    }
+
+    fn split_at_mut(self, mid: usize) -> (Self, Self) {
+        let (a0, a1) = self.0.split_at_mut(mid);
+        let (b0, b1) = self.1.split_at_mut(mid);
+        return ((a0, b0), (a1, b1));
+    }
+
+    unsafe fn get_unchecked_mut(&mut self, i: usize) -> Self::Item {
+        return (self.0.get_unchecked_mut(i), self.1.get_unchecked_mut(i));
+    }
+}
+
+pub struct PosStencil<I>
+where
+    I: Send,
 {
     /// The current cell.
-    pub cell: &'a mut T,
+    pub cell: I,
 
     /// The positive neighbors in `x`,`y`-direction.
-    pub neighbors: [&'a mut T; 2],
+    pub neighbors: [I; 2],
+}
+
+pub type PosStencilMut<'a, T> = PosStencil<&'a mut T>;
+
+/// Iterator over all chunks with exact size `size` of the view.
+/// The remainder is skipped.
+pub struct ChunksExactMut<D> {
+    data: Option<D>,
+    size: usize,
+}
+
+impl<D> ChunksExactMut<D> {
+    pub fn new(data: D, size: usize) -> Self {
+        assert!(size > 0, "Chunk size must be non-zero.");
+        return ChunksExactMut {
+            data: Some(data),
+            size,
+        };
+    }
+}
+
+impl<D> Iterator for ChunksExactMut<D>
+where
+    D: SliceMut,
+{
+    type Item = D;
+
+    fn next(&mut self) -> Option<D> {
+        let data = self.data.take()?;
+        if data.len() < self.size {
+            return None;
+        }
+
+        let (chunk, rest) = data.split_at_mut(self.size);
+        self.data = Some(rest);
+        return Some(chunk);
+    }
+}
+
+/// Parallel iterator over all chunks with exact size `size` of the view.
+/// The remainder is skipped.
+pub fn par_chunks_exact_mut<D>(data: D, size: usize) -> impl ParallelIterator<Item = D>
+where
+    D: SliceMut,
+{
+    assert!(size > 0, "Chunk size must be non-zero.");
+
+    let n = data.len() / size;
+    let (data, _) = data.split_at_mut(n * size);
+
+    // Rayon splits the view into pieces (multiple of `size`)
+    // until there is enough work, each piece is then chunked sequentially.
+    return rayon::iter::split(data, move |d| {
+        let n = d.len() / size;
+        if n <= 1 {
+            return (d, None);
+        }
+
+        let (l, r) = d.split_at_mut((n / 2) * size);
+        return (l, Some(r));
+    })
+    .flat_map_iter(move |d| ChunksExactMut::new(d, size));
 }
 
 /// First dimension is stored first (column-major).
-pub fn positive_stencils_mut<T>(
-    data: &mut [T],
+pub fn positive_stencils_mut<D>(
+    data: D,
     dim: Index2,
     min: Option<Index2>,    // Min point.
     max: Option<Index2>,    // Max point (exclusive).
     offset: Option<Index2>, // Stencil offset added to min/max.
-) -> impl ParallelIterator<Item = PosStencilMut<T>>
+) -> impl ParallelIterator<Item = PosStencil<D::Item>>
 where
-    T: Send + Sync,
+    D: SliceMut,
 {
     assert!(
         dim > idx!(0, 0) && dim.iter().fold(1, std::ops::Mul::mul) == data.len(),
@@ -50,21 +184,26 @@ where
     let start_y = 0 + min.y * dim.x;
     let stop_y = 0 + max.y * dim.x; // exclusive.
 
-    return data[start_y..stop_y]
-        .par_chunks_exact_mut(2 * dim.x)
-        .flat_map(move |row| {
-            let (top, bot) = row.split_at_mut(dim.x);
-            let y0 = top[min.x..max.x].par_chunks_exact_mut(2);
-            let y1 = bot[min.x..max.x].par_chunks_exact_mut(2);
-
-            y0.zip(y1).map(|ys| match ys {
-                ([ref mut x0_y0, ref mut x1_y0], [ref mut x0_y1, _]) => PosStencilMut {
-                    cell: x0_y0,
-                    neighbors: [x1_y0, x0_y1],
-                },
-                _ => unreachable!(),
-            })
-        });
+    let (_, data) = data.split_at_mut(start_y);
+    let (data, _) = data.split_at_mut(stop_y - start_y);
+
+    // A stencil needs its positive neighbors inside the range, hence a trailing
+    // odd row/column is skipped here (it is covered by the stencils with the
+    // other offsets).
+    return par_chunks_exact_mut(data, 2 * dim.x).flat_map(move |rows| {
+        let (top, bot) = rows.split_at_mut(dim.x);
+        let y0 = top.split_at_mut(min.x).1.split_at_mut(max.x - min.x).0;
+        let y1 = bot.split_at_mut(min.x).1.split_at_mut(max.x - min.x).0;
+
+        par_chunks_exact_mut((y0, y1), 2).map(|(y0, y1)| {
+            let (x0_y0, x1_y0) = y0.split_at_mut(1);
+
+            PosStencil {
+                cell: x0_y0.into_first(),
+                neighbors: [x1_y0.into_first(), y1.into_first()],
+            }
+        })
+    });
 }
 
 mod test {
@@ -209,4 +348,69 @@ mod test {
 
         print!("{:?}", v.as_slice());
     }
+
+    #[test]
+    fn test_zipped_views() {
+        // Two fields (structure-of-arrays):
+        // 4 5 6    40 50 60
+        // 1 2 3    10 20 30
+        // -> x
+        let mut a = nalgebra::Matrix3x2::<usize>::new(1, 4, 2, 5, 3, 6);
+        let mut b = a * 10;
+
+        positive_stencils_mut(
+            (a.as_mut_slice(), b.as_mut_slice()),
+            idx!(3, 2),
+            None,
+            None,
+            None,
+        )
+        .for_each(|s| {
+            *s.cell.0 += *s.neighbors[0].1;
+            *s.neighbors[1].1 += *s.cell.0;
+        });
+
+        assert!(a[(0, 0)] == 21);
+        assert!(b[(0, 1)] == 61);
+        assert!(a.as_slice()[1..] == [2, 3, 4, 5, 6]);
+        assert!(b.as_slice()[..3] == [10, 20, 30]);
+        assert!(b.as_slice()[4..] == [50, 60]);
+    }
+
+    #[test]
+    fn test_chunks_exact() {
+        let mut v = [1, 2, 3, 4, 5, 6, 7];
+        let chunks: Vec<Vec<usize>> = ChunksExactMut::new(v.as_mut_slice(), 3)
+            .map(|c| c.to_vec())
+            .collect();
+        assert!(chunks == vec![vec![1, 2, 3], vec![4, 5, 6]]);
+
+        let n = par_chunks_exact_mut(v.as_mut_slice(), 2)
+            .map(|c| {
+                c[0] += 10;
+                return c.len();
+            })
+            .sum::<usize>();
+        assert!(n == 6);
+        assert!(v == [11, 2, 13, 4, 15, 6, 7]);
+    }
+
+    #[test]
+    fn test_four_colors_odd() {
+        // All stencils of the four offsets cover each cell which has
+        // positive neighbors exactly once, also for odd dimensions.
+        let dim = idx!(5, 3);
+        let mut v = vec![0usize; dim.x * dim.y];
+
+        for offset in [idx!(0, 0), idx!(1, 0), idx!(0, 1), idx!(1, 1)] {
+            positive_stencils_mut(v.as_mut_slice(), dim, None, None, Some(offset)).for_each(|s| {
+                *s.cell += 1;
+            });
+        }
+
+        for (i, n) in v.iter().enumerate() {
+            let expected = if i % dim.x < dim.x - 1 && i / dim.x < dim.y - 1 { 1 } else { 0 };
+            assert!(*n == expected, "Cell {} covered {} times.", i, n);
+        }
+    }
 }
@@ -0,0 +1,172 @@
+use crate::scene::grid_stencil::{PosStencil, SliceMut};
+use crate::types::*;
+
+const OFFSETS: [Index2; 4] = [idx!(0, 0), idx!(1, 0), idx!(0, 1), idx!(1, 1)];
+
+/// A rectangular tile of stencils on column-major grid data.
+/// The tile holds one view per row, including the halo row and column
+/// on the positive side (the positive neighbors of the stencils on the border).
+pub struct Tile<D> {
+    /// The index of the first stencil cell.
+    pub min: Index2,
+    /// The number of stencil cells (without halo).
+    pub size: Index2,
+
+    /// The origin of the stencil coloring (the `min` of the whole range).
+    origin: Index2,
+    rows: Vec<D>,
+}
+
+/// Split `data` into all tiles of size `tile_size` with tile offset `offset`.
+/// The stencil cells lie in the range `[min, max - 1)` (the positive neighbors
+/// must be inside `[min, max)`), the tiles with offset `(a, b)` are the tiles
+/// `(a + 2k, b + 2l)`, such that the tiles (including their halo) never overlap.
+/// First dimension is stored first (column-major).
+pub fn tiles_mut<D>(
+    data: D,
+    dim: Index2,
+    min: Index2,
+    max: Index2,
+    tile_size: Index2,
+    offset: Index2,
+) -> Vec<Tile<D>>
+where
+    D: SliceMut,
+{
+    assert!(
+        dim > idx!(0, 0) && dim.iter().fold(1, std::ops::Mul::mul) == data.len(),
+        "Wrong dimensions."
+    );
+
+    assert!(
+        tile_size > idx!(0, 0) && min < max && max <= dim,
+        "Min: {}, max: {}, dim: {} and tile size: {}",
+        min,
+        max,
+        dim,
+        tile_size
+    );
+
+    // The stencil ranges `[start, end)` of all tiles with `offset` in direction `dir`.
+    let ranges = |dir: usize| -> Vec<(usize, usize)> {
+        let end = max[dir] - 1;
+
+        return (min[dir]..end)
+            .step_by(tile_size[dir])
+            .skip(offset[dir])
+            .step_by(2)
+            .map(|start| (start, end.min(start + tile_size[dir])))
+            .collect();
+    };
+
+    let (x_ranges, y_ranges) = (ranges(0), ranges(1));
+
+    let mut tiles: Vec<Tile<D>> = y_ranges
+        .iter()
+        .flat_map(|y| {
+            x_ranges.iter().map(move |x| Tile {
+                min: idx!(x.0, y.0),
+                size: idx!(x.1 - x.0, y.1 - y.0),
+                origin: min,
+                rows: vec![],
+            })
+        })
+        .collect();
+
+    // Split off all rows of the tiles (including the halo row)
+    // and split each row into the pieces of the tiles (including the halo column).
+    let mut rest = data;
+    let mut rest_start = 0;
+
+    for (b, y_range) in y_ranges.iter().enumerate() {
+        for y in y_range.0..=y_range.1 {
+            let (_, r) = rest.split_at_mut(y * dim.x - rest_start);
+            let (mut row, r) = r.split_at_mut(dim.x);
+            rest = r;
+            rest_start = (y + 1) * dim.x;
+
+            let mut row_start = 0;
+            for (a, x_range) in x_ranges.iter().enumerate() {
+                let (_, r) = row.split_at_mut(x_range.0 - row_start);
+                let (piece, r) = r.split_at_mut(x_range.1 + 1 - x_range.0);
+                row = r;
+                row_start = x_range.1 + 1;
+
+                tiles[b * x_ranges.len() + a].rows.push(piece);
+            }
+        }
+    }
+
+    return tiles;
+}
+
+impl<D> Tile<D>
+where
+    D: SliceMut,
+{
+    /// Run `sweeps` red-black sweeps (four offsets) over all stencils in this tile.
+    ///
+    /// # Safety
+    /// Each cell is visited `sweeps` times, hence `func` must not
+    /// keep any item of the stencils after the call.
+    pub unsafe fn sweep<F>(&mut self, sweeps: u64, func: &F)
+    where
+        F: Fn(PosStencil<D::Item>),
+    {
+        for _ in 0..sweeps {
+            for offset in OFFSETS.iter() {
+                // Start such that the coloring is the same as for the whole range.
+                let start = (offset + self.min - self.origin).map(|v| v % 2);
+
+                for j in (start.y..self.size.y).step_by(2) {
+                    let (rows, rows_next) = self.rows.split_at_mut(j + 1);
+                    let (row, row_next) = (&mut rows[j], &mut rows_next[0]);
+
+                    for i in (start.x..self.size.x).step_by(2) {
+                        func(PosStencil {
+                            cell: row.get_unchecked_mut(i),
+                            neighbors: [row.get_unchecked_mut(i + 1), row_next.get_unchecked_mut(i)],
+                        });
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn test_coverage() {
+    // All tiles of the four offsets cover each stencil cell in `[min, max - 1)`
+    // exactly `sweeps` times, also for tile sizes which do not divide the range.
+    let dim = idx!(9, 7);
+    let mut v = vec![0usize; dim.x * dim.y];
+
+    for offset in OFFSETS.iter() {
+        tiles_mut(v.as_mut_slice(), dim, idx!(1, 1), dim, idx!(3, 2), *offset)
+            .into_iter()
+            .for_each(|mut t| unsafe { t.sweep(2, &|s| *s.cell += 1) });
+    }
+
+    for (i, n) in v.iter().enumerate() {
+        let (x, y) = (i % dim.x, i / dim.x);
+        let expected = if x >= 1 && x < dim.x - 1 && y >= 1 && y < dim.y - 1 { 2 } else { 0 };
+        assert!(*n == expected, "Cell {} covered {} times.", i, n);
+    }
+}
+
+#[test]
+fn test_neighbors() {
+    let dim = idx!(8, 5);
+    let mut v: Vec<usize> = (0..dim.x * dim.y).collect();
+
+    for offset in OFFSETS.iter() {
+        tiles_mut(v.as_mut_slice(), dim, idx!(0, 0), dim, idx!(2, 3), *offset)
+            .into_iter()
+            .for_each(|mut t| unsafe {
+                t.sweep(1, &|s| {
+                    assert!(*s.neighbors[0] == *s.cell + 1);
+                    assert!(*s.neighbors[1] == *s.cell + dim.x);
+                })
+            });
+    }
+}
@@ -1,19 +1,19 @@
 use crate::math::*;
-use crate::scene::grid_stencil::PosStencilMut;
+use crate::scene::grid_stencil::{PosStencil, SliceMut};
 use crate::types::*;
 use itertools::Itertools;
 use rayon::prelude::*;
 
 /// First dimension is stored first (column-major).
-pub fn positive_stencils_mut<T>(
-    data: &mut [T],
+pub fn positive_stencils_mut<D>(
+    mut data: D,
     dim: Index2,
     min: Option<Index2>,
     max: Option<Index2>,
     offset: Option<Index2>, // Stencil offset added to min/max.
-) -> impl ParallelIterator<Item = PosStencilMut<'_, T>>
+) -> impl ParallelIterator<Item = PosStencil<D::Item>>
 where
-    T: Send + Sync,
+    D: SliceMut,
 {
     assert!(
         dim > idx!(0, 0) && dim.iter().fold(1, std::ops::Mul::mul) == data.len(),
@@ -28,10 +28,6 @@ where
     min += offset;
     max = clamp_to_range(idx!(0, 0), dim, max + offset);
 
-    for dir in 0..2 {
-        max[dir] -= (max[dir] - min[dir]) % 2 // Subtract the remainder to make the range correct.
-    }
-
     assert!(
         min >= Index2::zeros() && max <= dim && min < max,
         "Min: {} and max: {}, dim: {}",
@@ -40,24 +36,29 @@ where
         dim
     );
 
-    return (min[0]..max[0])
+    // A stencil at `index` needs its positive neighbors inside the range, hence
+    // the last stencil starts at `max - 2` (also for odd ranges, where the
+    // trailing row/column is covered by the stencils with the other offsets).
+    return (min[0]..max[0] - 1)
         .step_by(2)
-        .cartesian_product((min[1]..max[1]).step_by(2))
+        .cartesian_product((min[1]..max[1] - 1).step_by(2))
         .map(move |(i, j)| {
             let index = idx!(i, j);
 
             // Here the unsafe part happens.
-            let offset = index[0] + index[1] * dim[0];
-            let cell: *mut T = unsafe { data.as_mut_ptr().add(offset) };
-
             // Get two non-aliasing mutable references for the neighbors.
-            return PosStencilMut {
-                cell: unsafe { &mut *cell },
-                neighbors: [unsafe { &mut *cell.add(1) }, unsafe {
-                    &mut *cell.add(dim[0])
-                }],
+            let offset = index[0] + index[1] * dim[0];
+            return unsafe {
+                PosStencil {
+                    cell: data.get_unchecked_mut(offset),
+                    neighbors: [
+                        data.get_unchecked_mut(offset + 1),
+                        data.get_unchecked_mut(offset + dim[0]),
+                    ],
+                }
             };
-        }).par_bridge();
+        })
+        .par_bridge();
 
 }
 
@@ -69,26 +70,35 @@ fn test() {
     // -> x
     let mut v = Matrix2T::<usize>::new(1, 3, 2, 4);
     {
-        let mut it = positive_stencils_mut(v.as_mut_slice(), idx!(2, 2), None, None);
-        let s = it.next().unwrap();
+        let mut stencils: Vec<_> =
+            positive_stencils_mut(v.as_mut_slice(), idx!(2, 2), None, None, None).collect();
+        assert!(stencils.len() == 1);
 
-        // drop(v); // This should invalidate the life-time of `s`
+        let s = &mut stencils[0];
         *s.cell += 3;
-        if let Some(&mut ref mut n) = s.neighbors[0] {
-            *n += 3;
-        };
-        if let Some(&mut ref mut n) = s.neighbors[1] {
-            *n += 3;
-        };
+        *s.neighbors[0] += 3;
+        *s.neighbors[1] += 3;
     }
 
     assert!(v[(0, 0)] == 4);
     assert!(v[(1, 0)] == 5);
     assert!(v[(0, 1)] == 6);
+    assert!(v[(1, 1)] == 4);
+}
 
-    {
-        let mut it = positive_stencils_mut(v.as_mut_slice(), idx!(2, 2), None, None);
-        it.next();
-        assert!(it.next().is_none());
+#[test]
+fn test_four_colors_odd() {
+    let dim = idx!(5, 3);
+    let mut v = vec![0usize; dim.x * dim.y];
+
+    for offset in [idx!(0, 0), idx!(1, 0), idx!(0, 1), idx!(1, 1)] {
+        positive_stencils_mut(v.as_mut_slice(), dim, None, None, Some(offset)).for_each(|s| {
+            *s.cell += 1;
+        });
+    }
+
+    for (i, n) in v.iter().enumerate() {
+        let expected = if i % dim.x < dim.x - 1 && i / dim.x < dim.y - 1 { 1 } else { 0 };
+        assert!(*n == expected, "Cell {} covered {} times.", i, n);
     }
 }
@@ -1,12 +1,19 @@
 pub mod cell;
 pub mod cell_stats;
 
+pub mod checkpoint;
+pub mod forces;
 pub mod grid;
+pub mod grid_fields;
 pub mod grid_stencil;
+pub mod grid_stencil_tiled;
 pub mod grid_stencil_unsafe;
 
+pub mod probes;
 pub mod setup;
+pub mod streamlines;
 pub mod timestepper;
+pub mod tracers;
 
 pub mod visualization;
 
@@ -0,0 +1,72 @@
+use crate::scene::cell::CellRef;
+use crate::scene::grid::Grid;
+use crate::types::*;
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::BufReader;
+
+fn default_line_samples() -> usize {
+    return 50;
+}
+
+/// A line probe from `start` to `end` (world coordinates) sampled at
+/// `samples` equidistant points.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ProbeLine {
+    pub start: Vector2,
+    pub end: Vector2,
+    #[serde(default = "default_line_samples")]
+    pub samples: usize,
+}
+
+impl ProbeLine {
+    /// The sample points with their distance to `start`.
+    pub fn points(&self) -> Vec<(Scalar, Vector2)> {
+        let n = self.samples.max(2);
+        let length = (self.end - self.start).norm();
+
+        return (0..n)
+            .map(|i| {
+                let s = i as Scalar / (n - 1) as Scalar;
+                return (s * length, self.start + (self.end - self.start) * s);
+            })
+            .collect();
+    }
+}
+
+/// The point and line probes (e.g. from a JSON file
+/// `{"points": [[0.5, 0.5]], "lines": [{"start": [1.0, 0.0], "end": [1.0, 1.0], "samples": 20}]}`).
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct ProbeConfig {
+    #[serde(default)]
+    pub points: Vec<Vector2>,
+    #[serde(default)]
+    pub lines: Vec<ProbeLine>,
+}
+
+impl ProbeConfig {
+    pub fn is_empty(&self) -> bool {
+        return self.points.is_empty() && self.lines.is_empty();
+    }
+}
+
+pub fn load_probes(file: &str) -> GenericResult<ProbeConfig> {
+    return Ok(serde_json::from_reader(BufReader::new(File::open(file)?))?);
+}
+
+/// The interpolated fields at a probe position.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ProbeSample<T: Float = Scalar> {
+    pub velocity: Vector2T<T>,
+    pub pressure: T,
+    pub smoke: T,
+}
+
+/// Sample the velocity, the pressure and the smoke at `pos` (world coordinates).
+pub fn sample_probe<T: Float>(grid: &Grid<T>, pos: Vector2T<T>) -> ProbeSample<T> {
+    return ProbeSample {
+        velocity: grid.sample_velocity(pos),
+        pressure: grid.sample_center(pos, |cell: CellRef<T>| cell.pressure()),
+        smoke: grid.sample_center(pos, |cell: CellRef<T>| cell.smoke()),
+    };
+}
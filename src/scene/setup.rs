@@ -4,10 +4,13 @@ use std::str::FromStr;
 use crate::log::*;
 use crate::scene::cell::CellTypes;
 use crate::scene::grid::{CellGetter, Grid};
+use crate::scene::streamlines::{seed_points, Seeds};
 use crate::scene::timestepper::{Integrate, Manipulator, TimeStepper, ExecutionMode};
+use crate::scene::tracers::Tracers;
 use crate::types::*;
-use clap::Parser;
+use clap::{Args, Parser, Subcommand, ValueEnum};
 use nalgebra as na;
+use serde::{Deserialize, Serialize};
 
 fn parse_vector<T, const DIM: usize>(s: &str) -> Result<na::SVector<T, DIM>, String>
 where
@@ -29,12 +32,284 @@ where
     return Ok(na::SVector::<T, DIM>::from_iterator(it));
 }
 
+/// Parse a per-field option `<field>=<value>` of the field plots.
+fn parse_field_option(s: &str) -> Result<(String, String), String> {
+    let (field, value) = s
+        .split_once('=')
+        .ok_or(format!("Need '<field>=<value>'. '{}'", s))?;
+
+    let field = field.trim();
+    if !["vel", "press", "vort", "div"].contains(&field) {
+        return Err(format!(
+            "Field '{}' is not one of 'vel', 'press', 'vort' or 'div'.",
+            field
+        ));
+    }
+
+    return Ok((field.to_string(), value.trim().to_string()));
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum OutputFormat {
+    /// Plots as `.png` images.
+    Png,
+    /// All fields as VTK ImageData `.vti` files with a `.pvd` collection.
+    Vti,
+    /// All fields as NumPy `.npy` arrays with a `.json` metadata sidecar.
+    Npy,
+    /// All fields bundled in a NumPy `.npz` archive with a `.json` metadata sidecar.
+    Npz,
+    /// Plots as animated `.gif` files (one per plot).
+    Gif,
+    /// Plots as animated `.png` (APNG) files (one per plot).
+    Apng,
+    /// One plot as raw RGBA frames streamed to `--stream-output`.
+    Rgba,
+    /// One plot as YUV4MPEG2 video streamed to `--stream-output`.
+    Y4m,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum PlotRenderer {
+    /// Draw each cell as a rectangle with `plotters`.
+    Plotters,
+    /// Write the cell colors directly into the pixel buffer (parallel).
+    Direct,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum PlotInterpolation {
+    /// Nearest cell color.
+    Nearest,
+    /// Bilinear interpolation between the cell centers.
+    Bilinear,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum StreamlineSeeds {
+    /// On a lattice (traced in both directions).
+    Lattice,
+    /// Along the inflow (traced downstream).
+    Inflow,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum TracerSeeds {
+    /// On a lattice with `--tracer-spacing`.
+    Lattice,
+    /// Along the inflow with `--tracer-spacing`.
+    Inflow,
+    /// At the points `--tracer-points`.
+    Points,
+}
+
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
-pub struct CLIArgs {
+pub struct Cli {
+    /// Number of threads for the parallel execution (default: number of cores).
+    #[arg(long = "threads", global = true)]
+    pub threads: Option<usize>,
+
+    #[command(subcommand)]
+    pub command: Command,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Run the simulation and write the frames.
+    Simulate(Box<CLIArgs>),
+    /// Plot the fields written by `simulate` (`--output-format npy|npz`).
+    Render(Box<RenderArgs>),
+}
+
+/// The plot settings (shared by `simulate` and `render`).
+#[derive(Args, Debug)]
+pub struct PlotArgs {
     #[arg(short = 'o', long, default_value_t = String::from("./frames/frame-{}.png"))]
     pub output: String,
 
+    #[arg(long = "plot-dim", default_value = "1600, 800", value_parser = parse_vector::<usize, 2>)]
+    pub plot_dim: Index2,
+
+    #[arg(long = "plot-pressure", default_value_t = false)]
+    pub plot_pressure: bool,
+
+    #[arg(long = "plot-velocity", default_value_t = false)]
+    pub plot_velocity: bool,
+
+    #[arg(long = "plot-masked-pressure", default_value_t = false)]
+    pub plot_masked_pressure: bool,
+
+    #[arg(long = "plot-masked-velocity", default_value_t = false)]
+    pub plot_masked_velocity: bool,
+
+    #[arg(long = "plot-stats", default_value_t = false)]
+    pub plot_stats: bool,
+
+    /// Plot the vorticity (curl of the velocity, centered at zero).
+    #[arg(long = "plot-vorticity", default_value_t = false)]
+    pub plot_vorticity: bool,
+
+    #[arg(long = "plot-masked-vorticity", default_value_t = false)]
+    pub plot_masked_vorticity: bool,
+
+    /// Plot the divergence of the velocity (centered at zero).
+    #[arg(long = "plot-divergence", default_value_t = false)]
+    pub plot_divergence: bool,
+
+    #[arg(long = "plot-masked-divergence", default_value_t = false)]
+    pub plot_masked_divergence: bool,
+
+    /// The colormaps of the field plots as `<field>=<name>` (comma-separated, fields
+    /// `vel`, `press`, `vort`, `div`, names `turbo`, `viridis`, `magma`, `coolwarm`,
+    /// `grayscale` or from `--plot-colormap-config`).
+    #[arg(long = "plot-colormap", value_delimiter = ',', value_parser = parse_field_option)]
+    pub plot_colormap: Vec<(String, String)>,
+
+    /// JSON file with custom colormaps, e.g. `{"fire": ["#000000", "#ff0000", "#ffff00"]}`.
+    #[arg(long = "plot-colormap-config")]
+    pub plot_colormap_config: Option<String>,
+
+    /// The value ranges of the field plots as `<field>=<range>` (comma-separated)
+    /// with the range `auto` (per frame), `symmetric` (around zero), `<min>:<max>`,
+    /// `global` (running min./max. over the frames) or `smoothed:<alpha>` (moving
+    /// average with the weight `alpha` of the newest frame). Add `-symmetric` to
+    /// `global` and `smoothed` to center them at zero (e.g. `smoothed-symmetric:0.1`).
+    #[arg(long = "plot-range", value_delimiter = ',', value_parser = parse_field_option)]
+    pub plot_range: Vec<(String, String)>,
+
+    /// Combine the plots into one image (`panels`) with this layout `<columns>,<rows>`,
+    /// each plot with a title and the stats on top.
+    #[arg(long = "plot-layout", value_parser = parse_vector::<usize, 2>)]
+    pub plot_layout: Option<Index2>,
+
+    /// The plots in the layout (comma-separated, row-major, default all).
+    #[arg(long = "plot-panels", value_delimiter = ',', value_parser = ["smoke", "vel", "press", "vort", "div", "stream", "lic"])]
+    pub plot_panels: Vec<String>,
+
+    /// Draw a colorbar with tick labels next to the field plots.
+    #[arg(long = "plot-colorbar", default_value_t = false)]
+    pub plot_colorbar: bool,
+
+    /// The number of tick labels of the colorbar.
+    #[arg(long = "plot-colorbar-ticks", default_value_t = 5)]
+    pub plot_colorbar_ticks: usize,
+
+    /// The renderer of the plots.
+    #[arg(long = "plot-renderer", value_enum, default_value = "plotters")]
+    pub plot_renderer: PlotRenderer,
+
+    /// The upscaling of the cells to the plot size (`direct` renderer).
+    #[arg(long = "plot-interpolation", value_enum, default_value = "nearest")]
+    pub plot_interpolation: PlotInterpolation,
+
+    /// Overlay velocity arrows on all plots on a lattice with this spacing (in cells).
+    #[arg(long = "plot-quiver")]
+    pub plot_quiver: Option<usize>,
+
+    /// The length of the arrow of the max. velocity (in lattice spacings).
+    #[arg(long = "plot-quiver-scale", default_value_t = 1.0)]
+    pub plot_quiver_scale: Scalar,
+
+    /// Clamp the arrow lengths (in lattice spacings).
+    #[arg(long = "plot-quiver-clamp")]
+    pub plot_quiver_clamp: Option<Scalar>,
+
+    /// Plot streamlines traced with RK4 through the velocity field.
+    #[arg(long = "plot-streamlines", default_value_t = false)]
+    pub plot_streamlines: bool,
+
+    /// Where the streamlines are seeded.
+    #[arg(long = "plot-streamline-seeds", value_enum, default_value = "lattice")]
+    pub plot_streamline_seeds: StreamlineSeeds,
+
+    /// The spacing of the streamline seeds (in cells).
+    #[arg(long = "plot-streamline-spacing", default_value_t = 16)]
+    pub plot_streamline_spacing: usize,
+
+    /// Plot the line integral convolution (LIC) of the velocity field.
+    #[arg(long = "plot-lic", default_value_t = false)]
+    pub plot_lic: bool,
+
+    /// Color the LIC texture by the velocity magnitude.
+    #[arg(long = "plot-lic-colored", default_value_t = false)]
+    pub plot_lic_colored: bool,
+
+    /// The LIC kernel length in each direction (in cells).
+    #[arg(long = "plot-lic-length", default_value_t = 10)]
+    pub plot_lic_length: usize,
+
+    /// Output pattern of the animations (`{}` is replaced by the plot name).
+    #[arg(long = "animation-output", default_value_t = String::from("./{}"))]
+    pub animation_output: String,
+
+    /// Frames per second of the animations.
+    #[arg(long = "frame-rate", default_value_t = 25.0)]
+    pub frame_rate: f64,
+
+    /// Number of times the animations are played (`0` loops forever).
+    #[arg(long = "loops", default_value_t = 0)]
+    pub loops: u16,
+
+    /// Sampling factor `1..=30` of the GIF palette quantization (`1`: best quality).
+    #[arg(long = "gif-quantization-speed", default_value_t = 10)]
+    pub gif_quantization_speed: i32,
+
+    /// Output of the frame stream, a file or named pipe (`-` is `stdout`).
+    #[arg(long = "stream-output", default_value_t = String::from("-"))]
+    pub stream_output: String,
+
+    /// The plot which is streamed.
+    #[arg(long = "stream-plot", default_value = "smoke", value_parser = ["smoke", "vel", "press", "vort", "div", "stream", "lic", "panels"])]
+    pub stream_plot: String,
+}
+
+#[derive(Args, Debug)]
+pub struct RenderArgs {
+    /// Input pattern of the fields written by `simulate` (`--npy-output`).
+    #[arg(short = 'i', long, default_value_t = String::from("./frames/fields-{}"))]
+    pub input: String,
+
+    #[command(flatten)]
+    pub plot: PlotArgs,
+
+    /// The output formats of the plots (comma-separated, `png`, `gif`, `apng`, `rgba` or `y4m`).
+    #[arg(long = "output-format", value_enum, value_delimiter = ',', default_value = "png")]
+    pub output_format: Vec<OutputFormat>,
+
+    /// Render the frames in parallel.
+    #[arg(long = "parallel", default_value_t = false)]
+    pub parallel: bool,
+
+    /// Scan all frames before rendering, such that the `global` ranges
+    /// (`--plot-range`) span all frames from the first one on.
+    #[arg(long = "two-pass", default_value_t = false)]
+    pub two_pass: bool,
+
+    #[arg(long = "show-progress", default_value_t = false)]
+    pub show_progress: bool,
+}
+
+#[derive(Parser, Debug)]
+pub struct CLIArgs {
+    #[command(flatten)]
+    pub plot: PlotArgs,
+
+    /// The output formats of each frame (comma-separated).
+    #[arg(long = "output-format", value_enum, value_delimiter = ',', default_value = "png")]
+    pub output_format: Vec<OutputFormat>,
+
+    #[arg(long = "vti-output", default_value_t = String::from("./frames/fields-{}.vti"))]
+    pub vti_output: String,
+
+    /// Output pattern for `npy` and `npz` (without extension).
+    #[arg(long = "npy-output", default_value_t = String::from("./frames/fields-{}"))]
+    pub npy_output: String,
+
+    /// Also export the raw staggered velocity faces into the `.vti` files.
+    #[arg(long = "vti-raw-faces", default_value_t = false)]
+    pub vti_raw_faces: bool,
+
     #[arg(short = 'e', long = "time-end", default_value_t = 5.0)]
     pub time_end: Scalar,
 
@@ -47,9 +322,6 @@ pub struct CLIArgs {
     #[arg(long = "dim", default_value = "200, 100", value_parser = parse_vector::<usize, 2>)]
     pub dim: Index2,
 
-    #[arg(long = "plot-dim", default_value = "1600, 800", value_parser = parse_vector::<usize, 2>)]
-    pub plot_dim: Index2,
-
     #[arg(short = 'g', long = "gravity", default_value = "0.0, 9.81",  value_parser = parse_vector::<Scalar, 2>)]
     pub gravity: Vector2,
 
@@ -59,47 +331,163 @@ pub struct CLIArgs {
     #[arg(long = "scene-index", default_value_t = 0)]
     pub scene_idx: usize,
 
-    #[arg(long = "plot-pressure", default_value_t = false)]
-    pub plot_pressure: bool,
-
-    #[arg(long = "plot-velocity", default_value_t = false)]
-    pub plot_velocity: bool,
-
-    #[arg(long = "plot-masked-pressure", default_value_t = false)]
-    pub plot_masked_pressure: bool,
-
-    #[arg(long = "plot-masked-velocity", default_value_t = false)]
-    pub plot_masked_velocity: bool,
-
-    #[arg(long = "plot-stats", default_value_t = false)]
-    pub plot_stats: bool,
-
     #[arg(long = "parallel", default_value_t = false)]
     pub parallel: bool,
 
     #[arg(long = "parallel-unsafe", default_value_t = false)]
     pub parallel_unsafe: bool,
 
+    #[arg(long = "parallel-tiled", default_value_t = false)]
+    pub parallel_tiled: bool,
+
+    /// Tile size for `--parallel-tiled`.
+    #[arg(long = "tile-size", default_value = "64, 64", value_parser = parse_vector::<usize, 2>)]
+    pub tile_size: Index2,
+
+    /// Solver iterations per tile for `--parallel-tiled` (temporal blocking).
+    #[arg(long = "tile-sweeps", default_value_t = 4)]
+    pub tile_sweeps: u64,
+
+    /// Reduce in a fixed order such that the results are bit-identical
+    /// for any number of threads.
+    #[arg(long = "deterministic", default_value_t = false)]
+    pub deterministic: bool,
+
+    /// Record the statistics of each step (min./max. pressure, divergence and
+    /// velocity, total smoke, kinetic energy and divergence residual).
+    #[arg(long = "record-stats", default_value_t = false)]
+    pub record_stats: bool,
+
+    /// Output of the recorded statistics, written as `.csv` and `.png` (without extension).
+    #[arg(long = "stats-output", default_value_t = String::from("./stats"))]
+    pub stats_output: String,
+
+    /// Record the pressure force and the drag and lift coefficients of each
+    /// obstacle every step.
+    #[arg(long = "record-forces", default_value_t = false)]
+    pub record_forces: bool,
+
+    /// Output of the recorded forces, written as `.csv` and `.png` (without extension).
+    #[arg(long = "forces-output", default_value_t = String::from("./forces"))]
+    pub forces_output: String,
+
+    /// Record the transverse velocity at a probe behind the obstacle and report the
+    /// vortex shedding frequency and the Strouhal number at the end.
+    #[arg(long = "shedding-analysis", default_value_t = false)]
+    pub shedding_analysis: bool,
+
+    /// The probe of the shedding analysis in world coordinates `x,y`
+    /// (default: three diameters behind the obstacle center).
+    #[arg(long = "shedding-probe", value_parser = parse_vector::<Scalar, 2>)]
+    pub shedding_probe: Option<Vector2>,
+
+    /// Output of the shedding analysis, written as `.csv` (probe signal) and
+    /// `.json` (summary) (without extension).
+    #[arg(long = "shedding-output", default_value_t = String::from("./shedding"))]
+    pub shedding_output: String,
+
+    /// Point probes in world coordinates `x,y` (separated by `;`), sampled every
+    /// step (velocity, pressure and smoke).
+    #[arg(long = "probe-points", value_delimiter = ';', value_parser = parse_vector::<Scalar, 2>)]
+    pub probe_points: Vec<Vector2>,
+
+    /// Line probes in world coordinates `x0,y0,x1,y1` (separated by `;`).
+    #[arg(long = "probe-lines", value_delimiter = ';', value_parser = parse_vector::<Scalar, 4>)]
+    pub probe_lines: Vec<na::Vector4<Scalar>>,
+
+    /// The number of samples along the line probes.
+    #[arg(long = "probe-line-samples", default_value_t = 50)]
+    pub probe_line_samples: usize,
+
+    /// JSON file with probes, e.g.
+    /// `{"points": [[0.5, 0.5]], "lines": [{"start": [1.0, 0.0], "end": [1.0, 1.0], "samples": 20}]}`.
+    #[arg(long = "probes-file")]
+    pub probes_file: Option<String>,
+
+    /// The times of the profile plots of the line probes (comma-separated).
+    #[arg(long = "probe-profile-times", value_delimiter = ',')]
+    pub probe_profile_times: Vec<Scalar>,
+
+    /// Output of the probes, written as `<output>-points.csv`, `<output>-line-<k>.csv`
+    /// and the profile plots `<output>-line-<k>.png`.
+    #[arg(long = "probes-output", default_value_t = String::from("./probes"))]
+    pub probes_output: String,
+
+    /// Write a checkpoint every `N` steps.
+    #[arg(long = "checkpoint-every")]
+    pub checkpoint_every: Option<u64>,
+
+    #[arg(long = "checkpoint-output", default_value_t = String::from("./checkpoints/checkpoint-{}.bin"))]
+    pub checkpoint_output: String,
+
+    /// Seed passive tracer particles (drawn on the smoke plot).
+    #[arg(long = "tracers", value_enum)]
+    pub tracers: Option<TracerSeeds>,
+
+    /// The spacing of the tracer seeds on the lattice or along the inflow (in cells).
+    #[arg(long = "tracer-spacing", default_value_t = 8)]
+    pub tracer_spacing: usize,
+
+    /// The tracer seeds in world coordinates `x,y` (separated by `;`).
+    #[arg(long = "tracer-points", value_delimiter = ';', value_parser = parse_vector::<Scalar, 2>)]
+    pub tracer_points: Vec<Vector2>,
+
+    /// Seed new tracers every `N` steps (`0`: only at the start).
+    #[arg(long = "tracer-emit-every", default_value_t = 0)]
+    pub tracer_emit_every: u64,
+
+    /// The length of the tracer trails (in steps, `0`: dots only).
+    #[arg(long = "tracer-trail", default_value_t = 0)]
+    pub tracer_trail: usize,
+
+    /// Restart the simulation from a checkpoint file.
+    #[arg(long = "restart-from")]
+    pub restart_from: Option<String>,
+
+    /// Draw a live preview into the terminal every `N` steps (instead of the progress bar).
+    #[arg(long = "preview-every")]
+    pub preview_every: Option<u64>,
+
+    /// The plot which is previewed.
+    #[arg(long = "preview-plot", default_value = "smoke", value_parser = ["smoke", "vel", "press", "vort", "div", "lic"])]
+    pub preview_plot: String,
+
+    /// The size of the preview in characters `columns,rows`
+    /// (default: `COLUMNS` and `LINES` or `80x24`).
+    #[arg(long = "preview-size", value_parser = parse_vector::<usize, 2>)]
+    pub preview_size: Option<Index2>,
+
     #[arg(long = "show-progress", default_value_t = false)]
     pub show_progress: bool,
 }
 
-pub fn parse_args() -> CLIArgs {
-    return CLIArgs::parse();
+pub fn parse_args() -> Cli {
+    return Cli::parse();
+}
+
+/// The builder of the thread pool with `--threads` threads (by default one per core),
+/// e.g. for the global pool.
+pub fn thread_pool_builder(cli: &Cli) -> rayon::ThreadPoolBuilder {
+    let mut builder = rayon::ThreadPoolBuilder::new();
+    if let Some(threads) = cli.threads {
+        builder = builder.num_threads(threads);
+    }
+    return builder;
 }
 
+#[derive(Serialize, Deserialize)]
 struct AddSmokeBar {
     pub center: Index2,
     pub height: usize,
 }
 
-impl Manipulator for AddSmokeBar {
+impl<T: Float> Manipulator<T> for AddSmokeBar {
     fn manipulate(
         &self,
         log: &Logger,
-        t: Scalar,
-        dt: Scalar,
-        objects: &mut Vec<Box<dyn Integrate>>,
+        t: T,
+        dt: T,
+        objects: &mut Vec<Box<dyn Integrate<T>>>,
     ) {
         debug!(log, "Add smoke at {}, {}", t, dt);
 
@@ -108,7 +496,7 @@ impl Manipulator for AddSmokeBar {
             .expect("No objects.")
             .as_mut()
             .as_any_mut()
-            .downcast_mut::<Grid>()
+            .downcast_mut::<Grid<T>>()
             .expect("");
 
         // Setup smoke on border.
@@ -120,20 +508,37 @@ impl Manipulator for AddSmokeBar {
         (y_range[0]..y_range[1]).for_each(|y| {
             let idx = idx!(0, y);
             if let Some(cell) = grid.cell_mut_opt(idx) {
-                cell.smoke.back = 1.0;
+                *cell.smoke = T::one();
             }
         });
     }
+
+    fn save_state(&self) -> GenericResult<Vec<u8>> {
+        return Ok(bincode::serialize(self)?);
+    }
+
+    fn load_state(&mut self, state: &[u8]) -> GenericResult<()> {
+        *self = bincode::deserialize(state)?;
+        return Ok(());
+    }
+}
+
+/// The velocity of the inflow on the left border.
+pub fn inflow_velocity<T: Float>() -> Vector2T<T> {
+    return vec2!(to_float::<T>(2.0), T::zero());
 }
 
-pub fn setup_scene<'t>(log: &'t Logger, cli: &'t CLIArgs) -> SimpleResult<Box<TimeStepper<'t>>> {
-    let velocity_in = vec2!(2.0, 0.0);
-    let height = 1.0;
-    let cell_width = height / cli.dim.y as Scalar;
-    let width = cli.dim.x as Scalar * cell_width;
+pub fn setup_scene<'t, T: Float>(
+    log: &'t Logger,
+    cli: &'t CLIArgs,
+) -> SimpleResult<Box<TimeStepper<'t, T>>> {
+    let velocity_in = inflow_velocity::<T>();
+    let height = T::one();
+    let cell_width = height / to_float(cli.dim.y);
+    let width = to_float::<T>(cli.dim.x) * cell_width;
 
     let obstacle_size_rel = 0.3;
-    let obstacle_size = obstacle_size_rel * height;
+    let obstacle_size = to_float::<T>(obstacle_size_rel) * height;
 
     info!(
         log,
@@ -146,6 +551,8 @@ pub fn setup_scene<'t>(log: &'t Logger, cli: &'t CLIArgs) -> SimpleResult<Box<Ti
     );
 
     let mut grid = Box::new(Grid::new(cli.dim, cell_width));
+    grid.deterministic = cli.deterministic;
+    grid.with_div_residual = cli.record_stats;
 
     if cli.scene_idx == 0 {
         for idx in grid.iter_index() {
@@ -153,11 +560,11 @@ pub fn setup_scene<'t>(log: &'t Logger, cli: &'t CLIArgs) -> SimpleResult<Box<Ti
 
             // Set walls.
             if idx.x == 0 || idx.y == 0 || idx.y == grid.dim.y - 1 {
-                grid.cell_mut(idx).mode = CellTypes::Solid;
+                *grid.cell_mut(idx).mode = CellTypes::Solid;
             }
 
             if is_inside && idx.x == 1 {
-                grid.cell_mut(idx).velocity.back = velocity_in;
+                grid.cell_mut(idx).set_velocity(velocity_in);
             }
         }
     } else {
@@ -165,14 +572,14 @@ pub fn setup_scene<'t>(log: &'t Logger, cli: &'t CLIArgs) -> SimpleResult<Box<Ti
     }
 
     let grav = if cli.scene_idx == 0 {
-        Vector2::zeros()
+        Vector2T::zeros()
     } else {
-        cli.gravity
+        cli.gravity.map(|g| to_float(g))
     };
 
     // Setup obstacle.
-    let p = vec2!(width * 0.25, height * 0.5);
-    grid.set_obstacle(p, obstacle_size / 2.0, None);
+    let p = vec2!(width * to_float(0.25), height * to_float(0.5));
+    grid.set_obstacle(p, obstacle_size / to_float(2.0), None);
 
     // Set manipulator (for smoke).
     let smoke_adder = Box::new(AddSmokeBar {
@@ -180,20 +587,51 @@ pub fn setup_scene<'t>(log: &'t Logger, cli: &'t CLIArgs) -> SimpleResult<Box<Ti
         height: (1.1 * obstacle_size_rel * grid.dim.y as Scalar) as usize,
     });
 
-    let manips: Vec<Box<dyn Manipulator>> = vec![smoke_adder];
-    let objs: Vec<Box<dyn Integrate>> = vec![grid];
+    // The tracer seeds in world coordinates.
+    let to_world = |seeds: Seeds| -> Vec<_> {
+        return seed_points(&grid, seeds)
+            .into_iter()
+            .map(|p| p * cell_width)
+            .collect();
+    };
+    let tracer_seeds = match cli.tracers {
+        Some(TracerSeeds::Lattice) => Some(to_world(Seeds::Lattice(cli.tracer_spacing))),
+        Some(TracerSeeds::Inflow) => Some(to_world(Seeds::Inflow(cli.tracer_spacing))),
+        Some(TracerSeeds::Points) => Some(
+            cli.tracer_points
+                .iter()
+                .map(|p| p.map(|v| to_float::<T>(v)))
+                .collect(),
+        ),
+        None => None,
+    };
 
+    let manips: Vec<Box<dyn Manipulator<T>>> = vec![smoke_adder];
+    let mut objs: Vec<Box<dyn Integrate<T>>> = vec![grid];
+
+    if let Some(seeds) = tracer_seeds {
+        objs.push(Box::new(Tracers::new(
+            seeds,
+            cli.tracer_emit_every,
+            cli.tracer_trail,
+        )));
+    }
 
     let exec_mode = if cli.parallel {
             ExecutionMode::Parallel
         } else if cli.parallel_unsafe {
             ExecutionMode::ParallelUnsafe
+        } else if cli.parallel_tiled {
+            ExecutionMode::ParallelTiled {
+                tile_size: cli.tile_size,
+                sweeps: cli.tile_sweeps,
+            }
         } else {
             ExecutionMode::Single
         };
     let timestepper = Box::new(TimeStepper::new(
         &log,
-        cli.density,
+        to_float(cli.density),
         grav,
         cli.incompress_iter,
         exec_mode,
@@ -0,0 +1,173 @@
+use crate::scene::cell::CellTypes;
+use crate::scene::grid::{CellGetter, Grid};
+use crate::types::*;
+use rayon::prelude::*;
+
+/// The seed points of the streamlines.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Seeds {
+    /// On a lattice with this spacing (in cells).
+    Lattice(usize),
+    /// Along the inflow (the first inside column) with this spacing (in cells).
+    Inflow(usize),
+}
+
+/// Is the position `pos` (in cell coordinates) inside a fluid cell.
+pub(crate) fn is_fluid<T: Float>(grid: &Grid<T>, pos: Vector2T<T>) -> bool {
+    if pos.iter().any(|v| *v < T::one()) {
+        return false;
+    }
+
+    let idx = pos.map(|v| v.to_usize().unwrap_or(0));
+    return grid.is_inside_border(idx) && grid.cell(idx).mode() != CellTypes::Solid;
+}
+
+/// The normalized velocity direction at `pos` (in cell coordinates).
+fn direction<T: Float>(grid: &Grid<T>, pos: Vector2T<T>) -> Option<Vector2T<T>> {
+    let v = grid.sample_velocity(pos * grid.cell_width);
+    let norm = v.norm();
+
+    if norm <= T::epsilon() {
+        return None;
+    }
+
+    return Some(v / norm);
+}
+
+/// The seed points (in cell coordinates) in fluid cells.
+pub fn seed_points<T: Float>(grid: &Grid<T>, seeds: Seeds) -> Vec<Vector2T<T>> {
+    let half = to_float::<T>(0.5);
+    let center = |i: usize, j: usize| vec2!(to_float::<T>(i) + half, to_float::<T>(j) + half);
+
+    let points: Vec<_> = match seeds {
+        Seeds::Lattice(spacing) => {
+            let s = spacing.max(1);
+            (s / 2..grid.dim.y)
+                .step_by(s)
+                .flat_map(|j| (s / 2..grid.dim.x).step_by(s).map(move |i| center(i, j)))
+                .collect()
+        }
+        Seeds::Inflow(spacing) => (0..grid.dim.y)
+            .step_by(spacing.max(1))
+            .map(|j| center(1, j))
+            .filter(|p| direction(grid, *p).map_or(false, |d| d.x > T::zero()))
+            .collect(),
+    };
+
+    return points.into_iter().filter(|p| is_fluid(grid, *p)).collect();
+}
+
+/// Trace the streamline from `start` (in cell coordinates) with the classical
+/// Runge-Kutta method (RK4) along the normalized velocity with step length `step`
+/// (in cells) until it leaves the fluid, stagnates or `max_steps` are reached.
+/// With `backward` the streamline is traced against the flow.
+pub fn trace_streamline<T: Float>(
+    grid: &Grid<T>,
+    start: Vector2T<T>,
+    step: T,
+    max_steps: usize,
+    backward: bool,
+) -> Vec<Vector2T<T>> {
+    let h = if backward { -step } else { step };
+    let two = to_float::<T>(2.0);
+    let six = to_float::<T>(6.0);
+
+    let mut line = vec![start];
+    let mut p = start;
+
+    for _ in 0..max_steps {
+        let next = (|| {
+            let k1 = direction(grid, p)?;
+            let k2 = direction(grid, p + k1 * (h / two))?;
+            let k3 = direction(grid, p + k2 * (h / two))?;
+            let k4 = direction(grid, p + k3 * h)?;
+            return Some(p + (k1 + k2 * two + k3 * two + k4) * (h / six));
+        })();
+
+        match next {
+            Some(n) if is_fluid(grid, n) => {
+                p = n;
+                line.push(p);
+            }
+            _ => break,
+        }
+    }
+
+    return line;
+}
+
+/// Trace the streamlines (in cell coordinates) from the seeds. Lattice seeds
+/// are traced in both directions, inflow seeds only downstream.
+pub fn streamlines<T: Float>(
+    grid: &Grid<T>,
+    seeds: Seeds,
+    step: T,
+    max_steps: usize,
+) -> Vec<Vec<Vector2T<T>>> {
+    return seed_points(grid, seeds)
+        .into_par_iter()
+        .map(|p| {
+            let mut line = trace_streamline(grid, p, step, max_steps, false);
+
+            if let Seeds::Lattice(_) = seeds {
+                let mut back = trace_streamline(grid, p, step, max_steps, true);
+                back.reverse();
+                back.pop(); // The seed is in both.
+                back.append(&mut line);
+                line = back;
+            }
+
+            return line;
+        })
+        .filter(|l| l.len() > 1)
+        .collect();
+}
+
+/// A white noise value in `[0, 1)` for the cell with linear index `i` (SplitMix64).
+fn noise(i: usize) -> f64 {
+    let mut z = (i as u64).wrapping_add(0x9e3779b97f4a7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+    z ^= z >> 31;
+
+    return (z >> 11) as f64 / (1u64 << 53) as f64;
+}
+
+/// The line integral convolution (LIC) of a white noise texture along the
+/// velocity field with a box kernel of `length` cells in each direction.
+/// Returns the intensity in `[0, 1]` for each cell (row-major), `0` for solid cells.
+pub fn lic<T: Float>(grid: &Grid<T>, length: usize) -> Vec<f64> {
+    let step = to_float::<T>(0.5);
+    let max_steps = 2 * length;
+    let half = to_float::<T>(0.5);
+
+    return (0..grid.dim.x * grid.dim.y)
+        .into_par_iter()
+        .map(|k| {
+            let center = vec2!(
+                to_float::<T>(k % grid.dim.x) + half,
+                to_float::<T>(k / grid.dim.x) + half
+            );
+            if !is_fluid(grid, center) {
+                return 0.0;
+            }
+
+            let forward = trace_streamline(grid, center, step, max_steps, false);
+            let backward = trace_streamline(grid, center, step, max_steps, true);
+
+            let samples = forward.iter().chain(backward.iter().skip(1));
+            let n = forward.len() + backward.len() - 1;
+
+            let sum: f64 = samples
+                .map(|p| {
+                    let idx = p.map(|v| v.to_usize().unwrap_or(0));
+                    return noise(idx.x + idx.y * grid.dim.x);
+                })
+                .sum();
+
+            // Stretch the contrast which decreases with the number of samples.
+            let avg = sum / n as f64;
+            return (0.5 + (avg - 0.5) * 0.7 * (n as f64).sqrt()).clamp(0.0, 1.0);
+        })
+        .collect();
+}
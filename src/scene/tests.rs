@@ -2,9 +2,13 @@
 mod tests {
 
     use crate::log::*;
+    use crate::scene::checkpoint::{load_checkpoint, save_checkpoint};
     use crate::scene::grid::*;
     use crate::scene::cell::*;
+    use crate::scene::setup::{setup_scene, CLIArgs};
+    use crate::scene::timestepper::{ExecutionMode, Integrate};
     use crate::types::*;
+    use clap::Parser;
     use float_cmp::approx_eq;
 
     #[test]
@@ -12,8 +16,8 @@ mod tests {
         let (log, _) = create_logger();
         let mut grid = Grid::new(dim!(10, 10), 1.0);
 
-        let sample_back_vel = |cell: &Cell| {
-            let v = cell.velocity.back[1];
+        let sample_back_vel = |cell: CellRef| {
+            let v = cell.velocity()[1];
             debug!(log, "Val {}", v);
             return v;
         };
@@ -24,10 +28,10 @@ mod tests {
         //   |- 1 -|- 2 -|
         //   0 ----1---->2
 
-        grid.cell_mut(idx!(0, 0)).velocity.back = vec2!(-1.0, 1.0);
-        grid.cell_mut(idx!(1, 0)).velocity.back = vec2!(-1.0, 2.0);
-        grid.cell_mut(idx!(0, 1)).velocity.back = vec2!(-1.0, 3.0);
-        grid.cell_mut(idx!(1, 1)).velocity.back = vec2!(-1.0, 4.0);
+        grid.cell_mut(idx!(0, 0)).set_velocity(vec2!(-1.0, 1.0));
+        grid.cell_mut(idx!(1, 0)).set_velocity(vec2!(-1.0, 2.0));
+        grid.cell_mut(idx!(0, 1)).set_velocity(vec2!(-1.0, 3.0));
+        grid.cell_mut(idx!(1, 1)).set_velocity(vec2!(-1.0, 4.0));
 
         let min = idx!(0, 0);
         let max = grid.dim;
@@ -59,4 +63,628 @@ mod tests {
         assert!(approx_eq!(Scalar, val, 0.0, epsilon = 1e-6), "Val: {}", val);
     }
 
+    #[test]
+    fn check_f32_against_f64() {
+        let (log, switch) = create_logger();
+        switch.disable();
+
+        let cli = CLIArgs::parse_from(["rsfluid", "--dim", "80,40", "--incompress-iters", "40"]);
+        let mut ts_32 = setup_scene::<f32>(&log, &cli).unwrap();
+        let mut ts_64 = setup_scene::<f64>(&log, &cli).unwrap();
+
+        for _ in 0..20 {
+            ts_32.compute_step(to_float(cli.dt));
+            ts_64.compute_step(to_float(cli.dt));
+        }
+
+        let grid_32 = ts_32.objects[0].as_any().downcast_ref::<Grid<f32>>().unwrap();
+        let grid_64 = ts_64.objects[0].as_any().downcast_ref::<Grid<f64>>().unwrap();
+
+        let v_max = grid_64.stats[1].velocity_norm;
+
+        for idx in grid_64.iter_index() {
+            let (c_32, c_64) = (grid_32.cell(idx), grid_64.cell(idx));
+
+            let dv = (c_32.velocity().cast::<f64>() - c_64.velocity()).norm();
+            assert!(dv <= 1e-3 * v_max, "Velocity differs at {}: {}", idx, dv);
+
+            let ds = (c_32.smoke() as f64 - c_64.smoke()).abs();
+            assert!(ds <= 1e-3, "Smoke differs at {}: {}", idx, ds);
+        }
+    }
+
+    #[test]
+    fn check_parallel_advection_against_sequential() {
+        let (log, switch) = create_logger();
+        switch.disable();
+
+        let cli = CLIArgs::parse_from(["rsfluid", "--dim", "80,40", "--incompress-iters", "40"]);
+
+        for mode in [ExecutionMode::Parallel, ExecutionMode::ParallelUnsafe] {
+            let mut ts_seq = setup_scene::<Scalar>(&log, &cli).unwrap();
+            let mut ts_par = setup_scene::<Scalar>(&log, &cli).unwrap();
+
+            // Develop some flow (sequentially) to advect.
+            for _ in 0..10 {
+                ts_seq.compute_step(cli.dt);
+                ts_par.compute_step(cli.dt);
+            }
+
+            let grid_seq = ts_seq.objects[0].as_any_mut().downcast_mut::<Grid>().unwrap();
+            let grid_par = ts_par.objects[0].as_any_mut().downcast_mut::<Grid>().unwrap();
+
+            grid_seq.integrate(&log, cli.dt, cli.gravity, ExecutionMode::Single);
+            grid_par.integrate(&log, cli.dt, cli.gravity, mode);
+
+            for dir in 0..2 {
+                assert!(
+                    grid_seq.fields().velocity[dir].back == grid_par.fields().velocity[dir].back,
+                    "Border extrapolation differs for mode {:?}.",
+                    mode
+                );
+            }
+
+            grid_seq.advect(&log, cli.dt, ExecutionMode::Single);
+            grid_par.advect(&log, cli.dt, mode);
+
+            for dir in 0..2 {
+                assert!(
+                    grid_seq.fields().velocity[dir].back == grid_par.fields().velocity[dir].back,
+                    "Velocity advection differs for mode {:?}.",
+                    mode
+                );
+            }
+
+            assert!(
+                grid_seq.fields().smoke.back == grid_par.fields().smoke.back,
+                "Smoke advection differs for mode {:?}.",
+                mode
+            );
+        }
+    }
+
+    #[test]
+    fn check_parallel_solver_odd_dimensions() {
+        let (log, switch) = create_logger();
+        switch.disable();
+
+        // Iterate until convergence, since the red-black ordering
+        // of the parallel solver differs from the sequential one.
+        let iterations = 2000;
+
+        for dim in ["41,21", "40,21", "41,20"] {
+            let cli = CLIArgs::parse_from(["rsfluid", "--dim", dim]);
+
+            let tiled = ExecutionMode::ParallelTiled {
+                tile_size: idx!(8, 6),
+                sweeps: 3,
+            };
+
+            for mode in [ExecutionMode::Parallel, ExecutionMode::ParallelUnsafe, tiled] {
+                let mut ts_seq = setup_scene::<Scalar>(&log, &cli).unwrap();
+                let mut ts_par = setup_scene::<Scalar>(&log, &cli).unwrap();
+
+                for _ in 0..5 {
+                    ts_seq.compute_step(cli.dt);
+                    ts_par.compute_step(cli.dt);
+                }
+
+                let grid_seq = ts_seq.objects[0].as_any_mut().downcast_mut::<Grid>().unwrap();
+                let grid_par = ts_par.objects[0].as_any_mut().downcast_mut::<Grid>().unwrap();
+
+                grid_seq.solve_incompressibility(
+                    &log,
+                    cli.dt,
+                    iterations,
+                    cli.density,
+                    ExecutionMode::Single,
+                );
+                grid_par.solve_incompressibility(&log, cli.dt, iterations, cli.density, mode);
+
+                let v_max = grid_seq.stats[1].velocity_norm;
+                let tol = (1e-6 as Scalar).max(1e3 * Scalar::EPSILON);
+
+                for idx in grid_seq.iter_index_inside() {
+                    let dv = (grid_seq.cell(idx).velocity() - grid_par.cell(idx).velocity()).norm();
+                    assert!(
+                        dv <= tol * v_max,
+                        "Velocity differs at {} for dim {} and mode {:?}: {}",
+                        idx,
+                        dim,
+                        mode,
+                        dv
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn check_deterministic_thread_counts() {
+        let (log, switch) = create_logger();
+        switch.disable();
+
+        for mode in ["--parallel", "--parallel-unsafe"] {
+            let mut checksums = vec![];
+
+            for threads in [1, 2, 8] {
+                let cli = CLIArgs::parse_from([
+                    "rsfluid",
+                    "--dim",
+                    "80,40",
+                    "--incompress-iters",
+                    "40",
+                    "--scene-index",
+                    "0",
+                    "--deterministic",
+                    mode,
+                ]);
+
+                let pool = rayon::ThreadPoolBuilder::new()
+                    .num_threads(threads)
+                    .build()
+                    .unwrap();
+
+                let checksum = pool.install(|| {
+                    let mut timestepper = setup_scene::<Scalar>(&log, &cli).unwrap();
+                    for _ in 0..20 {
+                        timestepper.compute_step(cli.dt);
+                    }
+
+                    let grid = timestepper.objects[0].as_any().downcast_ref::<Grid>().unwrap();
+                    return (grid.fields().checksum(), format!("{:?}", grid.stats));
+                });
+
+                checksums.push(checksum);
+            }
+
+            assert!(
+                checksums.iter().all(|c| *c == checksums[0]),
+                "Checksums differ for {}: {:?}",
+                mode,
+                checksums
+            );
+        }
+    }
+
+    #[test]
+    fn check_threads_option() {
+        use crate::scene::setup::{thread_pool_builder, Cli};
+
+        // The option is global, before and after the subcommand.
+        for args in [
+            ["rsfluid", "--threads", "3", "simulate"],
+            ["rsfluid", "simulate", "--threads", "3"],
+            ["rsfluid", "render", "--threads", "3"],
+        ] {
+            let cli = Cli::parse_from(args);
+            let pool = thread_pool_builder(&cli).build().unwrap();
+            assert!(pool.current_num_threads() == 3, "Args: {:?}", args);
+        }
+
+        let cli = Cli::parse_from(["rsfluid", "simulate"]);
+        let pool = thread_pool_builder(&cli).build().unwrap();
+        assert!(pool.current_num_threads() == rayon::current_num_threads());
+    }
+
+    #[test]
+    fn check_tiled_single_tile_against_parallel() {
+        let (log, switch) = create_logger();
+        switch.disable();
+
+        // One tile over the whole domain with one sweep is the same
+        // red-black ordering as the parallel solver.
+        let cli = CLIArgs::parse_from(["rsfluid", "--dim", "41,20", "--incompress-iters", "40"]);
+        let tiled = ExecutionMode::ParallelTiled {
+            tile_size: cli.dim,
+            sweeps: 1,
+        };
+
+        let mut ts_par = setup_scene::<Scalar>(&log, &cli).unwrap();
+        let mut ts_tiled = setup_scene::<Scalar>(&log, &cli).unwrap();
+
+        for _ in 0..5 {
+            ts_par.compute_step(cli.dt);
+            ts_tiled.compute_step(cli.dt);
+        }
+
+        let grid_par = ts_par.objects[0].as_any_mut().downcast_mut::<Grid>().unwrap();
+        let grid_tiled = ts_tiled.objects[0].as_any_mut().downcast_mut::<Grid>().unwrap();
+
+        grid_par.solve_incompressibility(&log, cli.dt, 40, cli.density, ExecutionMode::Parallel);
+        grid_tiled.solve_incompressibility(&log, cli.dt, 40, cli.density, tiled);
+
+        assert!(
+            grid_par.fields().checksum() == grid_tiled.fields().checksum(),
+            "Tiled solver differs from parallel solver."
+        );
+    }
+
+    #[test]
+    fn check_restart_from_checkpoint() {
+        let (log, switch) = create_logger();
+        switch.disable();
+
+        let cli = CLIArgs::parse_from(["rsfluid", "--dim", "80,40", "--incompress-iters", "40"]);
+        let file = std::env::temp_dir().join(format!("rsfluid-checkpoint-{}.bin", std::process::id()));
+        let file = file.to_str().unwrap();
+
+        // Uninterrupted run.
+        let mut ts = setup_scene::<Scalar>(&log, &cli).unwrap();
+        for _ in 0..20 {
+            ts.compute_step(cli.dt);
+        }
+
+        // Interrupted run with a restart at step 10.
+        let mut ts_before = setup_scene::<Scalar>(&log, &cli).unwrap();
+        for _ in 0..10 {
+            ts_before.compute_step(cli.dt);
+        }
+        save_checkpoint(file, 10, &ts_before).unwrap();
+
+        let mut ts_restart = setup_scene::<Scalar>(&log, &cli).unwrap();
+        let step = load_checkpoint(file, &mut ts_restart).unwrap();
+        std::fs::remove_file(file).unwrap();
+        assert!(step == 10);
+
+        for _ in step..20 {
+            ts_restart.compute_step(cli.dt);
+        }
+
+        assert!(ts.t().to_bits() == ts_restart.t().to_bits());
+
+        let grid = ts.objects[0].as_any().downcast_ref::<Grid>().unwrap();
+        let grid_restart = ts_restart.objects[0].as_any().downcast_ref::<Grid>().unwrap();
+
+        assert!(
+            grid.fields().checksum() == grid_restart.fields().checksum(),
+            "Restarted run differs from the uninterrupted run."
+        );
+        assert!(format!("{:?}", grid.stats) == format!("{:?}", grid_restart.stats));
+    }
+
+    #[test]
+    fn check_quiver_overlay() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::scene::visualization::{render_grid_plots, PlotParamsBuilder};
+
+        let mut grid = Grid::new(dim!(20, 10), 0.1);
+        for idx in grid.iter_index().collect::<Vec<_>>() {
+            grid.cell_mut(idx).set_velocity(vec2!(1.0, 0.5));
+        }
+        grid.stats[1].velocity_norm = Vector2::new(1.0, 0.5).norm();
+
+        let v = grid.sample_velocity(vec2!(0.55, 0.45));
+        assert!(approx_eq!(Scalar, v.x, 1.0, ulps = 10) && approx_eq!(Scalar, v.y, 0.5, ulps = 10));
+
+        let mut builder = PlotParamsBuilder::default();
+        builder.size(idx!(220, 120));
+        let plain = render_grid_plots(&grid, 0, &builder.build()?)?;
+        let quiver = render_grid_plots(&grid, 0, &builder.quiver(Some(4)).build()?)?;
+
+        assert!(plain[0].0 == "smoke" && quiver[0].0 == "smoke");
+        assert!(plain[0].1.data.iter().all(|c| *c == 0));
+        assert!(quiver[0].1.data.iter().any(|c| *c > 0));
+
+        return Ok(());
+    }
+
+    #[test]
+    fn check_streamlines() {
+        use crate::scene::streamlines::*;
+
+        let mut grid = Grid::new(dim!(20, 10), 0.1);
+        for idx in grid.iter_index().collect::<Vec<_>>() {
+            grid.cell_mut(idx).set_velocity(vec2!(1.0, 0.0));
+        }
+        *grid.cell_mut(idx!(15, 5)).mode = CellTypes::Solid;
+
+        // Straight downstream until the solid cell.
+        let line = trace_streamline(&grid, vec2!(2.5, 5.5), 0.5, 100, false);
+        assert!(line.iter().all(|p| approx_eq!(Scalar, p.y, 5.5, ulps = 10)));
+        assert!(line.last().unwrap().x < 15.0 && line.last().unwrap().x >= 14.5);
+
+        // Upstream until the border.
+        let line = trace_streamline(&grid, vec2!(2.5, 5.5), 0.5, 100, true);
+        assert!(line.last().unwrap().x >= 1.0 && line.last().unwrap().x < 1.5);
+
+        let seeds = seed_points(&grid, Seeds::Inflow(2));
+        assert!(seeds.len() == 5 && seeds.iter().all(|p| p.x == 1.5));
+
+        let lines = streamlines(&grid, Seeds::Lattice(4), 0.5, 100);
+        assert!(!lines.is_empty() && lines.iter().all(|l| l.len() > 1));
+
+        let intensity = lic(&grid, 4);
+        assert!(intensity.len() == grid.dim.x * grid.dim.y);
+        assert!(intensity[15 + 5 * grid.dim.x] == 0.0);
+        assert!(intensity.iter().all(|t| (0.0..=1.0).contains(t)));
+        assert!(intensity.iter().any(|t| *t > 0.0));
+    }
+
+    #[test]
+    fn check_vorticity() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::scene::visualization::{render_grid_plots, PlotParamsBuilder};
+
+        // Solid-body rotation with unit vorticity on the staggered faces.
+        let h = 0.1;
+        let mut grid = Grid::new(dim!(20, 10), h);
+        for idx in grid.iter_index().collect::<Vec<_>>() {
+            let (x, y) = (idx.x as Scalar * h, idx.y as Scalar * h);
+            grid.cell_mut(idx)
+                .set_velocity(vec2!(-0.5 * (y + 0.5 * h), 0.5 * (x + 0.5 * h)));
+        }
+
+        let vorticity = grid.compute_vorticity();
+        for idx in grid.iter_index() {
+            let w = vorticity[idx.x + idx.y * grid.dim.x];
+            if grid.is_inside_border(idx) {
+                let eps = (1e-9 as Scalar).max(10.0 * Scalar::EPSILON);
+                assert!(approx_eq!(Scalar, w, 1.0, epsilon = eps), "Val: {}", w);
+            } else {
+                assert!(w == 0.0);
+            }
+        }
+
+        let mut builder = PlotParamsBuilder::default();
+        builder
+            .size(idx!(220, 120))
+            .with_vorticity(true)
+            .with_divergence(true);
+        let images = render_grid_plots(&grid, 0, &builder.build()?)?;
+
+        assert!(images.iter().any(|(name, _)| *name == "vort"));
+        assert!(images.iter().any(|(name, _)| *name == "div"));
+
+        return Ok(());
+    }
+
+    #[test]
+    fn check_range_tracker_once_per_frame() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::plotting::{Colormap, RangeMode};
+        use crate::scene::visualization::{
+            frame_ranges, render_grid_plots, render_grid_plots_in_ranges, save_rendered_plots,
+            FieldStyle, PlotParamsBuilder,
+        };
+
+        let (log, _) = create_logger();
+        let output = std::env::temp_dir().join("rsfluid-check-range-{}.png");
+        let smoothed = RangeMode::Smoothed {
+            alpha: 0.5,
+            symmetric: false,
+        };
+
+        let params = PlotParamsBuilder::default()
+            .size(idx!(44, 24))
+            .with_velocity(true)
+            .velocity_style(FieldStyle::new(Colormap::Turbo, smoothed))
+            .output(output.to_str().unwrap().to_string())
+            .build()?;
+
+        // Render each frame once for the PNG and e.g. an animation.
+        let mut grid = Grid::new(dim!(20, 10), 0.1);
+        grid.stats[0].velocity_norm = 0.0;
+        for (step, max) in [1.0, 3.0].into_iter().enumerate() {
+            grid.stats[1].velocity_norm = max;
+            let images = render_grid_plots(&grid, step as u64, &params)?;
+            save_rendered_plots(&log, &images, step as u64, &params)?;
+        }
+
+        // Rendering with the ranges of a frame doesn't advance the tracker.
+        let ranges = frame_ranges(&grid, &params);
+        render_grid_plots_in_ranges(&grid, 2, &params, &ranges)?;
+        render_grid_plots_in_ranges(&grid, 2, &params, &ranges)?;
+
+        // The ranges `(0, 1)`, `(0, 2)` and `(0, 2.5)` of one update per frame.
+        assert!(ranges.velocity == Some((0.0, 2.5)));
+        assert!(params.velocity_style.update_range(0.0, 2.5) == (0.0, 2.5));
+
+        for step in 0..2 {
+            for name in ["smoke", "vel"] {
+                let file = params
+                    .output
+                    .replace("{}", &format!("{}-{:06}", name, step));
+                std::fs::remove_file(file)?;
+            }
+        }
+
+        return Ok(());
+    }
+
+    #[test]
+    fn check_velocity_range_plots() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::plotting::{Colormap, RangeMode};
+        use crate::scene::visualization::{
+            frame_ranges, include_global_ranges, FieldStyle, PlotParamsBuilder,
+        };
+
+        let mut grid = Grid::new(dim!(20, 10), 0.1);
+        grid.stats[0].velocity_norm = 0.0;
+        grid.stats[1].velocity_norm = 2.0;
+
+        // Only the colored LIC uses the velocity range, for the scan and the frames.
+        for (lic_colored, expected) in [(false, None), (true, Some((0.0, 2.0)))] {
+            let global = RangeMode::Global { symmetric: false };
+            let params = PlotParamsBuilder::default()
+                .with_lic(true)
+                .lic_colored(lic_colored)
+                .velocity_style(FieldStyle::new(Colormap::Turbo, global))
+                .build()?;
+
+            // Without the scan the tracker starts at the range of the first frame.
+            include_global_ranges(&grid, &params);
+            let range = params.velocity_style.update_range(0.5, 0.5);
+            assert!(range == expected.unwrap_or((0.5, 0.5)));
+            assert!(frame_ranges(&grid, &params).velocity == expected);
+        }
+
+        return Ok(());
+    }
+
+    #[test]
+    fn check_panel_layout() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::scene::visualization::{render_grid_plots, PlotParamsBuilder};
+
+        let mut grid = Grid::new(dim!(20, 10), 0.1);
+        for idx in grid.iter_index().collect::<Vec<_>>() {
+            grid.cell_mut(idx).set_velocity(vec2!(1.0, 0.5));
+        }
+
+        let mut builder = PlotParamsBuilder::default();
+        builder
+            .size(idx!(400, 200))
+            .with_velocity(true)
+            .with_pressure(true)
+            .with_stats(true)
+            .with_colorbar(true);
+
+        let separate = render_grid_plots(&grid, 0, &builder.build()?)?;
+        let names = separate.iter().map(|(name, _)| *name).collect::<Vec<_>>();
+        assert!(names == ["smoke", "vel", "press"]);
+
+        // The pressure stays a separate plot.
+        builder
+            .layout(Some(idx!(2, 1)))
+            .panels(vec!["vel".into(), "smoke".into()]);
+        let images = render_grid_plots(&grid, 0, &builder.build()?)?;
+        let names = images.iter().map(|(name, _)| *name).collect::<Vec<_>>();
+        assert!(names == ["press", "panels"]);
+
+        // Two slots of the velocity panel (half the width with a title and a
+        // colorbar of 100px) below the stats (the grid has a border, `22x12`).
+        let (_, panels) = &images[1];
+        let panel_height = 25 + (200.0 * 12.0 / 22.0) as usize;
+        assert!(panels.size == idx!(2 * (200 + 100), 25 + panel_height));
+
+        builder.panels(vec!["div".into()]);
+        assert!(render_grid_plots(&grid, 0, &builder.build()?).is_err());
+
+        return Ok(());
+    }
+
+    #[test]
+    fn check_div_residual() {
+        let (log, switch) = create_logger();
+        switch.disable();
+
+        // The residual is only computed for the statistics.
+        for record_stats in [false, true] {
+            let mut args = vec!["rsfluid", "--dim", "40,20"];
+            if record_stats {
+                args.push("--record-stats");
+            }
+
+            let cli = CLIArgs::parse_from(args);
+            let mut timestepper = setup_scene::<Scalar>(&log, &cli).unwrap();
+            timestepper.compute_step(cli.dt);
+
+            let grid = timestepper.objects[0].as_any().downcast_ref::<Grid>().unwrap();
+            assert!(grid.with_div_residual == record_stats);
+            assert!((grid.div_residual > 0.0) == record_stats);
+        }
+    }
+
+    #[test]
+    fn check_tracers() {
+        use crate::scene::tracers::*;
+
+        let (log, switch) = create_logger();
+        switch.disable();
+
+        let h = 0.1;
+        let mut grid = Grid::new(dim!(20, 10), h);
+        for idx in grid.iter_index().collect::<Vec<_>>() {
+            grid.cell_mut(idx).set_velocity(vec2!(1.0, 0.0));
+        }
+        *grid.cell_mut(idx!(15, 5)).mode = CellTypes::Solid;
+
+        // Seeded at the start and every 2 steps, the second one is outside.
+        // The positions stay off the cell faces, also in single precision.
+        let seeds = vec![vec2!(13.6 * h, 5.5 * h), vec2!(25.0 * h, 5.5 * h)];
+        let mut tracers = Tracers::new(seeds, 2, 2);
+        let objects: Vec<Box<dyn Integrate>> = vec![Box::new(grid)];
+
+        tracers.advect_passive(&log, 0.5 * h, ExecutionMode::Single, &objects);
+        assert!(tracers.particles.len() == 1);
+        let p = &tracers.particles[0];
+        assert!(approx_eq!(Scalar, p.position.x, 14.1 * h, ulps = 10));
+        assert!(approx_eq!(Scalar, p.position.y, 5.5 * h, ulps = 10));
+        assert!(p.trail == [vec2!(13.6 * h, 5.5 * h)]);
+
+        // The particle enters the solid cell and is removed.
+        tracers.advect_passive(&log, 0.5 * h, ExecutionMode::Single, &objects);
+        assert!(tracers.particles.len() == 1);
+        tracers.advect_passive(&log, 0.5 * h, ExecutionMode::Single, &objects);
+        assert!(tracers.particles.len() == 1 && tracers.particles[0].trail.len() == 1);
+        tracers.advect_passive(&log, h, ExecutionMode::Single, &objects);
+        assert!(tracers.particles.is_empty());
+    }
+
+    #[test]
+    fn check_obstacle_forces() {
+        use crate::scene::forces::*;
+
+        let mut grid = Grid::new(dim!(6, 6), 0.5);
+        for idx in grid.iter_index().collect::<Vec<_>>() {
+            *grid.cell_mut(idx).mode = if grid.is_inside_border(idx) {
+                CellTypes::Fluid
+            } else {
+                CellTypes::Solid
+            };
+            *grid.cell_mut(idx).pressure = idx.x as Scalar;
+        }
+
+        // A cell at the wall and a 2x2 block.
+        for idx in [idx!(1, 1), idx!(3, 3), idx!(4, 3), idx!(3, 4), idx!(4, 4)] {
+            *grid.cell_mut(idx).mode = CellTypes::Solid;
+        }
+
+        let obstacles = find_obstacles(&grid);
+        assert!(obstacles.len() == 2);
+        assert!(obstacles[0].cells == [idx!(1, 1)]);
+        assert!(obstacles[1].cells == [idx!(3, 3), idx!(4, 3), idx!(3, 4), idx!(4, 4)]);
+        assert!(obstacles[1].size(grid.cell_width) == 1.0);
+        assert!(obstacles[1].center(grid.cell_width) == vec2!(2.0, 2.0));
+
+        // The pressure 2 on the left and 5 on the right faces (two each).
+        let force = pressure_force(&grid, &obstacles[1]);
+        assert!(force == vec2!(2.0 * 2.0 * 0.5 - 5.0 * 2.0 * 0.5, 0.0));
+
+        let c = force_coefficients(force, 2.0, 1.0, obstacles[1].size(grid.cell_width));
+        assert!(c == vec2!(-3.0, 0.0));
+    }
+
+    #[test]
+    fn check_probes() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::scene::probes::*;
+
+        let h = 0.25;
+        let mut grid = Grid::new(dim!(8, 4), h);
+        for idx in grid.iter_index().collect::<Vec<_>>() {
+            grid.cell_mut(idx).set_velocity(vec2!(1.0, 0.5));
+            *grid.cell_mut(idx).pressure = (idx.x as Scalar + 0.5) * h;
+            *grid.cell_mut(idx).smoke = 0.5;
+        }
+
+        // The pressure is linear in the cell centers.
+        let sample = sample_probe(&grid, vec2!(0.8, 0.6));
+        assert!(approx_eq!(Scalar, sample.pressure, 0.8, epsilon = 1e-6));
+        assert!(approx_eq!(Scalar, sample.smoke, 0.5, epsilon = 1e-6));
+        assert!(approx_eq!(Scalar, sample.velocity.x, 1.0, epsilon = 1e-6));
+        assert!(approx_eq!(Scalar, sample.velocity.y, 0.5, epsilon = 1e-6));
+
+        let config: ProbeConfig = serde_json::from_str(
+            r#"{"lines": [{"start": [0.0, 0.5], "end": [2.0, 0.5], "samples": 3}, {"start": [0.0, 0.0], "end": [0.0, 1.0]}]}"#,
+        )?;
+        assert!(config.points.is_empty() && !config.is_empty());
+        assert!(config.lines[1].samples == 50);
+        assert!(
+            config.lines[0].points()
+                == [
+                    (0.0, vec2!(0.0, 0.5)),
+                    (1.0, vec2!(1.0, 0.5)),
+                    (2.0, vec2!(2.0, 0.5))
+                ]
+        );
+
+        return Ok(());
+    }
 }
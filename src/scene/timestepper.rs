@@ -1,21 +1,50 @@
-use crate::types::{Scalar, Vector2};
+use crate::types::{bail, Float, GenericResult, Index2, Scalar, Vector2T};
+use serde::{Deserialize, Serialize};
 use slog::{info, Logger};
 use std::any::Any;
 
-pub trait Integrate {
+pub trait Integrate<T: Float = Scalar> {
     fn reset(&mut self, _log: &Logger) {}
-    fn integrate(&mut self, _log: &Logger, _dt: Scalar, _gravity: Vector2) {}
+    fn integrate(
+        &mut self,
+        _log: &Logger,
+        _dt: T,
+        _gravity: Vector2T<T>,
+        _execution_mode: ExecutionMode,
+    ) {
+    }
     fn solve_incompressibility(
         &mut self,
         _log: &Logger,
-        _dt: Scalar,
+        _dt: T,
         _iterations: u64,
-        _density: Scalar,
+        _density: T,
         _parallel: ExecutionMode,
     ) {
     }
 
-    fn advect(&mut self, _log: &Logger, _dt: Scalar) {}
+    fn advect(&mut self, _log: &Logger, _dt: T, _execution_mode: ExecutionMode) {}
+
+    /// Advect with the velocity of the other `objects` (e.g. passive tracers
+    /// in the velocity of a grid), called before `advect` of all objects.
+    fn advect_passive(
+        &mut self,
+        _log: &Logger,
+        _dt: T,
+        _execution_mode: ExecutionMode,
+        _objects: &[Box<dyn Integrate<T>>],
+    ) {
+    }
+
+    /// Serialize the full state (for checkpoints).
+    fn save_state(&self) -> GenericResult<Vec<u8>> {
+        return Ok(vec![]);
+    }
+
+    /// Restore the state serialized with `save_state`.
+    fn load_state(&mut self, _state: &[u8]) -> GenericResult<()> {
+        return Ok(());
+    }
 
     // For downcasting.
     // This can be solved differently and nicer.
@@ -28,46 +57,73 @@ pub trait Integrate {
     fn as_any_mut(&mut self) -> &mut dyn Any;
 }
 
-pub trait Manipulator {
+pub trait Manipulator<T: Float = Scalar> {
     fn manipulate(
         &self,
         log: &Logger,
-        t: Scalar,
-        dt: Scalar,
-        objects: &mut Vec<Box<dyn Integrate>>,
+        t: T,
+        dt: T,
+        objects: &mut Vec<Box<dyn Integrate<T>>>,
     );
+
+    /// Serialize the full state (for checkpoints).
+    fn save_state(&self) -> GenericResult<Vec<u8>> {
+        return Ok(vec![]);
+    }
+
+    /// Restore the state serialized with `save_state`.
+    fn load_state(&mut self, _state: &[u8]) -> GenericResult<()> {
+        return Ok(());
+    }
 }
 
-pub struct TimeStepper<'a> {
-    gravity: Vector2,
-    density: Scalar,
+pub struct TimeStepper<'a, T: Float = Scalar> {
+    gravity: Vector2T<T>,
+    density: T,
     incompress_iters: u64,
 
-    t: Scalar,
+    t: T,
     execution_mode: ExecutionMode,
 
-    pub objects: Vec<Box<dyn Integrate>>,
-    pub manipulators: Vec<Box<dyn Manipulator>>,
+    pub objects: Vec<Box<dyn Integrate<T>>>,
+    pub manipulators: Vec<Box<dyn Manipulator<T>>>,
 
     log: &'a Logger,
 }
 
-#[derive(Copy, Clone)]
+/// The serialized state of a [`TimeStepper`] (see [`crate::scene::checkpoint`]).
+#[derive(Serialize, Deserialize)]
+#[serde(bound = "")] // Implied by `Float`.
+pub struct TimeStepperState<T: Float = Scalar> {
+    pub t: T,
+    pub gravity: Vector2T<T>,
+    pub density: T,
+    pub incompress_iters: u64,
+
+    /// The serialized states of all objects and manipulators (in order).
+    pub objects: Vec<Vec<u8>>,
+    pub manipulators: Vec<Vec<u8>>,
+}
+
+#[derive(Copy, Clone, Debug)]
 pub enum ExecutionMode {
     Single,
     Parallel,
     ParallelUnsafe,
+    /// Parallel over tiles of size `tile_size` which run `sweeps` solver
+    /// iterations before moving on (temporal blocking).
+    ParallelTiled { tile_size: Index2, sweeps: u64 },
 }
 
-impl<'a> TimeStepper<'a> {
+impl<'a, T: Float> TimeStepper<'a, T> {
     pub fn new(
         log: &'a Logger,
-        density: Scalar,
-        gravity: Vector2,
+        density: T,
+        gravity: Vector2T<T>,
         incompress_iters: u64,
         execution_mode: ExecutionMode,
-        objects: Vec<Box<dyn Integrate>>,
-        manipulators: Vec<Box<dyn Manipulator>>,
+        objects: Vec<Box<dyn Integrate<T>>>,
+        manipulators: Vec<Box<dyn Manipulator<T>>>,
     ) -> Self {
         return TimeStepper {
             log,
@@ -77,12 +133,12 @@ impl<'a> TimeStepper<'a> {
             execution_mode: execution_mode,
             objects,
             manipulators,
-            t: 0.0,
+            t: T::zero(),
         };
     }
 
-    pub fn compute_step(&mut self, dt: Scalar) {
-        if dt <= 0.0 {
+    pub fn compute_step(&mut self, dt: T) {
+        if dt <= T::zero() {
             panic!("Timestep is invalid.")
         }
 
@@ -97,19 +153,71 @@ impl<'a> TimeStepper<'a> {
         self.t = self.t + dt;
     }
 
+    pub fn t(&self) -> T {
+        return self.t;
+    }
+
+    pub fn density(&self) -> T {
+        return self.density;
+    }
+
+    pub fn save_state(&self) -> GenericResult<TimeStepperState<T>> {
+        return Ok(TimeStepperState {
+            t: self.t,
+            gravity: self.gravity,
+            density: self.density,
+            incompress_iters: self.incompress_iters,
+            objects: self
+                .objects
+                .iter()
+                .map(|o| o.save_state())
+                .collect::<GenericResult<_>>()?,
+            manipulators: self
+                .manipulators
+                .iter()
+                .map(|m| m.save_state())
+                .collect::<GenericResult<_>>()?,
+        });
+    }
+
+    /// Restore the state. The objects and manipulators must be
+    /// the same as the ones the state was saved from.
+    pub fn load_state(&mut self, state: TimeStepperState<T>) -> GenericResult<()> {
+        if state.objects.len() != self.objects.len()
+            || state.manipulators.len() != self.manipulators.len()
+        {
+            bail!("State does not match the objects and manipulators of the scene.");
+        }
+
+        for (obj, s) in self.objects.iter_mut().zip(state.objects.iter()) {
+            obj.load_state(s)?;
+        }
+
+        for (manip, s) in self.manipulators.iter_mut().zip(state.manipulators.iter()) {
+            manip.load_state(s)?;
+        }
+
+        self.t = state.t;
+        self.gravity = state.gravity;
+        self.density = state.density;
+        self.incompress_iters = state.incompress_iters;
+
+        return Ok(());
+    }
+
     fn reset(&mut self) {
         for obj in self.objects.iter_mut() {
             obj.reset(self.log);
         }
     }
 
-    fn manipulate(&mut self, t: Scalar, dt: Scalar) {
+    fn manipulate(&mut self, t: T, dt: T) {
         for manip in self.manipulators.iter_mut() {
             manip.manipulate(self.log, t, dt, &mut self.objects);
         }
     }
 
-    fn integrate(&mut self, dt: Scalar) {
+    fn integrate(&mut self, dt: T) {
         info!(
             self.log,
             "Integrate from t: '{:0.3}' -> '{:0.3}'.",
@@ -118,11 +226,11 @@ impl<'a> TimeStepper<'a> {
         );
 
         for obj in self.objects.iter_mut() {
-            obj.integrate(self.log, dt, self.gravity);
+            obj.integrate(self.log, dt, self.gravity, self.execution_mode);
         }
     }
 
-    fn solve_incompressibility(&mut self, dt: Scalar) {
+    fn solve_incompressibility(&mut self, dt: T) {
         info!(self.log, "Solve incompressibility at t: '{:0.3}'.", self.t,);
 
         for obj in self.objects.iter_mut() {
@@ -136,11 +244,18 @@ impl<'a> TimeStepper<'a> {
         }
     }
 
-    fn advect(&mut self, dt: Scalar) {
+    fn advect(&mut self, dt: T) {
         info!(self.log, "Advect at t: '{:0.3}'.", self.t,);
 
+        // Each object is taken out to pass it the other objects.
+        for i in 0..self.objects.len() {
+            let mut obj = self.objects.remove(i);
+            obj.advect_passive(self.log, dt, self.execution_mode, &self.objects);
+            self.objects.insert(i, obj);
+        }
+
         for obj in self.objects.iter_mut() {
-            obj.advect(self.log, dt);
+            obj.advect(self.log, dt, self.execution_mode);
         }
     }
 }
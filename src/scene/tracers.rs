@@ -0,0 +1,118 @@
+use crate::log::*;
+use crate::scene::grid::Grid;
+use crate::scene::streamlines::is_fluid;
+use crate::scene::timestepper::{ExecutionMode, Integrate};
+use crate::types::*;
+use serde::{Deserialize, Serialize};
+use std::any::Any;
+
+/// A massless marker particle with its previous positions (oldest first).
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(bound = "")] // Implied by `Float`.
+pub struct Particle<T: Float = Scalar> {
+    pub position: Vector2T<T>,
+    pub trail: Vec<Vector2T<T>>,
+}
+
+/// Passive tracer particles advected with the velocity of the grid (RK2).
+/// Particles entering solid cells or leaving the domain are removed.
+/// All positions are in world coordinates.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(bound = "")] // Implied by `Float`.
+pub struct Tracers<T: Float = Scalar> {
+    /// The positions where particles are seeded.
+    pub seeds: Vec<Vector2T<T>>,
+    /// Seed new particles every `emit_every` steps (`0`: only at the start).
+    pub emit_every: u64,
+    /// The number of previous positions kept per particle.
+    pub trail_length: usize,
+
+    pub particles: Vec<Particle<T>>,
+    steps: u64,
+}
+
+impl<T: Float> Tracers<T> {
+    pub fn new(seeds: Vec<Vector2T<T>>, emit_every: u64, trail_length: usize) -> Self {
+        return Tracers {
+            seeds,
+            emit_every,
+            trail_length,
+            particles: vec![],
+            steps: 0,
+        };
+    }
+
+    fn emit(&mut self) {
+        self.particles.extend(self.seeds.iter().map(|p| Particle {
+            position: *p,
+            trail: vec![],
+        }));
+    }
+
+    /// Move the particles with the explicit midpoint method (RK2) and
+    /// remove the ones outside of the fluid cells.
+    pub fn advect_particles(&mut self, grid: &Grid<T>, dt: T) {
+        let half = to_float::<T>(0.5);
+        let trail_length = self.trail_length;
+
+        for particle in self.particles.iter_mut() {
+            let p = particle.position;
+            let mid = p + grid.sample_velocity(p) * (half * dt);
+            particle.position = p + grid.sample_velocity(mid) * dt;
+
+            if trail_length > 0 {
+                if particle.trail.len() == trail_length {
+                    particle.trail.remove(0);
+                }
+                particle.trail.push(p);
+            }
+        }
+
+        self.particles
+            .retain(|particle| is_fluid(grid, particle.position / grid.cell_width));
+    }
+}
+
+impl<T: Float> Integrate<T> for Tracers<T> {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+
+    fn save_state(&self) -> GenericResult<Vec<u8>> {
+        return Ok(bincode::serialize(self)?);
+    }
+
+    fn load_state(&mut self, state: &[u8]) -> GenericResult<()> {
+        *self = bincode::deserialize(state)?;
+        return Ok(());
+    }
+
+    fn advect_passive(
+        &mut self,
+        log: &Logger,
+        dt: T,
+        _execution_mode: ExecutionMode,
+        objects: &[Box<dyn Integrate<T>>],
+    ) {
+        let grid = match objects
+            .iter()
+            .find_map(|o| o.as_any().downcast_ref::<Grid<T>>())
+        {
+            Some(grid) => grid,
+            None => return,
+        };
+
+        if self.steps == 0 || (self.emit_every > 0 && self.steps % self.emit_every == 0) {
+            self.emit();
+        }
+
+        self.advect_particles(grid, dt);
+        self.steps += 1;
+
+        debug!(log, "Advect {} tracer particles.", self.particles.len());
+    }
+}
@@ -1,12 +1,61 @@
 use crate::log::*;
 use crate::plotting;
-use crate::plotting::ColorFunction;
+use crate::plotting::{
+    AnimationParams, AnimationWriter, ColorFunction, Colorbar, Colormap, FrameStream, RangeMode,
+    RangeTracker, Renderer,
+};
 use crate::scene::grid::{CellGetter, Grid};
 use crate::scene::cell::CellTypes;
+use crate::scene::streamlines::{lic, streamlines, Seeds};
 use crate::scene::timestepper::TimeStepper;
+use crate::scene::tracers::Tracers;
 use crate::types::*;
 use colorgrad;
+use itertools::Itertools;
 use std::error::Error;
+use std::io::Write;
+
+/// The colormap and the value range of a field plot.
+#[derive(Clone, Debug)]
+pub struct FieldStyle {
+    pub colormap: Colormap,
+    pub range: RangeMode,
+    pub tracker: RangeTracker,
+}
+
+impl FieldStyle {
+    pub fn new(colormap: Colormap, range: RangeMode) -> Self {
+        return FieldStyle {
+            colormap,
+            range,
+            tracker: RangeTracker::default(),
+        };
+    }
+
+    /// Add the frame with the field values in `[min, max]` to the range tracker
+    /// and return the range of the frame. Call it once per frame in frame order.
+    pub fn update_range(&self, min: Scalar, max: Scalar) -> (Scalar, Scalar) {
+        return self.tracker.update(self.range, min, max);
+    }
+
+    /// The colorbar over the `range` of a frame (from `update_range`).
+    pub fn colorbar(&self, range: (Scalar, Scalar)) -> Result<Colorbar, Box<dyn Error>> {
+        return Ok(Colorbar {
+            gradient: self.colormap.gradient()?,
+            min: range.0,
+            max: range.1,
+        });
+    }
+}
+
+/// The ranges of the enabled field plots of one frame.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct FrameRanges {
+    pub velocity: Option<(Scalar, Scalar)>,
+    pub pressure: Option<(Scalar, Scalar)>,
+    pub vorticity: Option<(Scalar, Scalar)>,
+    pub divergence: Option<(Scalar, Scalar)>,
+}
 
 #[derive(Builder)]
 #[builder(pattern = "mutable")]
@@ -26,11 +75,85 @@ pub struct PlotParams {
     #[builder(default)]
     pub with_pressure_masked: bool,
 
+    #[builder(default)]
+    pub with_vorticity: bool,
+
+    #[builder(default)]
+    pub with_vorticity_masked: bool,
+
+    #[builder(default)]
+    pub with_divergence: bool,
+
+    #[builder(default)]
+    pub with_divergence_masked: bool,
+
+    #[builder(default = "FieldStyle::new(Colormap::Turbo, RangeMode::Auto)")]
+    pub velocity_style: FieldStyle,
+
+    #[builder(default = "FieldStyle::new(Colormap::Turbo, RangeMode::Auto)")]
+    pub pressure_style: FieldStyle,
+
+    #[builder(default = "FieldStyle::new(Colormap::Coolwarm, RangeMode::Symmetric)")]
+    pub vorticity_style: FieldStyle,
+
+    #[builder(default = "FieldStyle::new(Colormap::Coolwarm, RangeMode::Symmetric)")]
+    pub divergence_style: FieldStyle,
+
+    /// Draw a colorbar next to the field plots.
+    #[builder(default)]
+    pub with_colorbar: bool,
+
+    /// The number of tick labels of the colorbar.
+    #[builder(default = "5")]
+    pub colorbar_ticks: usize,
+
     #[builder(default = "idx!(800,400)")]
     pub size: Index2,
 
+    /// Combine the plots into one image with this layout (columns, rows).
+    #[builder(default)]
+    pub layout: Option<Index2>,
+
+    /// The plots in the layout (row-major), all plots if empty.
+    #[builder(default)]
+    pub panels: Vec<String>,
+
     #[builder(default)]
     pub output: String,
+
+    #[builder(default = "Renderer::Plotters")]
+    pub renderer: Renderer,
+
+    /// Overlay velocity arrows on a lattice with this spacing (in cells).
+    #[builder(default)]
+    pub quiver: Option<usize>,
+
+    /// The length of the arrow of the max. velocity (in lattice spacings).
+    #[builder(default = "1.0")]
+    pub quiver_scale: Scalar,
+
+    /// Clamp the arrow lengths (in lattice spacings).
+    #[builder(default)]
+    pub quiver_clamp: Option<Scalar>,
+
+    /// Plot streamlines (traced with RK4).
+    #[builder(default)]
+    pub with_streamlines: bool,
+
+    #[builder(default = "Seeds::Lattice(16)")]
+    pub streamline_seeds: Seeds,
+
+    /// Plot the line integral convolution (LIC) of the velocity.
+    #[builder(default)]
+    pub with_lic: bool,
+
+    /// Color the LIC texture by the velocity magnitude.
+    #[builder(default)]
+    pub lic_colored: bool,
+
+    /// The LIC kernel length in each direction (in cells).
+    #[builder(default = "10")]
+    pub lic_length: usize,
 }
 
 fn make_solid<'a>(
@@ -39,7 +162,7 @@ fn make_solid<'a>(
     f: &'a impl ColorFunction,
 ) -> impl ColorFunction + 'a {
     return |idx: Index2| {
-        if grid.cell(idx).mode == CellTypes::Solid {
+        if grid.cell(idx).mode() == CellTypes::Solid {
             return solid_color.clone();
         }
         return f(idx);
@@ -49,68 +172,188 @@ fn make_solid<'a>(
 fn make_masked<'a>(grid: &'a Grid, f: &'a impl ColorFunction) -> impl ColorFunction + 'a {
     return |idx: Index2| {
         let mut c = f(idx);
-        c.a *= grid.cell(idx).smoke.back;
+        c.a *= to_float::<f64>(grid.cell(idx).smoke());
         return c;
     };
 }
 
+/// The value range `(min, max)` of the vorticity in the fluid cells.
+fn vorticity_range(grid: &Grid, vorticity: &[Scalar]) -> (Scalar, Scalar) {
+    return grid
+        .iter_index()
+        .filter(|idx| grid.cell(*idx).mode() != CellTypes::Solid)
+        .map(|idx| vorticity[idx.x + idx.y * grid.dim.x])
+        .fold((0.0, 0.0), |(min, max), w| (min.min(w), max.max(w)));
+}
+
+/// Whether a plot uses the velocity range (the velocity plot or the colored LIC).
+fn with_velocity_range(params: &PlotParams) -> bool {
+    return params.with_velocity || (params.with_lic && params.lic_colored);
+}
+
+/// The ranges of the enabled field plots of the frame `grid`. This advances the
+/// range trackers (modes `Global` and `Smoothed`), such that it is called exactly
+/// once per frame and in frame order, also if the frames are rendered in parallel.
+pub fn frame_ranges(grid: &Grid, params: &PlotParams) -> FrameRanges {
+    let update = |enabled: bool, style: &FieldStyle, range: &dyn Fn() -> (Scalar, Scalar)| {
+        if !enabled {
+            return None;
+        }
+        let (min, max) = range();
+        return Some(style.update_range(min, max));
+    };
+
+    let stats = &grid.stats;
+    return FrameRanges {
+        velocity: update(
+            with_velocity_range(params),
+            &params.velocity_style,
+            &|| (stats[0].velocity_norm, stats[1].velocity_norm),
+        ),
+        pressure: update(params.with_pressure, &params.pressure_style, &|| {
+            (stats[0].pressure, stats[1].pressure)
+        }),
+        vorticity: update(params.with_vorticity, &params.vorticity_style, &|| {
+            vorticity_range(grid, &grid.compute_vorticity())
+        }),
+        divergence: update(params.with_divergence, &params.divergence_style, &|| {
+            (stats[0].div, stats[1].div)
+        }),
+    };
+}
+
+/// Extend the ranges of the enabled field plots with mode `Global` by the
+/// values of `grid`, to get global ranges over all frames before rendering.
+pub fn include_global_ranges(grid: &Grid, params: &PlotParams) {
+    let include = |enabled: bool, style: &FieldStyle, range: &dyn Fn() -> (Scalar, Scalar)| {
+        if enabled && matches!(style.range, RangeMode::Global { .. }) {
+            let (min, max) = range();
+            let (min, max) = style.range.range(min, max);
+            style.tracker.include(min, max);
+        }
+    };
+
+    let stats = &grid.stats;
+    include(
+        with_velocity_range(params),
+        &params.velocity_style,
+        &|| (stats[0].velocity_norm, stats[1].velocity_norm),
+    );
+    include(params.with_pressure, &params.pressure_style, &|| {
+        (stats[0].pressure, stats[1].pressure)
+    });
+    include(params.with_vorticity, &params.vorticity_style, &|| {
+        vorticity_range(grid, &grid.compute_vorticity())
+    });
+    include(params.with_divergence, &params.divergence_style, &|| {
+        (stats[0].div, stats[1].div)
+    });
+}
+
+fn get_grid<'a>(timestepper: &'a TimeStepper) -> &'a Grid {
+    return timestepper.objects[0]
+        .as_any()
+        .downcast_ref::<Grid>()
+        .expect("Not a grid");
+}
+
+fn get_tracers<'a>(timestepper: &'a TimeStepper) -> Option<&'a Tracers> {
+    return timestepper
+        .objects
+        .iter()
+        .find_map(|o| o.as_any().downcast_ref::<Tracers>());
+}
+
 pub fn save_plots(
     log: &Logger,
     timestepper: &TimeStepper,
     step: u64,
     params: &PlotParams,
 ) -> Result<(), Box<dyn Error>> {
-    info!(log, "Saving plots.");
+    let images = render_plots(timestepper, step, params)?;
+    return save_rendered_plots(log, &images, step, params);
+}
 
-    let grid = timestepper.objects[0]
-        .as_any()
-        .downcast_ref::<Grid>()
-        .expect("Not a grid");
+/// The title with the frame statistics (if enabled).
+fn stats_text(grid: &Grid, step: u64, params: &PlotParams) -> Option<String> {
+    if !params.with_stats {
+        return None;
+    }
 
-    let cg: colorgrad::Gradient = colorgrad::turbo();
-    let solid_color = colorgrad::Color::new(0.2, 0.2, 0.2, 1.0);
+    return Some(format!(
+        "frame: {:5.0}, pressure: [{:.3} , {:.3}], div: [{:.3} , {:.3}], vel: [{:.3} , {:.3}]",
+        step,
+        grid.stats[0].pressure,
+        grid.stats[1].pressure,
+        grid.stats[0].div,
+        grid.stats[1].div,
+        grid.stats[0].velocity_norm,
+        grid.stats[1].velocity_norm
+    ));
+}
 
-    let text = if params.with_stats {
-        Some(format!(
-            "frame: {:5.0}, pressure: [{:.3} , {:.3}], div: [{:.3} , {:.3}], vel: [{:.3} , {:.3}]",
-            step,
-            grid.stats[0].pressure,
-            grid.stats[1].pressure,
-            grid.stats[0].div,
-            grid.stats[1].div,
-            grid.stats[0].velocity_norm,
-            grid.stats[1].velocity_norm
-        ))
-    } else {
-        None
+/// The title of a plot in a composite frame.
+fn panel_title(name: &str) -> &'static str {
+    return match name {
+        "smoke" => "smoke",
+        "vel" => "velocity",
+        "press" => "pressure",
+        "vort" => "vorticity",
+        "div" => "divergence",
+        "stream" => "streamlines",
+        "lic" => "LIC",
+        _ => "",
     };
+}
+
+/// The range of an enabled field plot (computed with the same `PlotParams`).
+fn frame_range(range: Option<(Scalar, Scalar)>) -> Result<(Scalar, Scalar), Box<dyn Error>> {
+    return range.ok_or_else(|| "Missing the frame range of an enabled plot.".into());
+}
 
-    let mut file = params.output.replace("{}", &format!("smoke-{:06}", step));
+/// Call `f` with the name, the color function, the colorbar (of the field plots)
+/// and the title of each enabled plot.
+fn for_each_plot(
+    grid: &Grid,
+    step: u64,
+    params: &PlotParams,
+    ranges: &FrameRanges,
+    mut f: impl FnMut(
+        &'static str,
+        &dyn ColorFunction,
+        Option<&Colorbar>,
+        Option<&str>,
+    ) -> Result<(), Box<dyn Error>>,
+) -> Result<(), Box<dyn Error>> {
+    let cg: colorgrad::Gradient = colorgrad::turbo();
+    let solid_color = colorgrad::Color::new(0.2, 0.2, 0.2, 1.0);
+
+    let text = stats_text(grid, step, params);
 
     let smoke_color: &dyn plotting::ColorFunction = &|idx: Index2| {
-        let alpha = grid.cell(idx).smoke.back;
+        let alpha = to_float::<f64>(grid.cell(idx).smoke());
         let mut color = cg.at(0.6 * alpha);
         color.a = alpha;
         return color;
     };
 
-    plotting::grid(
-        params.size,
-        grid.dim,
-        make_solid(&grid, &solid_color, &smoke_color),
-        file,
+    f(
+        "smoke",
+        &make_solid(&grid, &solid_color, &smoke_color),
+        None,
         text.as_deref(),
     )?;
 
-    if params.with_velocity {
-        file = params.output.replace("{}", &format!("vel-{:06}", step));
-        let cg: colorgrad::Gradient = colorgrad::turbo();
+    // Shared by the velocity and the colored LIC plot.
+    let velocity_colorbar = with_velocity_range(params)
+        .then(|| params.velocity_style.colorbar(frame_range(ranges.velocity)?))
+        .transpose()?;
 
-        let v_range = grid.stats[1].velocity_norm - grid.stats[0].velocity_norm;
+    if params.with_velocity {
+        let colorbar = velocity_colorbar.as_ref().unwrap();
 
         let get_color = |idx: Index2| {
-            let t = grid.cell(idx).velocity.back.norm() - grid.stats[0].velocity_norm / v_range;
-            return cg.at(t);
+            return colorbar.at(grid.cell(idx).velocity().norm());
         };
 
         let get_color_masked = make_masked(&grid, &get_color);
@@ -121,42 +364,509 @@ pub fn save_plots(
             &get_color
         };
 
-        plotting::grid(
-            params.size,
-            grid.dim,
-            make_solid(&grid, &solid_color, &velocity_color),
-            file,
+        f(
+            "vel",
+            &make_solid(&grid, &solid_color, &velocity_color),
+            Some(colorbar),
             text.as_deref(),
         )?;
     }
 
     if params.with_pressure {
-        let cg: colorgrad::Gradient = colorgrad::turbo();
-
-        let p_range = grid.stats[1].pressure - grid.stats[0].pressure;
+        let colorbar = params.pressure_style.colorbar(frame_range(ranges.pressure)?)?;
 
         let get_color: &dyn ColorFunction = &|idx: Index2| {
-            let t = (grid.cell(idx).pressure - grid.stats[0].pressure) / p_range;
-            return cg.at(t);
+            return colorbar.at(grid.cell(idx).pressure());
         };
 
         let get_color_masked = make_masked(&grid, &get_color);
 
-        let pressure_color: &dyn plotting::ColorFunction = if params.with_velocity_masked {
+        let pressure_color: &dyn plotting::ColorFunction = if params.with_pressure_masked {
+            &get_color_masked
+        } else {
+            &get_color
+        };
+
+        f(
+            "press",
+            &make_solid(&grid, &solid_color, &pressure_color),
+            Some(&colorbar),
+            text.as_deref(),
+        )?;
+    }
+
+    if params.with_vorticity {
+        let vorticity = grid.compute_vorticity();
+        let colorbar = params.vorticity_style.colorbar(frame_range(ranges.vorticity)?)?;
+
+        let get_color = |idx: Index2| {
+            return colorbar.at(vorticity[idx.x + idx.y * grid.dim.x]);
+        };
+
+        let get_color_masked = make_masked(grid, &get_color);
+
+        let vorticity_color: &dyn ColorFunction = if params.with_vorticity_masked {
+            &get_color_masked
+        } else {
+            &get_color
+        };
+
+        f(
+            "vort",
+            &make_solid(grid, &solid_color, &vorticity_color),
+            Some(&colorbar),
+            text.as_deref(),
+        )?;
+    }
+
+    if params.with_divergence {
+        let colorbar = params.divergence_style.colorbar(frame_range(ranges.divergence)?)?;
+
+        let get_color = |idx: Index2| {
+            return colorbar.at(grid.cell(idx).div());
+        };
+
+        let get_color_masked = make_masked(grid, &get_color);
+
+        let divergence_color: &dyn ColorFunction = if params.with_divergence_masked {
             &get_color_masked
         } else {
             &get_color
         };
 
-        file = params.output.replace("{}", &format!("press-{:06}", step));
-        plotting::grid(
-            params.size,
-            grid.dim,
-            make_solid(&grid, &solid_color, &pressure_color),
-            file,
+        f(
+            "div",
+            &make_solid(grid, &solid_color, &divergence_color),
+            Some(&colorbar),
+            text.as_deref(),
+        )?;
+    }
+
+    if params.with_streamlines {
+        // The streamlines are drawn onto this plot in `for_each_image`.
+        let background = |_: Index2| colorgrad::Color::new(0.0, 0.0, 0.0, 1.0);
+        f(
+            "stream",
+            &make_solid(grid, &solid_color, &background),
+            None,
+            text.as_deref(),
+        )?;
+    }
+
+    if params.with_lic {
+        let intensity = lic(grid, params.lic_length);
+        let colorbar = velocity_colorbar.as_ref().filter(|_| params.lic_colored);
+
+        let get_color = |idx: Index2| {
+            let t = intensity[idx.x + idx.y * grid.dim.x];
+
+            return match colorbar {
+                Some(colorbar) => {
+                    let mut c = colorbar.at(grid.cell(idx).velocity().norm());
+                    c.a = t;
+                    c
+                }
+                None => colorgrad::Color::new(t, t, t, 1.0),
+            };
+        };
+
+        f(
+            "lic",
+            &make_solid(grid, &solid_color, &get_color),
+            colorbar,
             text.as_deref(),
         )?;
     }
 
     return Ok(());
 }
+
+/// The velocity arrows `(start, end)` in cell coordinates on the quiver lattice
+/// (skipping solid cells).
+fn quiver_arrows(grid: &Grid, params: &PlotParams) -> Vec<(Vector2, Vector2)> {
+    let spacing = match params.quiver {
+        Some(s) if s > 0 => s,
+        _ => return vec![],
+    };
+
+    let v_max = grid.stats[1].velocity_norm;
+    if v_max.is_nan() || v_max <= 0.0 {
+        return vec![];
+    }
+
+    let h = grid.cell_width;
+    let lattice = |n: usize| (spacing / 2..n - 1).step_by(spacing).filter(|i| *i >= 1);
+
+    return lattice(grid.dim.x)
+        .cartesian_product(lattice(grid.dim.y).collect::<Vec<_>>())
+        .filter(|(i, j)| grid.cell(idx!(*i, *j)).mode() != CellTypes::Solid)
+        .filter_map(|(i, j)| {
+            let start = vec2!(i as Scalar + 0.5, j as Scalar + 0.5);
+            let v = grid.sample_velocity(start * h);
+            let v_norm = v.norm();
+            if v_norm == 0.0 {
+                return None;
+            }
+
+            let mut length = params.quiver_scale * v_norm / v_max;
+            if let Some(clamp) = params.quiver_clamp {
+                length = length.min(clamp);
+            }
+
+            return Some((start, start + v * (length * spacing as Scalar / v_norm)));
+        })
+        .collect();
+}
+
+/// Call `f` with the name and the rendered image (with overlays) of each enabled plot.
+/// With a panel layout the panel plots are combined into the plot `panels`.
+/// The `tracers` are drawn on the smoke plot.
+fn for_each_image(
+    grid: &Grid,
+    tracers: Option<&Tracers>,
+    step: u64,
+    params: &PlotParams,
+    ranges: &FrameRanges,
+    mut f: impl FnMut(&'static str, plotting::Image) -> Result<(), Box<dyn Error>>,
+) -> Result<(), Box<dyn Error>> {
+    let arrows = quiver_arrows(grid, params);
+    let arrow_color = colorgrad::Color::new(1.0, 1.0, 1.0, 0.8);
+
+    let lines = if params.with_streamlines {
+        streamlines(grid, params.streamline_seeds, 0.5, 2 * grid.dim.x)
+    } else {
+        vec![]
+    };
+
+    let is_panel = |name: &str| {
+        return params.layout.is_some()
+            && (params.panels.is_empty() || params.panels.iter().any(|p| p == name));
+    };
+    let mut panels = vec![];
+
+    for_each_plot(grid, step, params, ranges, |name, color, colorbar, text| {
+        let (size, text) = match params.layout {
+            Some(layout) if is_panel(name) => (
+                idx!(params.size.x / layout.x.max(1), params.size.y),
+                Some(panel_title(name)),
+            ),
+            _ => (params.size, text),
+        };
+
+        let mut image = plotting::render_grid(params.renderer, size, grid.dim, color, text)?;
+
+        if name == "stream" {
+            let line_color = colorgrad::Color::new(1.0, 1.0, 1.0, 0.9);
+            plotting::draw_paths(&mut image, grid.dim, &lines, line_color, text)?;
+        }
+
+        if let (true, Some(tracers)) = (name == "smoke", tracers) {
+            let to_cell = |p: &Vector2| p / grid.cell_width;
+            let tracer_color = colorgrad::Color::new(1.0, 0.9, 0.3, 1.0);
+
+            let trails = tracers
+                .particles
+                .iter()
+                .map(|p| p.trail.iter().chain([&p.position]).map(to_cell).collect())
+                .collect::<Vec<_>>();
+            let points = tracers.particles.iter().map(|p| to_cell(&p.position));
+
+            plotting::draw_trails(&mut image, grid.dim, &trails, tracer_color.clone(), text)?;
+            plotting::draw_points(
+                &mut image,
+                grid.dim,
+                &points.collect::<Vec<_>>(),
+                2,
+                tracer_color,
+                text,
+            )?;
+        }
+
+        if !arrows.is_empty() {
+            plotting::draw_arrows(&mut image, grid.dim, &arrows, arrow_color.clone(), text)?;
+        }
+
+        if let (true, Some(colorbar)) = (params.with_colorbar, colorbar) {
+            image = plotting::draw_colorbar(&image, colorbar, params.colorbar_ticks, text)?;
+        }
+
+        if is_panel(name) {
+            panels.push((name, image));
+            return Ok(());
+        }
+
+        return f(name, image);
+    })?;
+
+    if let Some(layout) = params.layout {
+        // The panels in the order of `params.panels`.
+        let mut ordered = vec![];
+        for panel in params.panels.iter() {
+            match panels.iter().find(|(name, _)| name == panel) {
+                Some((_, image)) => ordered.push(image),
+                None => bail!("Panel '{}' is not enabled.", panel),
+            }
+        }
+        if params.panels.is_empty() {
+            ordered = panels.iter().map(|(_, image)| image).collect();
+        }
+
+        let text = stats_text(grid, step, params);
+        f(
+            "panels",
+            plotting::composite(&ordered, layout, text.as_deref())?,
+        )?;
+    }
+
+    return Ok(());
+}
+
+/// Save the plots of the grid fields (also used to render stored fields).
+pub fn save_grid_plots(
+    log: &Logger,
+    grid: &Grid,
+    step: u64,
+    params: &PlotParams,
+) -> Result<(), Box<dyn Error>> {
+    let images = render_grid_plots(grid, step, params)?;
+    return save_rendered_plots(log, &images, step, params);
+}
+
+/// Save the rendered plots of one step as `.png` files (named by `params.output`),
+/// such that a frame is rendered once for all outputs.
+pub fn save_rendered_plots(
+    log: &Logger,
+    images: &[(&'static str, plotting::Image)],
+    step: u64,
+    params: &PlotParams,
+) -> Result<(), Box<dyn Error>> {
+    info!(log, "Saving plots.");
+
+    for (name, image) in images {
+        let file = params
+            .output
+            .replace("{}", &format!("{}-{:06}", name, step));
+        plotting::save_png(image, &file)?;
+    }
+
+    return Ok(());
+}
+
+/// Render the plots of the grid fields into images (named `smoke`, `vel`, `press`,
+/// `vort`, `div`, `stream`, `lic` and `panels`).
+pub fn render_grid_plots(
+    grid: &Grid,
+    step: u64,
+    params: &PlotParams,
+) -> Result<Vec<(&'static str, plotting::Image)>, Box<dyn Error>> {
+    return render_images(grid, None, step, params, &frame_ranges(grid, params));
+}
+
+/// Render the plots of the grid fields with the `ranges` of the frame (from
+/// `frame_ranges` in frame order), e.g. to render the frames in parallel.
+pub fn render_grid_plots_in_ranges(
+    grid: &Grid,
+    step: u64,
+    params: &PlotParams,
+    ranges: &FrameRanges,
+) -> Result<Vec<(&'static str, plotting::Image)>, Box<dyn Error>> {
+    return render_images(grid, None, step, params, ranges);
+}
+
+fn render_images(
+    grid: &Grid,
+    tracers: Option<&Tracers>,
+    step: u64,
+    params: &PlotParams,
+    ranges: &FrameRanges,
+) -> Result<Vec<(&'static str, plotting::Image)>, Box<dyn Error>> {
+    let mut images = vec![];
+
+    for_each_image(grid, tracers, step, params, ranges, |name, image| {
+        images.push((name, image));
+        return Ok(());
+    })?;
+
+    return Ok(images);
+}
+
+pub fn render_plots(
+    timestepper: &TimeStepper,
+    step: u64,
+    params: &PlotParams,
+) -> Result<Vec<(&'static str, plotting::Image)>, Box<dyn Error>> {
+    let grid = get_grid(timestepper);
+    let ranges = frame_ranges(grid, params);
+    return render_images(grid, get_tracers(timestepper), step, params, &ranges);
+}
+
+/// One animation file per plot, the file name is the output pattern with
+/// `{}` replaced by the plot name.
+pub struct PlotAnimations {
+    output: String,
+    params: AnimationParams,
+    writers: Vec<(&'static str, AnimationWriter)>,
+}
+
+impl PlotAnimations {
+    pub fn new(output: String, params: AnimationParams) -> Self {
+        return PlotAnimations {
+            output,
+            params,
+            writers: vec![],
+        };
+    }
+
+    /// Append the rendered plots of one step.
+    pub fn add_frames(
+        &mut self,
+        log: &Logger,
+        images: &[(&'static str, plotting::Image)],
+    ) -> GenericResult<()> {
+        for (name, image) in images {
+            let i = match self.writers.iter().position(|(n, _)| n == name) {
+                Some(i) => i,
+                None => {
+                    let file = format!(
+                        "{}.{}",
+                        self.output.replace("{}", name),
+                        self.params.format.extension()
+                    );
+                    info!(log, "Write animation '{}'.", file);
+                    self.writers
+                        .push((name, AnimationWriter::new(file, self.params.clone())));
+                    self.writers.len() - 1
+                }
+            };
+
+            self.writers[i].1.add_frame(image)?;
+        }
+
+        return Ok(());
+    }
+
+    pub fn finish(self) -> GenericResult<()> {
+        for (_, w) in self.writers {
+            w.finish()?;
+        }
+
+        return Ok(());
+    }
+}
+
+/// Streams the frames of one plot (e.g. `smoke`) to an encoder.
+pub struct PlotStream {
+    plot: String,
+    stream: FrameStream,
+}
+
+impl PlotStream {
+    pub fn new(plot: String, stream: FrameStream) -> Self {
+        return PlotStream { plot, stream };
+    }
+
+    /// Write the frame of the streamed plot from the rendered plots of one step.
+    pub fn add_frames(
+        &mut self,
+        log: &Logger,
+        images: &[(&'static str, plotting::Image)],
+    ) -> GenericResult<()> {
+        let image = match images.iter().find(|(name, _)| *name == self.plot) {
+            Some((_, image)) => image,
+            None => bail!("Plot '{}' to stream is not enabled.", self.plot),
+        };
+
+        if self.stream.size().is_none() {
+            info!(
+                log,
+                "Stream plot '{}' with size '{}x{}'.", self.plot, image.size.x, image.size.y
+            );
+        }
+
+        return self.stream.write_frame(image);
+    }
+
+    pub fn finish(mut self) -> GenericResult<()> {
+        return self.stream.flush();
+    }
+}
+
+/// Draws one plot (e.g. `smoke`) as a live preview into the terminal (`stderr`)
+/// with Unicode half blocks and 24-bit ANSI colors.
+pub struct TerminalPreview {
+    plot: String,
+    size: Option<Index2>,
+    params: PlotParams,
+    frames: u64,
+}
+
+impl TerminalPreview {
+    /// The preview uses its own `params` (only the previewed plot is enabled),
+    /// such that it does not advance the ranges of the saved plots.
+    pub fn new(plot: String, size: Option<Index2>, mut params: PlotParams) -> Self {
+        params.with_velocity = plot == "vel";
+        params.with_pressure = plot == "press";
+        params.with_vorticity = plot == "vort";
+        params.with_divergence = plot == "div";
+        params.with_streamlines = false;
+        params.with_lic = plot == "lic";
+
+        return TerminalPreview {
+            plot,
+            size,
+            params,
+            frames: 0,
+        };
+    }
+
+    /// Render the previewed plot into at most `size` characters (columns, rows).
+    pub fn render(&self, grid: &Grid, step: u64, size: Index2) -> GenericResult<String> {
+        let mut frame = None;
+
+        let ranges = frame_ranges(grid, &self.params);
+        for_each_plot(grid, step, &self.params, &ranges, |name, color, _, _| {
+            if name == self.plot {
+                frame = Some(plotting::grid_terminal(size, grid.dim, color));
+            }
+            return Ok(());
+        })?;
+
+        return match frame {
+            Some(frame) => Ok(frame),
+            None => bail!("Plot '{}' cannot be previewed.", self.plot),
+        };
+    }
+
+    /// Draw the frame of one step with a status line below.
+    pub fn show(
+        &mut self,
+        timestepper: &TimeStepper,
+        step: u64,
+        n_steps: u64,
+    ) -> GenericResult<()> {
+        let grid = get_grid(timestepper);
+
+        // Leave one row for the status line.
+        let size = self.size.unwrap_or_else(plotting::terminal_size);
+        let frame = self.render(grid, step, idx!(size.x, size.y.saturating_sub(1)))?;
+
+        let mut out = std::io::stderr().lock();
+        if self.frames == 0 {
+            // Clear the screen once, later frames overwrite it.
+            write!(out, "\x1b[2J")?;
+        }
+        writeln!(
+            out,
+            "\x1b[H{}{}: step {}/{}, t = {:.3}\x1b[K",
+            frame,
+            self.plot,
+            step + 1,
+            n_steps,
+            timestepper.t()
+        )?;
+        out.flush()?;
+
+        self.frames += 1;
+        return Ok(());
+    }
+}
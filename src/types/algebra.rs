@@ -1,6 +1,23 @@
 use nalgebra;
+use num_traits;
 
+/// The float type the simulation is compiled with.
+/// Enable the cargo feature `f32` to run in single precision.
+#[cfg(not(feature = "f32"))]
 pub type Scalar = f64;
+#[cfg(feature = "f32")]
+pub type Scalar = f32;
+
+/// Float trait all simulation types (cells, grid, stats, solver)
+/// are generic over (`f32` or `f64`).
+pub trait Float = nalgebra::RealField
+    + num_traits::Float
+    + Copy
+    + Send
+    + Sync
+    + serde::Serialize
+    + serde::de::DeserializeOwned;
+
 pub type Vector2 = nalgebra::Vector2<Scalar>;
 pub type Matrix2 = nalgebra::Matrix2<Scalar>;
 pub type Matrix1x2 = nalgebra::Matrix1x2<Scalar>;
@@ -14,10 +31,22 @@ pub type Matrix2x1T<T> = nalgebra::Matrix2x1<T>;
 pub type Index2 = nalgebra::Vector2<usize>;
 pub type Index2T<T> = nalgebra::Vector2<T>;
 
+/// Convert a primitive number (e.g. a literal) to the float type `T`.
+#[inline(always)]
+pub fn to_float<T: Float>(v: impl num_traits::ToPrimitive) -> T {
+    return <T as num_traits::NumCast>::from(v).expect("Number not representable.");
+}
+
+/// Convert an index to a vector of float type `T`.
+#[inline(always)]
+pub fn to_vec2<T: Float>(index: Index2) -> Vector2T<T> {
+    return index.map(|i| to_float(i));
+}
+
 #[macro_export]
 macro_rules! vec2 {
     ($x:expr, $($y:expr),+ ) => {
-        Vector2::new($x, $($y),+)
+        $crate::types::Vector2T::new($x, $($y),+)
     };
 }
 
@@ -1,15 +1,27 @@
-#[derive(Clone, Debug)]
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct FrontBackBuffer<T> {
     pub front: T, // Back buffer.
     pub back: T,  // Front buffer.
 }
 
-impl<T> FrontBackBuffer<T>  where T: Copy{
+impl<T> FrontBackBuffer<T> {
     pub fn swap(&mut self) {
-        //std::mem::swap(&mut self.front, &mut self.back);
+        std::mem::swap(&mut self.front, &mut self.back);
+    }
+}
 
-        let temp = self.back;
-        self.back = self.front;
-        self.front = temp;
+impl<T> FrontBackBuffer<Vec<T>>
+where
+    T: Copy + Send + Sync,
+{
+    /// Copy the back buffer into the front buffer (parallelized).
+    pub fn copy_back_to_front(&mut self) {
+        self.front
+            .par_iter_mut()
+            .zip(self.back.par_iter())
+            .for_each(|(f, b)| *f = *b);
     }
 }
@@ -19,7 +19,7 @@ scripts="$ROOT_DIR/profile"
 cd "$DIR"
 cargo build --profile release-bench --bin rsfluid
 
-nohup target/release-bench/rsfluid -e 10.0 -t 0.016 --incompress-iters=100 &>/dev/null &
+nohup target/release-bench/rsfluid simulate -e 10.0 -t 0.016 --incompress-iters=100 &>/dev/null &
 sleep 2
 pid=$(pgrep rsfluid)
 
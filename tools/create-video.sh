@@ -12,7 +12,7 @@ shift 1
 timestep=$(echo "scale=3; 1.0/$frameRate" | bc)
 frameRateVideo="$frameRate"
 
-cargo run --release --bin rsfluid -- \
+cargo run --release --bin rsfluid -- simulate \
     -e 15.0 \
     -t "$timestep" \
     --show-progress \